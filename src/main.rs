@@ -1,25 +1,122 @@
+#![allow(clippy::needless_return)]
+
 mod model;
 
-use gloo_events::EventListener;
-use model::shape::{ShapeState, ShapeStorage, ShapeType};
-use wasm_bindgen::{JsCast, prelude::Closure};
+use gloo_events::{EventListener, EventListenerOptions};
+use model::{
+    boolean::{combine_shapes, is_combinable, BooleanOp},
+    clip::{outcode_bits, ClipAlgorithm, ClipWindow, Segment},
+    clipboard::{from_clipboard, place, to_clipboard},
+    document::{Canvas, Document},
+    fill::Fill,
+    group::Group,
+    history::{Command, History},
+    layer::on_layer,
+    polygon_clip::clip_shape,
+    raster::{to_pixel, Connectivity, Framebuffer, Rgba},
+    renderer::{CanvasRenderer, Renderer},
+    shape::{Shape, ShapeState, ShapeStorage, ShapeType, ZOrder, HIT_TOLERANCE},
+    snap::{snap, SnapKind, SnapSettings, SnapTarget},
+    style::{is_valid_prop_value, prop_input_type},
+    svg_import::import_svg,
+    transform::Transform,
+    viewport::Viewport,
+};
+use wasm_bindgen::{closure::Closure, Clamped, JsCast, JsValue};
 use web_sys::*;
 use yew::prelude::*;
 
+const ANGLE_INPUT_ID: &str = "rotation-angle";
+const PIVOT_X_INPUT_ID: &str = "pivot-x";
+const PIVOT_Y_INPUT_ID: &str = "pivot-y";
+const SCALE_X_INPUT_ID: &str = "scale-x";
+const SCALE_Y_INPUT_ID: &str = "scale-y";
+const SKEW_X_INPUT_ID: &str = "skew-x";
+const SKEW_Y_INPUT_ID: &str = "skew-y";
+const PNG_SCALE_INPUT_ID: &str = "png-scale";
+const PNG_WIDTH_INPUT_ID: &str = "png-width";
+const FILL_COLOR_INPUT_ID: &str = "fill-color";
+const FILL_TOLERANCE_INPUT_ID: &str = "fill-tolerance";
+const FLATTEN_TOLERANCE_INPUT_ID: &str = "flatten-tolerance";
+const GRID_SPACING_INPUT_ID: &str = "grid-spacing";
+const HISTORY_DEPTH: usize = 100;
+// browsers refuse to encode larger canvases
+const PNG_MAX_SIZE: f64 = 16384.0;
+const SELECTION_COLOR: Rgba = [255, 0, 0, 255];
+const INSPECTOR_SIZE: u32 = 160;
+const INSPECTOR_ZOOM: u32 = 8;
+// opacity factor of the parts of lines outside the clip window
+const CLIPPED_OPACITY: f64 = 0.25;
+// zoom factor of one step of the mouse wheel
+const ZOOM_STEP: f64 = 1.1;
+const MIDDLE_BUTTON: i16 = 1;
+// how close the pointer snaps to points of shapes, in screen pixels
+const SNAP_RADIUS: f64 = 8.0;
+const SNAP_COLOR: &str = "#ff8000";
+const GRID_COLOR: &str = "#e0e0e0";
+// grid lines closer than this on the screen are left out
+const MIN_GRID_GAP: f64 = 4.0;
+
 #[derive(Debug, Clone, PartialEq)]
 enum Msg {
     ShapeChanged { shape_type: ShapeType },
     ModeChanged { mode: Mode },
-    MouseClicked { x: f64, y: f64 },
+    RenderModeChanged { render_mode: RenderMode },
+    MouseClicked { x: f64, y: f64, shift: bool },
     MouseMove { x: f64, y: f64 },
-    MouseUp { x: f64, y: f64 },
-    MouseDown { x: f64, y: f64 },
+    MouseUp { x: f64, y: f64, shift: bool, alt: bool },
+    MouseDown { x: f64, y: f64, button: i16 },
+    Wheel { x: f64, y: f64, delta: f64 },
+    KeyPressed { key: String, ctrl: bool, shift: bool },
+    KeyReleased { key: String },
+    ResetView,
+    GridToggled,
+    GridSpacingChanged { value: String },
+    SnapToggled { kind: SnapKind },
+    LayerAdded,
+    LayerRemoved { layer: usize },
+    LayerRenamed { layer: usize, name: String },
+    LayerVisibilityToggled { layer: usize },
+    LayerLockToggled { layer: usize },
+    LayerMoved { from: usize, to: usize },
+    LayerActivated { layer: usize },
+    SelectionMovedToLayer { layer: usize },
     ClearScreen,
     NewShape,
     SubmitShape,
+    DoubleClicked { x: f64, y: f64 },
     ValueChanged { key: String, value: String },
+    TransformValueChanged { key: String, value: String },
+    RotateSelected,
+    ScaleSelected,
+    UniformScaleToggled,
+    SkewSelected,
+    MatrixValueChanged { row: usize, col: usize, value: String },
+    ResetTransform,
+    DeleteSelected,
+    CopySelected,
+    CutSelected,
+    Paste { at_cursor: bool },
+    Pasted { text: Option<String>, at_cursor: bool },
+    DuplicateSelected,
+    ArrangeSelected { z_order: ZOrder },
+    GroupSelected,
+    UngroupSelected,
+    ClipSelected,
+    BooleanSelected { op: BooleanOp },
+    FlattenToleranceChanged { value: String },
+    Undo,
+    Redo,
     SaveToJson,
-    LoadFromJson { value: String },
+    SaveToSvg,
+    SaveToPng,
+    PngValueChanged { key: String, value: String },
+    PngTransparencyToggled,
+    FillValueChanged { key: String, value: String },
+    FillConnectivityToggled,
+    ClipAlgorithmChanged { algorithm: ClipAlgorithm },
+    RemoveClipWindow,
+    LoadFile { name: String, value: String },
     JsonChanged { value: String },
     None,
 }
@@ -30,16 +127,72 @@ enum Mode {
     Select,
     Resize,
     Move,
+    Rotate,
+    Scale,
+    Fill,
+    Clip,
+}
+
+// how the shapes get onto the canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RenderMode {
+    Canvas,
+    Raster,
+    AntiAliased,
 }
 
 struct App {
     shape_type: ShapeType,
     shape_storage: ShapeStorage,
+    history: History,
     mode: Mode,
+    render_mode: RenderMode,
     is_dragging: bool,
     last_cursor_pos: (f64, f64),
+    // where the pointer is on the screen, the center of the zoom inspector
+    cursor_pos: (f64, f64),
+    viewport: Viewport,
+    // screen position the view is dragged from, while panning
+    pan_anchor: Option<(f64, f64)>,
+    // the click ending a pan mustn't reach the tools
+    has_panned: bool,
+    space_held: bool,
+    show_grid: bool,
+    snap: SnapSettings,
+    // what the pointer snapped to last, marked on the canvas
+    snap_target: Option<SnapTarget>,
     resize_anchor: (f64, f64),
-    json: String
+    has_dragged: bool,
+    // corners of the rubber band rectangle dragged in select mode
+    marquee: Option<((f64, f64), (f64, f64))>,
+    pivot: Option<(f64, f64)>,
+    rotation_angle: f64,
+    scale_factor: (f64, f64),
+    uniform_scale: bool,
+    skew_angle: (f64, f64),
+    canvas: Canvas,
+    png_scale: f64,
+    png_transparent: bool,
+    // settings of the fill bucket
+    fill_color: String,
+    fill_tolerance: u8,
+    fill_connectivity: Connectivity,
+    // lines and rectangle edges are clipped to the window, the rest is shown dimmed
+    clip_window: Option<ClipWindow>,
+    // corner the clip window is dragged from
+    clip_anchor: Option<(f64, f64)>,
+    clip_algorithm: ClipAlgorithm,
+    // how far flattened curves may stray from the real ones in boolean operations, in pixels
+    flatten_tolerance: f64,
+    json: String,
+    // the copied shapes, for when the system clipboard can't be used
+    clipboard: String,
+    // problems met while loading the last file
+    load_messages: Vec<String>,
+    _keydown_listener: EventListener,
+    _keyup_listener: EventListener,
+    // attached once the canvas exists, to keep the page from scrolling while zooming
+    _wheel_listener: Option<EventListener>,
 }
 
 impl Component for App {
@@ -47,14 +200,83 @@ impl Component for App {
     type Properties = ();
 
     fn create(ctx: &Context<Self>) -> Self {
+        let link = ctx.link().clone();
+        let document = window().unwrap().document().unwrap();
+        let options = EventListenerOptions::enable_prevent_default();
+        let keydown_listener =
+            EventListener::new_with_options(&document, "keydown", options, move |event| {
+                // leave typing in the side panel alone
+                if from_input(event) {
+                    return;
+                }
+
+                let event = event.dyn_ref::<KeyboardEvent>().unwrap();
+                // space pans the view rather than scrolling the page
+                if event.key() == " " {
+                    event.prevent_default();
+                }
+                // rather than bookmarking the page and finding text on it
+                let ctrl = event.ctrl_key() || event.meta_key();
+                if ctrl && matches!(event.key().as_str(), "d" | "D" | "g" | "G") {
+                    event.prevent_default();
+                }
+                link.send_message(Msg::KeyPressed {
+                    key: event.key(),
+                    // Cmd on macOS
+                    ctrl,
+                    shift: event.shift_key(),
+                });
+            });
+        let link = ctx.link().clone();
+        let keyup_listener = EventListener::new(&document, "keyup", move |event| {
+            if from_input(event) {
+                return;
+            }
+
+            let event = event.dyn_ref::<KeyboardEvent>().unwrap();
+            link.send_message(Msg::KeyReleased { key: event.key() });
+        });
+
         Self {
             shape_type: ShapeType::Line,
             shape_storage: ShapeStorage::new(),
+            history: History::new(HISTORY_DEPTH),
             mode: Mode::Draw,
+            render_mode: RenderMode::Canvas,
             is_dragging: false,
             last_cursor_pos: (0.0, 0.0),
+            cursor_pos: (0.0, 0.0),
+            viewport: Viewport::default(),
+            pan_anchor: None,
+            has_panned: false,
+            space_held: false,
+            show_grid: false,
+            snap: SnapSettings::default(),
+            snap_target: None,
             resize_anchor: (0.0, 0.0),
+            has_dragged: false,
+            marquee: None,
+            pivot: None,
+            rotation_angle: 0.0,
+            scale_factor: (1.0, 1.0),
+            uniform_scale: false,
+            skew_angle: (0.0, 0.0),
+            canvas: Canvas::default(),
+            png_scale: 1.0,
+            png_transparent: false,
+            fill_color: "#ff0000".to_string(),
+            fill_tolerance: 32,
+            fill_connectivity: Connectivity::Four,
+            clip_window: None,
+            clip_anchor: None,
+            clip_algorithm: ClipAlgorithm::CohenSutherland,
+            flatten_tolerance: 0.5,
             json: String::new(),
+            clipboard: String::new(),
+            load_messages: Vec::new(),
+            _keydown_listener: keydown_listener,
+            _keyup_listener: keyup_listener,
+            _wheel_listener: None,
         }
     }
 
@@ -63,6 +285,7 @@ impl Component for App {
             ctx.link().callback(|event: MouseEvent| Msg::MouseClicked {
                 x: event.offset_x() as f64,
                 y: event.offset_y() as f64,
+                shift: event.shift_key(),
             });
         let canvas_mousemove_callback = ctx.link().callback(|event: MouseEvent| Msg::MouseMove {
             x: event.offset_x() as f64,
@@ -71,11 +294,25 @@ impl Component for App {
         let canvas_mouseup_callback = ctx.link().callback(|event: MouseEvent| Msg::MouseUp {
             x: event.offset_x() as f64,
             y: event.offset_y() as f64,
+            shift: event.shift_key(),
+            alt: event.alt_key(),
         });
-        let canvas_mousedown_callback = ctx.link().callback(|event: MouseEvent| Msg::MouseDown {
-            x: event.offset_x() as f64,
-            y: event.offset_y() as f64,
+        let canvas_mousedown_callback = ctx.link().callback(|event: MouseEvent| {
+            // no autoscroll while dragging with the middle button
+            if event.button() == MIDDLE_BUTTON {
+                event.prevent_default();
+            }
+            Msg::MouseDown {
+                x: event.offset_x() as f64,
+                y: event.offset_y() as f64,
+                button: event.button(),
+            }
         });
+        let canvas_dblclick_callback =
+            ctx.link().callback(|event: MouseEvent| Msg::DoubleClicked {
+                x: event.offset_x() as f64,
+                y: event.offset_y() as f64,
+            });
         let line_callback = ctx.link().callback(|_| Msg::ShapeChanged {
             shape_type: ShapeType::Line,
         });
@@ -85,6 +322,15 @@ impl Component for App {
         let circle_callback = ctx.link().callback(|_| Msg::ShapeChanged {
             shape_type: ShapeType::Circle,
         });
+        let polygon_callback = ctx.link().callback(|_| Msg::ShapeChanged {
+            shape_type: ShapeType::Polygon,
+        });
+        let polyline_callback = ctx.link().callback(|_| Msg::ShapeChanged {
+            shape_type: ShapeType::Polyline,
+        });
+        let bezier_callback = ctx.link().callback(|_| Msg::ShapeChanged {
+            shape_type: ShapeType::Bezier,
+        });
         let draw_mode_callback = ctx
             .link()
             .callback(|_| Msg::ModeChanged { mode: Mode::Draw });
@@ -97,13 +343,39 @@ impl Component for App {
         let move_mode_callback = ctx
             .link()
             .callback(|_| Msg::ModeChanged { mode: Mode::Move });
+        let rotate_mode_callback = ctx
+            .link()
+            .callback(|_| Msg::ModeChanged { mode: Mode::Rotate });
+        let scale_mode_callback = ctx
+            .link()
+            .callback(|_| Msg::ModeChanged { mode: Mode::Scale });
+        let fill_mode_callback = ctx
+            .link()
+            .callback(|_| Msg::ModeChanged { mode: Mode::Fill });
+        let clip_mode_callback = ctx
+            .link()
+            .callback(|_| Msg::ModeChanged { mode: Mode::Clip });
+        let ellipse_callback = ctx.link().callback(|_| Msg::ShapeChanged {
+            shape_type: ShapeType::Ellipse,
+        });
         let value_changed_callback = ctx.link().callback(move |event: InputEvent| {
             let target = event.target().unwrap();
             let target: web_sys::HtmlInputElement = target.dyn_into().unwrap();
-            let value = target.value();
+            let value = match target.type_().as_str() {
+                "checkbox" => target.checked().to_string(),
+                _ => target.value(),
+            };
             let key = target.id();
             Msg::ValueChanged { key, value }
         });
+        let transform_changed_callback = ctx.link().callback(move |event: InputEvent| {
+            let target: HtmlInputElement = event.target_unchecked_into();
+            Msg::TransformValueChanged {
+                key: target.id(),
+                value: target.value(),
+            }
+        });
+        let (pivot_x, pivot_y) = self.get_pivot().unwrap_or((0.0, 0.0));
 
         let prop_str = match self.shape_storage.get_highlighted() {
             Some(shape) => shape.get_prop_str(),
@@ -112,15 +384,43 @@ impl Component for App {
         let prop_list = prop_str.split("\n").filter(|str| !str.is_empty());
 
         let selected_shape = self.shape_storage.get_selected();
-        let file_cb = ctx.link().callback(|value: String| Msg::LoadFromJson { value });
+        let selection_count = self.shape_storage.get_selected_idxs().len();
+        let combinable_count = self
+            .shape_storage
+            .get_selection()
+            .filter(|shape| is_combinable(*shape))
+            .count();
+        let boolean_callback =
+            |op: BooleanOp| ctx.link().callback(move |_| Msg::BooleanSelected { op });
+        let png_changed_callback = ctx.link().callback(|event: InputEvent| {
+            let target: HtmlInputElement = event.target_unchecked_into();
+            Msg::PngValueChanged {
+                key: target.id(),
+                value: target.value(),
+            }
+        });
+        let fill_changed_callback = ctx.link().callback(|event: InputEvent| {
+            let target: HtmlInputElement = event.target_unchecked_into();
+            Msg::FillValueChanged {
+                key: target.id(),
+                value: target.value(),
+            }
+        });
+        let layers = self.shape_storage.get_layers();
+        let active_layer = self.shape_storage.get_active_layer();
+        let (png_width, png_height) = self.png_size();
+        let file_cb = ctx
+            .link()
+            .callback(|(name, value): (String, String)| Msg::LoadFile { name, value });
         html! {
             <div id="container">
                 <div style="width: 100%;height: 620px; margin: 0">
                     <canvas
                         id="canvas"
-                        width="800"
-                        height="600"
+                        width={self.canvas.width.to_string()}
+                        height={self.canvas.height.to_string()}
                         onclick={canvas_mouseclick_callback}
+                        ondblclick={canvas_dblclick_callback}
                         onmousedown={canvas_mousedown_callback}
                         onmouseup={canvas_mouseup_callback}
                         onmousemove={canvas_mousemove_callback}
@@ -128,6 +428,31 @@ impl Component for App {
                     <div style="float: left; margin-left: 20px">
                         <h2 style="margin-top: 0">{format!("Current shape type: {:?}", self.shape_type)}</h2>
                         <h2 style="margin-top: 0">{format!("Current mouse mode: {:?}", self.mode)}</h2>
+                        <h2 style="margin-top: 0">{format!("Rendering: {:?}", self.render_mode)}</h2>
+                        <div>
+                            <label>{format!("Zoom {}x", INSPECTOR_ZOOM)}</label>
+                            <br />
+                            <canvas
+                                id="inspector"
+                                width={INSPECTOR_SIZE.to_string()}
+                                height={INSPECTOR_SIZE.to_string()}
+                                style="border: 1px solid black" />
+                        </div>
+                        if self.clip_window.is_some() {
+                            <div>
+                                <h4>{format!("Clipping: {:?}", self.clip_algorithm)}</h4>
+                                <button onclick={ctx.link().callback(|_| Msg::ClipAlgorithmChanged { algorithm: ClipAlgorithm::CohenSutherland })}>{"Cohen–Sutherland"}</button>
+                                <button onclick={ctx.link().callback(|_| Msg::ClipAlgorithmChanged { algorithm: ClipAlgorithm::LiangBarsky })}>{"Liang–Barsky"}</button>
+                                <button onclick={ctx.link().callback(|_| Msg::RemoveClipWindow)}>{"Remove clip window"}</button>
+                                <ul>
+                                {self.clip_report().iter().map(|line| {
+                                    html! {
+                                        <li>{line}</li>
+                                    }
+                                }).collect::<Html>()}
+                                </ul>
+                            </div>
+                        }
                         <ul>
                         {prop_list.map(|prop| {
                             html! {
@@ -137,20 +462,117 @@ impl Component for App {
                         </ul>
                         if let Some(shape) = selected_shape {
                             <div>
-                                <h2>{"Selected shape"}</h2>
-                                <h4>{format!("Shape type: {:?}", shape.get_type())}</h4>
-                                {shape.get_props().iter().map(|prop| {
+                                if selection_count > 1 {
+                                    <h2>{format!("{} shapes selected", selection_count)}</h2>
+                                } else {
+                                    <h2>{"Selected shape"}</h2>
+                                    <h4>{format!("Shape type: {:?}", shape.get_type())}</h4>
+                                }
+                                {self.shared_props().iter().map(|prop| {
                                     html! {
                                         <div>
                                             <label>{format!("{}: ", prop.0)}</label>
                                             <input
                                                 id={prop.0.clone()}
-                                                type="number"
+                                                type={prop_input_type(&prop.0)}
                                                 oninput={value_changed_callback.clone()}
+                                                checked={prop.1 == "true"}
                                                 value={prop.1.clone()} />
                                         </div>
                                     }
                                 }).collect::<Html>()}
+                                <h4>{"Transform"}</h4>
+                                <div>
+                                    <label>{"Angle (deg): "}</label>
+                                    <input
+                                        id={ANGLE_INPUT_ID}
+                                        type="number"
+                                        oninput={transform_changed_callback.clone()}
+                                        value={self.rotation_angle.to_string()} />
+                                </div>
+                                <div>
+                                    <label>{"Pivot x: "}</label>
+                                    <input
+                                        id={PIVOT_X_INPUT_ID}
+                                        type="number"
+                                        oninput={transform_changed_callback.clone()}
+                                        value={pivot_x.to_string()} />
+                                </div>
+                                <div>
+                                    <label>{"Pivot y: "}</label>
+                                    <input
+                                        id={PIVOT_Y_INPUT_ID}
+                                        type="number"
+                                        oninput={transform_changed_callback.clone()}
+                                        value={pivot_y.to_string()} />
+                                </div>
+                                <button onclick={ctx.link().callback(|_| Msg::RotateSelected)}>{ "Rotate" }</button>
+                                <div>
+                                    <label>{"Scale x: "}</label>
+                                    <input
+                                        id={SCALE_X_INPUT_ID}
+                                        type="number"
+                                        oninput={transform_changed_callback.clone()}
+                                        value={self.scale_factor.0.to_string()} />
+                                </div>
+                                <div>
+                                    <label>{"Scale y: "}</label>
+                                    <input
+                                        id={SCALE_Y_INPUT_ID}
+                                        type="number"
+                                        disabled={self.uniform_scale}
+                                        oninput={transform_changed_callback.clone()}
+                                        value={self.scale_factor.1.to_string()} />
+                                </div>
+                                <div>
+                                    <label>{"Uniform: "}</label>
+                                    <input
+                                        type="checkbox"
+                                        checked={self.uniform_scale}
+                                        onchange={ctx.link().callback(|_| Msg::UniformScaleToggled)} />
+                                </div>
+                                <button onclick={ctx.link().callback(|_| Msg::ScaleSelected)}>{ "Scale" }</button>
+                                <div>
+                                    <label>{"Skew x (deg): "}</label>
+                                    <input
+                                        id={SKEW_X_INPUT_ID}
+                                        type="number"
+                                        oninput={transform_changed_callback.clone()}
+                                        value={self.skew_angle.0.to_string()} />
+                                </div>
+                                <div>
+                                    <label>{"Skew y (deg): "}</label>
+                                    <input
+                                        id={SKEW_Y_INPUT_ID}
+                                        type="number"
+                                        oninput={transform_changed_callback.clone()}
+                                        value={self.skew_angle.1.to_string()} />
+                                </div>
+                                <button onclick={ctx.link().callback(|_| Msg::SkewSelected)}>{ "Skew" }</button>
+                                if selection_count == 1 {
+                                    <h4>{"Matrix"}</h4>
+                                    {(0..2).map(|row| {
+                                        html! {
+                                            <div>
+                                                {(0..3).map(|col| {
+                                                    let transform = shape.get_transform();
+                                                    html! {
+                                                        <input
+                                                            type="number"
+                                                            style="width: 80px"
+                                                            oninput={ctx.link().callback(move |event: InputEvent| {
+                                                                let target: HtmlInputElement = event.target_unchecked_into();
+                                                                Msg::MatrixValueChanged { row, col, value: target.value() }
+                                                            })}
+                                                            value={transform.get(row, col).to_string()} />
+                                                    }
+                                                }).collect::<Html>()}
+                                            </div>
+                                        }
+                                    }).collect::<Html>()}
+                                    <div>{"0 0 1"}</div>
+                                }
+                                <button onclick={ctx.link().callback(|_| Msg::ResetTransform)}>{ "Reset transform" }</button>
                                 if shape.get_state() == ShapeState::New {
                                     <button onclick={ctx.link().callback(|_| Msg::SubmitShape)}>{ "Create shape" }</button>
                                 }
@@ -164,24 +586,131 @@ impl Component for App {
                     <button onclick={select_mode_callback}>{"Select"}</button>
                     <button onclick={resize_mode_callback}>{"Resize"}</button>
                     <button onclick={move_mode_callback}>{"Move"}</button>
+                    <button onclick={rotate_mode_callback}>{"Rotate"}</button>
+                    <button onclick={scale_mode_callback}>{"Scale"}</button>
+                    <button onclick={fill_mode_callback}>{"Fill bucket"}</button>
+                    <button onclick={clip_mode_callback}>{"Clip window"}</button>
+                </div>
+                if self.mode == Mode::Fill {
+                    <div>
+                        <label>{"Fill color: "}</label>
+                        <input
+                            id={FILL_COLOR_INPUT_ID}
+                            type="color"
+                            oninput={fill_changed_callback.clone()}
+                            value={self.fill_color.clone()} />
+                        <label>{" Tolerance: "}</label>
+                        <input
+                            id={FILL_TOLERANCE_INPUT_ID}
+                            type="number"
+                            min="0"
+                            max="255"
+                            oninput={fill_changed_callback}
+                            value={self.fill_tolerance.to_string()} />
+                        <label>{" 8-connected: "}</label>
+                        <input
+                            type="checkbox"
+                            checked={self.fill_connectivity == Connectivity::Eight}
+                            onchange={ctx.link().callback(|_| Msg::FillConnectivityToggled)} />
+                    </div>
+                }
+                <label>{"Rendering"}</label>
+                <div>
+                    <button onclick={ctx.link().callback(|_| Msg::RenderModeChanged { render_mode: RenderMode::Canvas })}>{"Canvas"}</button>
+                    <button onclick={ctx.link().callback(|_| Msg::RenderModeChanged { render_mode: RenderMode::Raster })}>{"Bresenham"}</button>
+                    <button onclick={ctx.link().callback(|_| Msg::RenderModeChanged { render_mode: RenderMode::AntiAliased })}>{"Wu (anti-aliased)"}</button>
+                </div>
+                <label>{"View"}</label>
+                <div>
+                    <span title="Wheel zooms, middle-drag or space-drag pans">
+                        {format!("Zoom: {:.0}% ", self.viewport.zoom * 100.0)}
+                    </span>
+                    <button onclick={ctx.link().callback(|_| Msg::ResetView)}>{"Reset view"}</button>
+                </div>
+                <label>{"Grid and snapping"}</label>
+                <div>
+                    <label>{"Show grid: "}</label>
+                    <input
+                        type="checkbox"
+                        checked={self.show_grid}
+                        onchange={ctx.link().callback(|_| Msg::GridToggled)} />
+                    <label>{" Spacing: "}</label>
+                    <input
+                        id={GRID_SPACING_INPUT_ID}
+                        type="number"
+                        min="1"
+                        oninput={ctx.link().callback(|event: InputEvent| {
+                            let target: HtmlInputElement = event.target_unchecked_into();
+                            Msg::GridSpacingChanged { value: target.value() }
+                        })}
+                        value={self.snap.spacing.to_string()} />
+                    <label>{" Snap to: "}</label>
+                    {[
+                        (SnapKind::Grid, "Grid"),
+                        (SnapKind::Endpoint, "Endpoints"),
+                        (SnapKind::Midpoint, "Midpoints"),
+                        (SnapKind::Center, "Centers"),
+                        (SnapKind::Intersection, "Intersections"),
+                    ].into_iter().map(|(kind, label)| {
+                        html! {
+                            <label>
+                                <input
+                                    type="checkbox"
+                                    checked={self.snap.is_enabled(kind)}
+                                    onchange={ctx.link().callback(move |_| Msg::SnapToggled { kind })} />
+                                {label}
+                            </label>
+                        }
+                    }).collect::<Html>()}
                 </div>
                 <label>{"Shape"}</label>
                 <div>
                     <button onclick={line_callback}>{"Line"}</button>
                     <button onclick={rectangle_callback}>{"Rectangle"}</button>
                     <button onclick={circle_callback}>{"Circle"}</button>
+                    <button onclick={polygon_callback}>{"Polygon"}</button>
+                    <button onclick={polyline_callback}>{"Polyline"}</button>
+                    <button onclick={bezier_callback}>{"Bezier"}</button>
+                    <button onclick={ellipse_callback}>{"Ellipse"}</button>
                 </div>
                 <label>{"Command"}</label>
                 <div>
                     <button onclick={ctx.link().callback(|_| Msg::ClearScreen)}>{"Clear"}</button>
                     <button onclick={ctx.link().callback(|_| Msg::NewShape)}>{"New"}</button>
+                    <button onclick={ctx.link().callback(|_| Msg::DeleteSelected)}>{"Delete"}</button>
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::CopySelected)}
+                        disabled={selection_count == 0}
+                        title="Ctrl+C">{"Copy"}</button>
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::CutSelected)}
+                        disabled={selection_count == 0}
+                        title="Ctrl+X">{"Cut"}</button>
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::Paste { at_cursor: false })}
+                        title="Ctrl+V pastes at the cursor">{"Paste"}</button>
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::DuplicateSelected)}
+                        disabled={selection_count == 0}
+                        title="Ctrl+D">{"Duplicate"}</button>
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::ClipSelected)}
+                        disabled={selection_count < 2}
+                        title="Clips the selected shapes to the last one selected">{"Clip to last selected"}</button>
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::Undo)}
+                        disabled={!self.history.can_undo()}>{"Undo"}</button>
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::Redo)}
+                        disabled={!self.history.can_redo()}>{"Redo"}</button>
                     <button onclick={ctx.link().callback(|_| Msg::SaveToJson)}>{"Save"}</button>
-                    // <button onclick={ctx.link().callback(|_| Msg::LoadFromJson)}>{"Load"}</button>
-                    <input type="file" onchange={ctx.link().callback(move |event: Event| {
+                    <button onclick={ctx.link().callback(|_| Msg::SaveToSvg)}>{"Save as SVG"}</button>
+                    <input type="file" accept=".json,.svg" onchange={ctx.link().callback(move |event: Event| {
                         let file_cb = file_cb.clone();
                         let target = event.target().unwrap();
                         let target: web_sys::HtmlInputElement = target.dyn_into().unwrap();
                         let file = target.files().unwrap().get(0).unwrap();
+                        let name = file.name();
                         let file_reader = web_sys::FileReader::new().unwrap();
                         file_reader.read_as_text(&file).unwrap();
                         log::info!("file: {:?}", file);
@@ -191,13 +720,147 @@ impl Component for App {
                             let target: web_sys::FileReader = target.dyn_into().unwrap();
                             let result = target.result().unwrap();
                             let result: String = result.as_string().unwrap();
-                            file_cb.emit(result);
+                            file_cb.emit((name.clone(), result));
                         });
                         listener.forget();
 
                         Msg::None
                     })} />
                 </div>
+                <label>{"Arrange"}</label>
+                <div>
+                    {[
+                        (ZOrder::Front, "Bring to front", "Ctrl+Shift+]"),
+                        (ZOrder::Forward, "Bring forward", "Ctrl+]"),
+                        (ZOrder::Backward, "Send backward", "Ctrl+["),
+                        (ZOrder::Back, "Send to back", "Ctrl+Shift+["),
+                    ].into_iter().map(|(z_order, label, shortcut)| {
+                        html! {
+                            <button
+                                onclick={ctx.link().callback(move |_| Msg::ArrangeSelected { z_order })}
+                                disabled={selection_count == 0}
+                                title={shortcut}>{label}</button>
+                        }
+                    }).collect::<Html>()}
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::GroupSelected)}
+                        disabled={selection_count < 2}
+                        title="Ctrl+G">{"Group"}</button>
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::UngroupSelected)}
+                        disabled={!self.shape_storage.get_selection().any(|shape| shape.as_group().is_some())}
+                        title="Ctrl+Shift+G">{"Ungroup"}</button>
+                </div>
+                <label>{"Layers"}</label>
+                <div>
+                    <button onclick={ctx.link().callback(|_| Msg::LayerAdded)}>{"Add layer"}</button>
+                    <ul>
+                    // the top layer first, as they're stacked on the canvas
+                    {(0..layers.len()).rev().map(|layer| {
+                        html! {
+                            <li>
+                                <input
+                                    type="radio"
+                                    name="active-layer"
+                                    title="New shapes go to the active layer"
+                                    checked={layer == active_layer}
+                                    onchange={ctx.link().callback(move |_| Msg::LayerActivated { layer })} />
+                                <input
+                                    type="text"
+                                    onchange={ctx.link().callback(move |event: Event| {
+                                        let target: HtmlInputElement = event.target_unchecked_into();
+                                        Msg::LayerRenamed { layer, name: target.value() }
+                                    })}
+                                    value={layers[layer].name.clone()} />
+                                <label>
+                                    <input
+                                        type="checkbox"
+                                        checked={layers[layer].visible}
+                                        onchange={ctx.link().callback(move |_| Msg::LayerVisibilityToggled { layer })} />
+                                    {"Visible"}
+                                </label>
+                                <label>
+                                    <input
+                                        type="checkbox"
+                                        checked={layers[layer].locked}
+                                        onchange={ctx.link().callback(move |_| Msg::LayerLockToggled { layer })} />
+                                    {"Locked"}
+                                </label>
+                                <button
+                                    onclick={ctx.link().callback(move |_| Msg::LayerMoved { from: layer, to: layer + 1 })}
+                                    disabled={layer + 1 == layers.len()}>{"Up"}</button>
+                                <button
+                                    onclick={ctx.link().callback(move |_| Msg::LayerMoved { from: layer, to: layer.saturating_sub(1) })}
+                                    disabled={layer == 0}>{"Down"}</button>
+                                <button
+                                    onclick={ctx.link().callback(move |_| Msg::SelectionMovedToLayer { layer })}
+                                    disabled={selection_count == 0}>{"Move selection here"}</button>
+                                <button
+                                    onclick={ctx.link().callback(move |_| Msg::LayerRemoved { layer })}
+                                    disabled={layers.len() == 1}
+                                    title="Deletes the layer with its shapes">{"Delete"}</button>
+                            </li>
+                        }
+                    }).collect::<Html>()}
+                    </ul>
+                </div>
+                <label>{"Boolean"}</label>
+                <div>
+                    <button
+                        onclick={boolean_callback(BooleanOp::Union)}
+                        disabled={combinable_count < 2}>{"Union"}</button>
+                    <button
+                        onclick={boolean_callback(BooleanOp::Intersection)}
+                        disabled={combinable_count < 2}>{"Intersect"}</button>
+                    <button
+                        onclick={boolean_callback(BooleanOp::Difference)}
+                        disabled={combinable_count < 2}
+                        title="Subtracts the others from the first selected shape">{"Subtract"}</button>
+                    <button
+                        onclick={boolean_callback(BooleanOp::Xor)}
+                        disabled={combinable_count < 2}>{"Exclude"}</button>
+                    <label>{" Curve tolerance (px): "}</label>
+                    <input
+                        id={FLATTEN_TOLERANCE_INPUT_ID}
+                        type="number"
+                        min="0.01"
+                        step="0.1"
+                        oninput={ctx.link().callback(|event: InputEvent| {
+                            let target: HtmlInputElement = event.target_unchecked_into();
+                            Msg::FlattenToleranceChanged { value: target.value() }
+                        })}
+                        value={self.flatten_tolerance.to_string()} />
+                </div>
+                <label>{"PNG export"}</label>
+                <div>
+                    <label>{"Scale: "}</label>
+                    <input
+                        id={PNG_SCALE_INPUT_ID}
+                        type="number"
+                        step="0.5"
+                        oninput={png_changed_callback.clone()}
+                        value={self.png_scale.to_string()} />
+                    <label>{" Width (px): "}</label>
+                    <input
+                        id={PNG_WIDTH_INPUT_ID}
+                        type="number"
+                        oninput={png_changed_callback}
+                        value={png_width.to_string()} />
+                    <span>{format!(" {} x {} px ", png_width, png_height)}</span>
+                    <label>{"Transparent: "}</label>
+                    <input
+                        type="checkbox"
+                        checked={self.png_transparent}
+                        onchange={ctx.link().callback(|_| Msg::PngTransparencyToggled)} />
+                    <button onclick={ctx.link().callback(|_| Msg::SaveToPng)}>{"Export PNG"}</button>
+                </div>
+                <ul>
+                {self.load_messages.iter().map(|message| {
+                    html! {
+                        <li>{message}</li>
+                    }
+                }).collect::<Html>()}
+                </ul>
                 <div>
                     <span>{"JSON: "}</span>
                     <textarea id="json"
@@ -210,76 +873,180 @@ impl Component for App {
         }
     }
 
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             Msg::ShapeChanged { shape_type } => {
                 self.shape_type = shape_type;
 
                 return true;
             }
-            Msg::MouseClicked { x, y } => {
+            Msg::MouseClicked { x, y, shift } => {
+                if self.has_panned {
+                    return false;
+                }
+
+                let (x, y) = self.viewport.to_world((x, y));
                 match self.mode {
                     Mode::Draw => {
+                        let drawing = self.drawing_idx();
+                        let target = self.find_snap((x, y), |idx| Some(idx) != drawing, true);
+                        let (x, y) = self.apply_snap((x, y), target);
+                        let shape_count = self.shape_storage.shape_count();
+                        let before = self
+                            .shape_storage
+                            .get_current_idx()
+                            .and_then(|idx| self.shape_storage.get_shape(idx))
+                            .map(|shape| shape.get_json());
                         let shape = self.shape_storage.get_or_create_shape(self.shape_type);
                         shape.add_point(x, y);
+                        let after = shape.get_json();
+
+                        let idx = self.shape_storage.get_current_idx().unwrap();
+                        match before {
+                            Some(before) if self.shape_storage.shape_count() == shape_count => {
+                                self.history.push(Command::Edit { idx, before, after })
+                            }
+                            _ => {
+                                let shape = self.shape_storage.shape_json(idx).unwrap();
+                                self.history.push(Command::Insert { idx, shape });
+                            }
+                        }
                     }
                     Mode::Resize | Mode::Move | Mode::Select => {
-                        self.shape_storage.intersect_and_select(x, y);
+                        // the click ending a drag or a marquee keeps the selection
+                        if self.has_dragged {
+                            return false;
+                        }
+
+                        self.select_at(x, y, shift);
+                    }
+                    Mode::Rotate | Mode::Scale => {
+                        // the click ending a drag must not move the pivot
+                        if self.has_dragged {
+                            return false;
+                        }
+
+                        // clicking a shape selects it, clicking elsewhere places the pivot
+                        if self.shape_storage.get_shape_at(x, y).is_some() {
+                            self.select_at(x, y, shift);
+                        } else if self.shape_storage.get_selected().is_some() {
+                            self.pivot = Some((x, y));
+                        }
                     }
+                    Mode::Fill => self.bucket_fill(x, y),
+                    Mode::Clip => {}
                 }
 
                 return true;
             }
             Msg::MouseMove { x, y } => {
+                self.cursor_pos = (x, y);
+                if let Some((ax, ay)) = self.pan_anchor {
+                    self.viewport.pan(x - ax, y - ay);
+                    self.pan_anchor = Some((x, y));
+
+                    return true;
+                }
+
+                let (x, y) = self.viewport.to_world((x, y));
+                self.snap_target = None;
                 match self.mode {
                     Mode::Draw => {
-                        let shape = self.shape_storage.get_current_mut();
-                        if shape.is_some() {
-                            let shape = shape.unwrap();
+                        let drawing = self.drawing_idx();
+                        let target = self.find_snap((x, y), |idx| Some(idx) != drawing, true);
+                        let (x, y) = self.apply_snap((x, y), target);
+                        if let Some(shape) = self.shape_storage.get_current_mut() {
                             if shape.get_state() == ShapeState::Drawing {
                                 shape.set_end(x, y);
                             }
                         }
                     }
                     Mode::Select => {
-                        self.shape_storage.intersect_and_highlight(x, y);
+                        if let Some((start, _)) = self.marquee {
+                            self.marquee = Some((start, (x, y)));
+                            self.has_dragged = true;
+                        } else {
+                            self.shape_storage.intersect_and_highlight(x, y);
+                        }
                     }
                     Mode::Resize => {
                         if self.is_dragging {
-                            let shape = self.shape_storage.get_selected_mut();
-                            if let Some(shape) = shape {
-                                shape.resize(
-                                    (x - self.last_cursor_pos.0, y - self.last_cursor_pos.1),
-                                    self.resize_anchor,
-                                );
+                            // only the primary selected shape is resized
+                            let primary = self.shape_storage.get_selected_idx();
+                            let target = self.find_snap((x, y), |idx| Some(idx) != primary, true);
+                            let (x, y) = self.apply_snap((x, y), target);
+                            let change = (x - self.last_cursor_pos.0, y - self.last_cursor_pos.1);
+                            let anchor = self.resize_anchor;
+                            let idx = self.shape_storage.get_selected_idx();
+                            self.record_edits(idx.as_slice(), true, |storage| {
+                                let tolerance = storage.get_hit_tolerance();
+                                if let Some(shape) = storage.get_selected_mut() {
+                                    shape.resize(change, anchor, tolerance);
+                                }
+                            });
+                            self.last_cursor_pos = (x, y);
+                            self.resize_anchor = (x, y);
+                            self.has_dragged = true;
+                        } else {
+                            self.shape_storage.intersect_and_highlight(x, y);
+                        }
+                    }
+                    Mode::Move => {
+                        if self.is_dragging {
+                            let selected = self.shape_storage.get_selected_idxs();
+                            let target =
+                                self.find_snap((x, y), |idx| !selected.contains(&idx), true);
+                            let (x, y) = self.apply_snap((x, y), target);
+                            let (dx, dy) = (x - self.last_cursor_pos.0, y - self.last_cursor_pos.1);
+                            self.edit_selected(true, |shape| shape.move_by(dx, dy));
+                            self.last_cursor_pos = (x, y);
+                            self.has_dragged = true;
+                        } else {
+                            self.shape_storage.intersect_and_highlight(x, y);
+                        }
+                    }
+                    Mode::Rotate => {
+                        if self.is_dragging {
+                            if let Some((px, py)) = self.get_pivot() {
+                                let (lx, ly) = self.last_cursor_pos;
+                                let angle = (y - py).atan2(x - px) - (ly - py).atan2(lx - px);
+                                self.edit_selected(true, |shape| shape.rotate(angle, (px, py)));
                                 self.last_cursor_pos = (x, y);
-                                self.resize_anchor = (x, y);
+                                self.has_dragged = true;
                             }
                         } else {
                             self.shape_storage.intersect_and_highlight(x, y);
                         }
                     }
-                    Mode::Move => {
+                    Mode::Scale => {
                         if self.is_dragging {
-                            let shape = self.shape_storage.get_selected_mut();
-                            if shape.is_some() {
-                                let shape = shape.unwrap();
-                                shape.move_by(
-                                    x - self.last_cursor_pos.0,
-                                    y - self.last_cursor_pos.1,
-                                );
+                            if let Some(pivot) = self.get_pivot() {
+                                let factor = self.drag_scale_factor(pivot, (x, y));
+                                self.edit_selected(true, |shape| shape.scale(factor, pivot));
                                 self.last_cursor_pos = (x, y);
+                                self.has_dragged = true;
                             }
                         } else {
                             self.shape_storage.intersect_and_highlight(x, y);
                         }
                     }
+                    Mode::Fill => {}
+                    Mode::Clip => {
+                        if let Some(anchor) = self.clip_anchor {
+                            self.clip_window = Some(ClipWindow::from_corners(anchor, (x, y)));
+                        }
+                    }
                 }
 
                 return true;
             }
             Msg::ClearScreen => {
+                let before = self.shape_storage.serialize_to_json();
                 self.shape_storage.clear();
+                let after = self.shape_storage.serialize_to_json();
+                if before != after {
+                    self.history.push(Command::Replace { before, after });
+                }
 
                 return true;
             }
@@ -288,73 +1055,511 @@ impl Component for App {
 
                 return true;
             }
+            Msg::RenderModeChanged { render_mode } => {
+                self.render_mode = render_mode;
+
+                true
+            }
             Msg::ValueChanged { key, value } => {
-                let shape = self.shape_storage.get_selected_mut();
-                if shape.is_some() {
-                    let shape = shape.unwrap();
-                    shape.set_prop(&key, &value);
+                // fields that differ across the selection are shown empty, nothing is set on the
+                // shapes until the field holds a whole value
+                if !is_valid_prop_value(&key, &value) {
+                    return false;
+                }
+                self.edit_selected(false, |shape| shape.set_prop(&key, &value));
+
+                true
+            }
+            Msg::TransformValueChanged { key, value } => {
+                let value: f64 = match value.parse() {
+                    Ok(value) => value,
+                    Err(_) => return false,
+                };
+                let (pivot_x, pivot_y) = self.get_pivot().unwrap_or((0.0, 0.0));
+                match key.as_str() {
+                    ANGLE_INPUT_ID => self.rotation_angle = value,
+                    SCALE_X_INPUT_ID => self.scale_factor.0 = value,
+                    SCALE_Y_INPUT_ID => self.scale_factor.1 = value,
+                    SKEW_X_INPUT_ID => self.skew_angle.0 = value,
+                    SKEW_Y_INPUT_ID => self.skew_angle.1 = value,
+                    PIVOT_X_INPUT_ID => self.pivot = Some((value, pivot_y)),
+                    PIVOT_Y_INPUT_ID => self.pivot = Some((pivot_x, value)),
+                    _ => {}
                 }
 
                 true
             }
-            Msg::MouseUp { x, y } => {
+            Msg::RotateSelected => {
+                let angle = self.rotation_angle.to_radians();
+                if let Some(pivot) = self.get_pivot() {
+                    self.edit_selected(false, |shape| shape.rotate(angle, pivot));
+                }
+
+                true
+            }
+            Msg::ScaleSelected => {
+                let factor = if self.uniform_scale {
+                    (self.scale_factor.0, self.scale_factor.0)
+                } else {
+                    self.scale_factor
+                };
+                if let Some(pivot) = self.get_pivot() {
+                    self.edit_selected(false, |shape| shape.scale(factor, pivot));
+                }
+
+                true
+            }
+            Msg::SkewSelected => {
+                let angle = (
+                    self.skew_angle.0.to_radians(),
+                    self.skew_angle.1.to_radians(),
+                );
+                if let Some(pivot) = self.get_pivot() {
+                    self.edit_selected(false, |shape| shape.skew(angle, pivot));
+                }
+
+                true
+            }
+            Msg::MatrixValueChanged { row, col, value } => {
+                let value: f64 = match value.parse() {
+                    Ok(value) => value,
+                    Err(_) => return false,
+                };
+                self.edit_selected(false, |shape| {
+                    let mut transform = shape.get_transform();
+                    transform.set(row, col, value);
+                    shape.set_transform(transform);
+                });
+
+                true
+            }
+            Msg::ResetTransform => {
+                self.edit_selected(false, |shape| shape.set_transform(Transform::identity()));
+
+                true
+            }
+            Msg::UniformScaleToggled => {
+                self.uniform_scale = !self.uniform_scale;
+
+                true
+            }
+            Msg::MouseUp { x, y, shift, alt } => {
+                if self.pan_anchor.take().is_some() {
+                    return true;
+                }
+
+                let (x, y) = self.viewport.to_world((x, y));
                 match self.mode {
-                    Mode::Resize | Mode::Move => {
+                    Mode::Select => {
+                        if let Some(((x1, y1), (x2, y2))) = self.marquee.take() {
+                            if self.has_dragged {
+                                let rect = (x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2));
+                                // alt also picks shapes crossing the rectangle, shift adds to the selection
+                                self.shape_storage.select_in_rect(rect, alt, shift);
+                                self.pivot = None;
+                            }
+                        }
+                    }
+                    Mode::Resize | Mode::Move | Mode::Rotate | Mode::Scale => {
                         self.is_dragging = false;
+                        self.history.end_merge();
                         self.last_cursor_pos = (x, y);
                         self.resize_anchor = (x, y);
                     }
+                    Mode::Clip => {
+                        // a click without a drag removes the window
+                        if let Some(anchor) = self.clip_anchor.take() {
+                            let window = ClipWindow::from_corners(anchor, (x, y));
+                            self.clip_window = if window.width() > 0.0 && window.height() > 0.0 {
+                                Some(window)
+                            } else {
+                                None
+                            };
+                        }
+                    }
                     _ => {}
                 }
 
                 true
             }
-            Msg::MouseDown { x, y } => {
+            Msg::MouseDown { x, y, button } => {
+                // middle-drag or space-drag pans the view whatever the mode
+                self.has_panned = button == MIDDLE_BUTTON || self.space_held;
+                if self.has_panned {
+                    self.pan_anchor = Some((x, y));
+
+                    return false;
+                }
+
+                let (x, y) = self.viewport.to_world((x, y));
+                let (x, y) = match self.mode {
+                    // the drag starts from the nearest point of the dragged shapes, so that point
+                    // lands on the snap target
+                    Mode::Move => {
+                        let selected = self.shape_storage.get_selected_idxs();
+                        let target = self.find_snap((x, y), |idx| selected.contains(&idx), false);
+                        self.apply_snap((x, y), target)
+                    }
+                    Mode::Resize => {
+                        let primary = self.shape_storage.get_selected_idx();
+                        let target = self.find_snap((x, y), |idx| Some(idx) == primary, false);
+                        self.apply_snap((x, y), target)
+                    }
+                    _ => (x, y),
+                };
                 match self.mode {
-                    Mode::Resize | Mode::Move => {
+                    Mode::Resize | Mode::Move | Mode::Rotate | Mode::Scale => {
                         self.is_dragging = true;
+                        self.has_dragged = false;
+                        self.history.end_merge();
                         self.last_cursor_pos = (x, y);
                         self.resize_anchor = (x, y);
                     }
+                    Mode::Select => {
+                        // dragging from empty canvas starts a marquee
+                        self.has_dragged = false;
+                        if self.shape_storage.get_shape_at(x, y).is_none() {
+                            self.marquee = Some(((x, y), (x, y)));
+                        }
+                    }
+                    Mode::Clip => {
+                        self.clip_anchor = Some((x, y));
+                        self.clip_window = None;
+                    }
                     _ => {}
                 }
 
                 true
             }
+            Msg::DeleteSelected => {
+                self.delete_selected();
+
+                true
+            }
+            Msg::CopySelected => {
+                self.copy_selected();
+
+                false
+            }
+            Msg::CutSelected => {
+                self.copy_selected();
+                self.delete_selected();
+
+                true
+            }
+            Msg::Paste { at_cursor } => {
+                read_clipboard(
+                    ctx.link()
+                        .callback(move |text| Msg::Pasted { text, at_cursor }),
+                );
+
+                false
+            }
+            Msg::Pasted { text, at_cursor } => {
+                // the system clipboard can be unreadable, e.g. outside a secure context, or hold
+                // something other than shapes, the shapes copied here last are pasted then
+                let shapes = match text.as_deref().map(from_clipboard) {
+                    Some(Ok(shapes)) => Ok(shapes),
+                    _ => from_clipboard(&self.clipboard),
+                };
+                let point = at_cursor.then(|| self.viewport.to_world(self.cursor_pos));
+                match shapes {
+                    Ok(shapes) => self.paste(shapes, point),
+                    Err(error) => {
+                        log::warn!("Nothing to paste: {}", error);
+                        false
+                    }
+                }
+            }
+            Msg::DuplicateSelected => self.duplicate_selected(),
+            Msg::ArrangeSelected { z_order } => self.arrange_selected(z_order),
+            Msg::GroupSelected => self.group_selected(),
+            Msg::UngroupSelected => self.ungroup_selected(),
+            Msg::ClipSelected => {
+                self.clip_selected();
+
+                true
+            }
+            Msg::BooleanSelected { op } => {
+                self.combine_selected(op);
+
+                true
+            }
+            Msg::FlattenToleranceChanged { value } => match value.parse::<f64>() {
+                Ok(tolerance) if tolerance > 0.0 => {
+                    self.flatten_tolerance = tolerance;
+
+                    true
+                }
+                _ => false,
+            },
             Msg::NewShape => {
                 self.shape_storage.new_shape(self.shape_type);
+                let idx = self.shape_storage.get_current_idx().unwrap();
+                let shape = self.shape_storage.shape_json(idx).unwrap();
+                self.history.push(Command::Insert { idx, shape });
 
                 true
             }
             Msg::SubmitShape => {
-                self.shape_storage.submit_shape();
+                let idx = self.shape_storage.get_selected_idx();
+                self.record_edits(idx.as_slice(), false, |storage| storage.submit_shape());
 
                 true
             }
+            // finishes the shape being drawn, or enters the group under the cursor
+            Msg::DoubleClicked { x, y } => {
+                if self.mode == Mode::Draw {
+                    let idx = self.shape_storage.get_current_idx();
+                    self.record_edits(idx.as_slice(), false, |storage| storage.finish_shape());
+                    return true;
+                }
+                let (x, y) = self.viewport.to_world((x, y));
+                self.pivot = None;
+
+                self.shape_storage.enter_group_at(x, y)
+            }
+            Msg::Wheel { x, y, delta } => {
+                let factor = if delta < 0.0 {
+                    ZOOM_STEP
+                } else {
+                    1.0 / ZOOM_STEP
+                };
+                self.viewport.zoom_at((x, y), factor);
+                self.update_hit_tolerance();
+
+                true
+            }
+            Msg::GridToggled => {
+                self.show_grid = !self.show_grid;
+
+                true
+            }
+            Msg::GridSpacingChanged { value } => match value.parse::<f64>() {
+                Ok(spacing) if spacing > 0.0 => {
+                    self.snap.spacing = spacing;
+
+                    true
+                }
+                _ => false,
+            },
+            Msg::SnapToggled { kind } => {
+                self.snap.toggle(kind);
+
+                true
+            }
+            Msg::LayerAdded => self.record_arrangement(|storage| {
+                storage.add_layer();
+            }),
+            Msg::LayerRemoved { layer } => {
+                self.record_arrangement(|storage| storage.remove_layer(layer))
+            }
+            Msg::LayerRenamed { layer, name } => {
+                self.record_arrangement(|storage| storage.rename_layer(layer, &name))
+            }
+            Msg::LayerVisibilityToggled { layer } => self.record_arrangement(|storage| {
+                let visible = storage.get_layers()[layer].visible;
+                storage.set_layer_visible(layer, !visible);
+            }),
+            Msg::LayerLockToggled { layer } => self.record_arrangement(|storage| {
+                let locked = storage.get_layers()[layer].locked;
+                storage.set_layer_locked(layer, !locked);
+            }),
+            Msg::LayerMoved { from, to } => {
+                self.record_arrangement(|storage| storage.move_layer(from, to))
+            }
+            Msg::LayerActivated { layer } => {
+                self.shape_storage.set_active_layer(layer);
+
+                true
+            }
+            Msg::SelectionMovedToLayer { layer } => {
+                let idxs = self.shape_storage.get_selected_idxs().to_vec();
+                self.record_arrangement(|storage| storage.move_to_layer(&idxs, layer))
+            }
+            Msg::ResetView => {
+                self.viewport = Viewport::default();
+                self.update_hit_tolerance();
+
+                true
+            }
+            Msg::KeyReleased { key } => {
+                if key == " " {
+                    self.space_held = false;
+                }
+
+                false
+            }
+            Msg::KeyPressed { key, ctrl, shift } => match key.as_str() {
+                " " => {
+                    self.space_held = true;
+
+                    false
+                }
+                "z" | "Z" if ctrl && shift => self.history.redo(&mut self.shape_storage),
+                "z" | "Z" if ctrl => self.history.undo(&mut self.shape_storage),
+                "Delete" | "Backspace" => {
+                    self.delete_selected();
+
+                    true
+                }
+                "c" | "C" if ctrl => {
+                    self.copy_selected();
+
+                    false
+                }
+                "x" | "X" if ctrl => {
+                    self.copy_selected();
+                    self.delete_selected();
+
+                    true
+                }
+                "v" | "V" if ctrl => {
+                    read_clipboard(ctx.link().callback(|text| Msg::Pasted {
+                        text,
+                        at_cursor: true,
+                    }));
+
+                    false
+                }
+                "d" | "D" if ctrl => self.duplicate_selected(),
+                // with shift the brackets may come as braces
+                "]" | "}" if ctrl && shift => self.arrange_selected(ZOrder::Front),
+                "]" if ctrl => self.arrange_selected(ZOrder::Forward),
+                "[" | "{" if ctrl && shift => self.arrange_selected(ZOrder::Back),
+                "[" if ctrl => self.arrange_selected(ZOrder::Backward),
+                "g" | "G" if ctrl && shift => self.ungroup_selected(),
+                "g" | "G" if ctrl => self.group_selected(),
+                "Escape" => {
+                    self.shape_storage.leave_groups();
+
+                    true
+                }
+                "Enter" if self.mode == Mode::Draw => {
+                    let idx = self.shape_storage.get_current_idx();
+                    self.record_edits(idx.as_slice(), false, |storage| storage.finish_shape());
+
+                    true
+                }
+                _ => false,
+            },
+            Msg::Undo => self.history.undo(&mut self.shape_storage),
+            Msg::Redo => self.history.redo(&mut self.shape_storage),
             Msg::SaveToJson => {
-                self.json = self.shape_storage.serialize_to_json();
-                let a = window()
-                    .unwrap()
-                    .document()
-                    .unwrap()
-                    .create_element("a")
-                    .unwrap();
-                a.set_attribute(
-                    "href",
-                    &format!("data:text/json;charset=utf-8,{}", self.json.clone()),
-                )
-                .unwrap();
-                a.set_attribute("download", "shapes.json").unwrap();
-                let a_element = a.dyn_into::<HtmlElement>().unwrap();
-                a_element.click();
-                a_element.remove();
+                let layers = self.shape_storage.get_layer_data();
+                self.json = Document::new(self.canvas, layers).to_json();
+                download("shapes.json", "text/json", &self.json);
 
                 true
             }
-            Msg::LoadFromJson { value } => {
-                log::info!("value: {}", &value);
-                self.shape_storage.deserialize_from_json(&value);
-                self.json = value;
+            Msg::SaveToSvg => {
+                let svg = model::svg::export_svg(
+                    self.shape_storage
+                        .get_visible_shapes()
+                        .map(|shape| shape.as_ref()),
+                    self.canvas.width as f64,
+                    self.canvas.height as f64,
+                );
+                download("shapes.svg", "image/svg+xml", &svg);
+
+                false
+            }
+            Msg::SaveToPng => {
+                match self.render_png() {
+                    Ok(url) => download_url("shapes.png", &url),
+                    Err(error) => log::error!("Couldn't export PNG: {:?}", error),
+                }
+
+                false
+            }
+            Msg::PngValueChanged { key, value } => {
+                let value: f64 = match value.parse() {
+                    Ok(value) if value > 0.0 => value,
+                    _ => return false,
+                };
+                let scale = match key.as_str() {
+                    PNG_SCALE_INPUT_ID => value,
+                    PNG_WIDTH_INPUT_ID => value / self.canvas.width as f64,
+                    _ => return false,
+                };
+                let largest = self.canvas.width.max(self.canvas.height) as f64;
+                self.png_scale = scale.min(PNG_MAX_SIZE / largest);
+
+                true
+            }
+            Msg::PngTransparencyToggled => {
+                self.png_transparent = !self.png_transparent;
+
+                true
+            }
+            Msg::FillValueChanged { key, value } => {
+                match key.as_str() {
+                    FILL_COLOR_INPUT_ID => self.fill_color = value,
+                    FILL_TOLERANCE_INPUT_ID => match value.parse() {
+                        Ok(tolerance) => self.fill_tolerance = tolerance,
+                        Err(_) => return false,
+                    },
+                    _ => return false,
+                }
+
+                true
+            }
+            Msg::ClipAlgorithmChanged { algorithm } => {
+                self.clip_algorithm = algorithm;
+
+                true
+            }
+            Msg::RemoveClipWindow => {
+                self.clip_window = None;
+
+                true
+            }
+            Msg::FillConnectivityToggled => {
+                self.fill_connectivity = match self.fill_connectivity {
+                    Connectivity::Four => Connectivity::Eight,
+                    Connectivity::Eight => Connectivity::Four,
+                };
+
+                true
+            }
+            Msg::LoadFile { name, value } => {
+                log::info!("loading {}", &name);
+                let before = self.shape_storage.serialize_to_json();
+                // our own files are JSON, anything that looks like markup is taken for SVG
+                if name.to_lowercase().ends_with(".svg") || value.trim_start().starts_with('<') {
+                    match import_svg(&value) {
+                        Ok(import) => {
+                            self.shape_storage.add_shapes(import.shapes);
+                            self.load_messages = import.warnings;
+                        }
+                        Err(error) => {
+                            self.load_messages = vec![format!("Couldn't read {}: {}", name, error)];
+                            return true;
+                        }
+                    }
+                } else {
+                    match Document::from_json(&value) {
+                        Ok(document) => {
+                            self.canvas = document.canvas;
+                            // an empty drawing takes the layers of the file, otherwise they go on top
+                            if self.shape_storage.shape_count() == 0 {
+                                self.shape_storage.replace_layers(document.layers);
+                            } else {
+                                self.shape_storage.add_layers(document.layers);
+                            }
+                            self.load_messages.clear();
+                            self.json = value;
+                        }
+                        Err(error) => {
+                            self.load_messages = vec![format!("Couldn't read {}: {}", name, error)];
+                            return true;
+                        }
+                    }
+                }
+                let after = self.shape_storage.serialize_to_json();
+                if before != after {
+                    self.history.push(Command::Replace { before, after });
+                }
 
                 true
             }
@@ -367,7 +1572,7 @@ impl Component for App {
         }
     }
 
-    fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
         let canvas = window()
             .unwrap()
             .document()
@@ -384,25 +1589,929 @@ impl Component for App {
             .dyn_into::<CanvasRenderingContext2d>()
             .unwrap();
 
+        if first_render {
+            let link = ctx.link().clone();
+            let options = EventListenerOptions::enable_prevent_default();
+            self._wheel_listener = Some(EventListener::new_with_options(
+                &canvas,
+                "wheel",
+                options,
+                move |event| {
+                    let event = event.dyn_ref::<WheelEvent>().unwrap();
+                    event.prevent_default();
+                    link.send_message(Msg::Wheel {
+                        x: event.offset_x() as f64,
+                        y: event.offset_y() as f64,
+                        delta: event.delta_y(),
+                    });
+                },
+            ));
+        }
+
         rendering_context.clear_rect(0.0, 0.0, canvas.width() as f64, canvas.height() as f64);
-        for shape in self.shape_storage.get_shapes() {
-            if shape.is_drawable() {
-                shape.draw(&rendering_context);
+        if self.render_mode != RenderMode::Canvas {
+            self.draw_raster(&rendering_context, canvas.width(), canvas.height());
+        }
+        let mut renderer = CanvasRenderer::new(rendering_context);
+        // everything below is in world coordinates
+        renderer.transform(&self.viewport.transform());
+        if self.show_grid {
+            self.draw_grid(&mut renderer, canvas.width(), canvas.height());
+        }
+        if self.render_mode == RenderMode::Canvas {
+            for shape in self.shape_storage.get_visible_shapes() {
+                if shape.is_drawable() {
+                    self.draw_clipped(shape.as_ref(), &mut renderer);
+                }
+            }
+
+            if let Some(shape) = self.shape_storage.get_highlighted() {
+                if shape.is_drawable() {
+                    shape.draw_highlighted(&mut renderer);
+                }
+            }
+
+            let pixel = self.viewport.to_world_length(1.0);
+            for shape in self.shape_storage.get_selection() {
+                if shape.is_drawable() {
+                    shape.draw_selected(&mut renderer);
+                    shape.draw_handles(&mut renderer, pixel);
+                }
+            }
+        }
+
+        if let Some(window) = self.clip_window {
+            renderer.save();
+            renderer.set_stroke_color("#0080ff");
+            renderer.set_line_dash(&[8.0, 4.0]);
+            renderer.stroke_rect(window.x_min, window.y_min, window.width(), window.height());
+            renderer.restore();
+        }
+
+        if let Some(((x1, y1), (x2, y2))) = self.marquee {
+            renderer.save();
+            renderer.set_line_dash(&[4.0, 4.0]);
+            renderer.stroke_rect(x1, y1, x2 - x1, y2 - y1);
+            renderer.restore();
+        }
+
+        if self.mode == Mode::Rotate || self.mode == Mode::Scale {
+            if let Some((px, py)) = self.get_pivot() {
+                renderer.begin_path();
+                renderer.move_to(px - 6.0, py);
+                renderer.line_to(px + 6.0, py);
+                renderer.move_to(px, py - 6.0);
+                renderer.line_to(px, py + 6.0);
+                renderer.stroke();
+            }
+        }
+
+        if let Some(target) = self.snap_target {
+            self.draw_snap_indicator(&mut renderer, target);
+        }
+
+        self.draw_inspector(&canvas);
+    }
+}
+
+impl App {
+    // copies the pixels around the cursor onto the inspector, enlarged without smoothing
+    fn draw_inspector(&self, canvas: &HtmlCanvasElement) {
+        let inspector = window()
+            .unwrap()
+            .document()
+            .unwrap()
+            .query_selector("#inspector")
+            .unwrap()
+            .unwrap()
+            .dyn_into::<HtmlCanvasElement>()
+            .unwrap();
+        let rendering_context = inspector
+            .get_context("2d")
+            .unwrap()
+            .unwrap()
+            .dyn_into::<CanvasRenderingContext2d>()
+            .unwrap();
+
+        let size = INSPECTOR_SIZE as f64;
+        let source_size = (INSPECTOR_SIZE / INSPECTOR_ZOOM) as f64;
+        let x = self.cursor_pos.0.floor() - (source_size / 2.0).floor();
+        let y = self.cursor_pos.1.floor() - (source_size / 2.0).floor();
+        rendering_context.clear_rect(0.0, 0.0, size, size);
+        rendering_context.set_image_smoothing_enabled(false);
+        rendering_context
+            .draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                canvas,
+                x,
+                y,
+                source_size,
+                source_size,
+                0.0,
+                0.0,
+                size,
+                size,
+            )
+            .expect("Couldn't draw the inspector!");
+    }
+
+    // rasterizes the shapes in software and copies the pixels onto the canvas
+    fn draw_raster(&self, rendering_context: &CanvasRenderingContext2d, width: u32, height: u32) {
+        let anti_aliased = self.render_mode == RenderMode::AntiAliased;
+        let mut framebuffer = Framebuffer::new(width, height);
+        for shape in self.shape_storage.get_visible_shapes() {
+            self.rasterize_clipped(shape.as_ref(), &mut framebuffer, anti_aliased);
+        }
+        for shape in self.shape_storage.get_selection() {
+            self.viewed(shape)
+                .rasterize_selected(&mut framebuffer, SELECTION_COLOR, anti_aliased);
+        }
+
+        let image = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(framebuffer.pixels()),
+            framebuffer.width(),
+            framebuffer.height(),
+        )
+        .expect("Couldn't create image data!");
+        rendering_context
+            .put_image_data(&image, 0.0, 0.0)
+            .expect("Couldn't put image data!");
+    }
+
+    // draws the shape dimmed with the edges inside the clip window on top
+    fn draw_clipped(&self, shape: &dyn Shape, renderer: &mut dyn Renderer) {
+        let (window, edges) = match (self.clip_window, clip_edges(shape)) {
+            (Some(window), Some(edges)) => (window, edges),
+            _ => return shape.draw(renderer),
+        };
+
+        dimmed(shape).draw(renderer);
+        renderer.save();
+        shape.get_style().apply(renderer);
+        renderer.begin_path();
+        for (a, b) in edges {
+            if let Some((start, end)) = window.clip(self.clip_algorithm, a, b) {
+                renderer.move_to(start.0, start.1);
+                renderer.line_to(end.0, end.1);
+            }
+        }
+        renderer.stroke();
+        renderer.restore();
+    }
+
+    // grid lines across the visible part of the drawing, left out when they'd crowd together
+    fn draw_grid(&self, renderer: &mut dyn Renderer, width: u32, height: u32) {
+        let spacing = self.snap.spacing;
+        if spacing * self.viewport.zoom < MIN_GRID_GAP {
+            return;
+        }
+
+        let (x1, y1) = self.viewport.to_world((0.0, 0.0));
+        let (x2, y2) = self.viewport.to_world((width as f64, height as f64));
+        renderer.save();
+        renderer.set_stroke_color(GRID_COLOR);
+        renderer.set_line_width(self.viewport.to_world_length(1.0));
+        renderer.begin_path();
+        let mut x = (x1 / spacing).ceil() * spacing;
+        while x <= x2 {
+            renderer.move_to(x, y1);
+            renderer.line_to(x, y2);
+            x += spacing;
+        }
+        let mut y = (y1 / spacing).ceil() * spacing;
+        while y <= y2 {
+            renderer.move_to(x1, y);
+            renderer.line_to(x2, y);
+            y += spacing;
+        }
+        renderer.stroke();
+        renderer.restore();
+    }
+
+    // marks the snap target, the mark telling what it is
+    fn draw_snap_indicator(&self, renderer: &mut dyn Renderer, target: SnapTarget) {
+        let size = self.viewport.to_world_length(5.0);
+        let (x, y) = target.point;
+        renderer.save();
+        renderer.set_stroke_color(SNAP_COLOR);
+        renderer.set_line_width(self.viewport.to_world_length(1.5));
+        renderer.begin_path();
+        match target.kind {
+            SnapKind::Grid => {
+                renderer.move_to(x - size, y);
+                renderer.line_to(x + size, y);
+                renderer.move_to(x, y - size);
+                renderer.line_to(x, y + size);
+            }
+            SnapKind::Endpoint => renderer.rect(x - size, y - size, 2.0 * size, 2.0 * size),
+            SnapKind::Midpoint => {
+                renderer.move_to(x, y - size);
+                renderer.line_to(x + size, y + size);
+                renderer.line_to(x - size, y + size);
+                renderer.close_path();
+            }
+            SnapKind::Center => renderer.arc(x, y, size, 0.0, 2.0 * std::f64::consts::PI),
+            SnapKind::Intersection => {
+                renderer.move_to(x - size, y - size);
+                renderer.line_to(x + size, y + size);
+                renderer.move_to(x - size, y + size);
+                renderer.line_to(x + size, y - size);
+            }
+        }
+        renderer.stroke();
+        renderer.restore();
+    }
+
+    // index of the shape being drawn, not yet complete
+    fn drawing_idx(&self) -> Option<usize> {
+        let idx = self.shape_storage.get_current_idx()?;
+        match self.shape_storage.get_shape(idx)?.get_state() {
+            ShapeState::Complete => None,
+            _ => Some(idx),
+        }
+    }
+
+    // what the point snaps to among the shapes `include` accepts, and the grid if `grid`
+    fn find_snap(
+        &self,
+        point: (f64, f64),
+        include: impl Fn(usize) -> bool,
+        grid: bool,
+    ) -> Option<SnapTarget> {
+        let shapes = self
+            .shape_storage
+            .get_shapes()
+            .enumerate()
+            .filter(|(idx, _)| self.shape_storage.is_shape_visible(*idx) && include(*idx))
+            .map(|(_, shape)| shape.as_ref());
+        let settings = SnapSettings {
+            grid: self.snap.grid && grid,
+            ..self.snap
+        };
+        snap(
+            point,
+            shapes,
+            &settings,
+            self.viewport.to_world_length(SNAP_RADIUS),
+        )
+    }
+
+    // moves the point onto the target, which is kept for the indicator
+    fn apply_snap(&mut self, point: (f64, f64), target: Option<SnapTarget>) -> (f64, f64) {
+        self.snap_target = target;
+        target.map_or(point, |target| target.point)
+    }
+
+    // a copy of the shape placed on the screen, for the software rasterizer
+    fn viewed(&self, shape: &dyn Shape) -> Box<dyn Shape> {
+        let mut viewed = shape.to_data().into_shape();
+        viewed.apply_transform(self.viewport.transform());
+        viewed
+    }
+
+    // keeps hits as close to shapes on the screen at any zoom
+    fn update_hit_tolerance(&mut self) {
+        let tolerance = self.viewport.to_world_length(HIT_TOLERANCE);
+        self.shape_storage.set_hit_tolerance(tolerance);
+    }
+
+    // like `draw_clipped`, for the software rasterizer
+    fn rasterize_clipped(
+        &self,
+        shape: &dyn Shape,
+        framebuffer: &mut Framebuffer,
+        anti_aliased: bool,
+    ) {
+        let color = shape.get_style().stroke_rgba();
+        let (window, edges) = match (self.clip_window, clip_edges(shape)) {
+            (Some(window), Some(edges)) => (window, edges),
+            _ => {
+                return self
+                    .viewed(shape)
+                    .rasterize(framebuffer, color, anti_aliased)
             }
+        };
+
+        let faint = self.viewed(dimmed(shape).as_ref());
+        faint.rasterize(framebuffer, faint.get_style().stroke_rgba(), anti_aliased);
+        if !shape.is_drawable() {
+            return;
         }
+        for (a, b) in edges {
+            if let Some((start, end)) = window.clip(self.clip_algorithm, a, b) {
+                let (start, end) = (self.viewport.to_screen(start), self.viewport.to_screen(end));
+                if anti_aliased {
+                    framebuffer.draw_line_wu(start, end, color);
+                } else {
+                    framebuffer.draw_line(to_pixel(start), to_pixel(end), color);
+                }
+            }
+        }
+    }
 
-        if let Some(shape) = self.shape_storage.get_highlighted() {
+    // how the selected line is clipped, in the terms of the chosen algorithm
+    fn clip_report(&self) -> Vec<String> {
+        let window = match self.clip_window {
+            Some(window) => window,
+            None => return Vec::new(),
+        };
+        let (a, b) = match self
+            .shape_storage
+            .get_selected()
+            .map(|shape| shape.get_outline())
+        {
+            Some(outline)
+                if self.shape_storage.get_selected_idxs().len() == 1 && outline.len() == 2 =>
+            {
+                (outline[0], outline[1])
+            }
+            _ => return vec!["Select a line to see how it's clipped".to_string()],
+        };
+
+        let mut report = Vec::new();
+        match self.clip_algorithm {
+            ClipAlgorithm::CohenSutherland => report.push(format!(
+                "Outcodes: {} {}",
+                outcode_bits(window.outcode(a)),
+                outcode_bits(window.outcode(b))
+            )),
+            ClipAlgorithm::LiangBarsky => match window.liang_barsky(a, b) {
+                Some((t0, t1)) => report.push(format!("t0 = {:.3}, t1 = {:.3}", t0, t1)),
+                None => report.push("t0 > t1".to_string()),
+            },
+        }
+        match window.clip(self.clip_algorithm, a, b) {
+            Some((start, end)) => report.push(format!("Visible: {:.0?} - {:.0?}", start, end)),
+            None => report.push("Rejected".to_string()),
+        }
+
+        report
+    }
+
+    // size of the exported image, rounded to whole pixels
+    fn png_size(&self) -> (u32, u32) {
+        let size = |length: u32| (length as f64 * self.png_scale).round().max(1.0) as u32;
+        (size(self.canvas.width), size(self.canvas.height))
+    }
+
+    // draws the shapes alone on an offscreen canvas, without the selection and highlight
+    fn render_png(&self) -> Result<String, JsValue> {
+        let (width, height) = self.png_size();
+        let (canvas, rendering_context) = offscreen_canvas(width, height)?;
+
+        if !self.png_transparent {
+            rendering_context.set_fill_style(&JsValue::from_str("white"));
+            rendering_context.fill_rect(0.0, 0.0, width as f64, height as f64);
+        }
+        let mut renderer = CanvasRenderer::new(rendering_context);
+        renderer.transform(&Transform::scaling(self.png_scale, self.png_scale));
+        for shape in self.shape_storage.get_visible_shapes() {
             if shape.is_drawable() {
-                shape.draw_highlighted(&rendering_context);
+                shape.draw(&mut renderer);
             }
         }
 
-        if let Some(shape) = self.shape_storage.get_selected() {
+        canvas.to_data_url_with_type("image/png")
+    }
+
+    // the pixels on the screen as currently rendered, without selection and highlight
+    fn composite(&self) -> Result<Framebuffer, JsValue> {
+        let (width, height) = (self.canvas.width, self.canvas.height);
+        if self.render_mode != RenderMode::Canvas {
+            let anti_aliased = self.render_mode == RenderMode::AntiAliased;
+            let mut framebuffer = Framebuffer::new(width, height);
+            for shape in self.shape_storage.get_visible_shapes() {
+                self.rasterize_clipped(shape.as_ref(), &mut framebuffer, anti_aliased);
+            }
+            return Ok(framebuffer);
+        }
+
+        let (_, rendering_context) = offscreen_canvas(width, height)?;
+        let mut renderer = CanvasRenderer::new(rendering_context.clone());
+        renderer.transform(&self.viewport.transform());
+        for shape in self.shape_storage.get_visible_shapes() {
             if shape.is_drawable() {
-                shape.draw_selected(&rendering_context);
+                self.draw_clipped(shape.as_ref(), &mut renderer);
+            }
+        }
+        let image = rendering_context.get_image_data(0.0, 0.0, width as f64, height as f64)?;
+
+        Ok(Framebuffer::from_pixels(width, height, image.data().0))
+    }
+
+    // floods the region around the clicked screen pixel and adds it as a fill shape
+    fn bucket_fill(&mut self, x: f64, y: f64) {
+        let framebuffer = match self.composite() {
+            Ok(framebuffer) => framebuffer,
+            Err(error) => {
+                log::error!("Couldn't read the canvas: {:?}", error);
+                return;
+            }
+        };
+        let seed = to_pixel(self.viewport.to_screen((x, y)));
+        let pixels = framebuffer.flood_region(seed, self.fill_tolerance, self.fill_connectivity);
+        if pixels.is_empty() {
+            return;
+        }
+
+        // flooded on the screen, the spans are mapped back into the world
+        let mut fill = Fill::from_pixels(&pixels, &self.fill_color);
+        let to_world = self
+            .viewport
+            .transform()
+            .inverse()
+            .expect("The zoom is never zero!");
+        fill.set_transform(to_world);
+        let idx = self.shape_storage.next_idx();
+        let layer = self.shape_storage.get_active_layer();
+        self.history.execute(
+            Command::Insert {
+                idx,
+                shape: on_layer(&fill.get_json(), layer),
+            },
+            &mut self.shape_storage,
+        );
+    }
+
+    // applies a change moving shapes around, e.g. between layers, and records the whole document
+    // before and after it
+    fn record_arrangement(&mut self, change: impl FnOnce(&mut ShapeStorage)) -> bool {
+        let before = self.shape_storage.serialize_to_json();
+        change(&mut self.shape_storage);
+        let after = self.shape_storage.serialize_to_json();
+        if before != after {
+            self.history.push(Command::Replace { before, after });
+        }
+
+        true
+    }
+
+    fn arrange_selected(&mut self, z_order: ZOrder) -> bool {
+        let idxs = self.shape_storage.get_selected_idxs().to_vec();
+        self.record_arrangement(|storage| storage.arrange(&idxs, z_order))
+    }
+
+    fn copy_selected(&mut self) {
+        if self.shape_storage.get_selected_idxs().is_empty() {
+            return;
+        }
+        self.clipboard = to_clipboard(self.shape_storage.get_selection());
+        write_clipboard(&self.clipboard);
+    }
+
+    // adds the shapes to the active layer and selects them, centered on `point` or next to where
+    // they were copied from
+    fn paste(&mut self, mut shapes: Vec<Box<dyn Shape>>, point: Option<(f64, f64)>) -> bool {
+        if shapes.is_empty() {
+            return false;
+        }
+        place(&mut shapes, point);
+
+        let idx = self.shape_storage.next_idx();
+        let layer = self.shape_storage.get_active_layer();
+        let commands = shapes
+            .iter()
+            .enumerate()
+            .map(|(offset, shape)| Command::Insert {
+                idx: idx + offset,
+                shape: on_layer(&shape.get_json(), layer),
+            })
+            .collect();
+        self.shape_storage.deselect();
+        self.history
+            .execute(Command::Batch(commands), &mut self.shape_storage);
+        self.pivot = None;
+
+        true
+    }
+
+    // pastes copies of the selected shapes without touching the clipboard
+    fn duplicate_selected(&mut self) -> bool {
+        let shapes = self
+            .shape_storage
+            .get_selection()
+            .map(|shape| shape.to_data().into_shape())
+            .collect();
+        self.paste(shapes, None)
+    }
+
+    // replaces the selected shapes with a group of them, placed where the topmost one was
+    fn group_selected(&mut self) -> bool {
+        let mut idxs = self.shape_storage.get_selected_idxs().to_vec();
+        if idxs.len() < 2 {
+            return false;
+        }
+        idxs.sort_unstable();
+
+        let children = idxs
+            .iter()
+            .map(|idx| {
+                self.shape_storage
+                    .get_shape(*idx)
+                    .unwrap()
+                    .to_data()
+                    .into_shape()
+            })
+            .collect();
+        let group = Group::new(children);
+        let top = *idxs.last().unwrap();
+        let layer = self.shape_storage.layer_of(top).unwrap();
+        // removing from the back keeps the remaining indices valid
+        let mut commands: Vec<Command> = idxs
+            .iter()
+            .rev()
+            .map(|idx| Command::Remove {
+                idx: *idx,
+                shape: self.shape_storage.shape_json(*idx).unwrap(),
+            })
+            .collect();
+        // the shapes below the topmost one are gone by then
+        commands.push(Command::Insert {
+            idx: top + 1 - idxs.len(),
+            shape: on_layer(&group.get_json(), layer),
+        });
+        self.history
+            .execute(Command::Batch(commands), &mut self.shape_storage);
+
+        true
+    }
+
+    // replaces the selected groups with their children, in the same place
+    fn ungroup_selected(&mut self) -> bool {
+        let mut idxs: Vec<usize> = self
+            .shape_storage
+            .get_selected_idxs()
+            .iter()
+            .copied()
+            .filter(|idx| {
+                self.shape_storage
+                    .get_shape(*idx)
+                    .unwrap()
+                    .as_group()
+                    .is_some()
+            })
+            .collect();
+        // working from the back keeps the remaining indices valid
+        idxs.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut commands = Vec::new();
+        for idx in idxs {
+            let group = self
+                .shape_storage
+                .get_shape(idx)
+                .unwrap()
+                .as_group()
+                .unwrap();
+            let layer = self.shape_storage.layer_of(idx).unwrap();
+            commands.push(Command::Remove {
+                idx,
+                shape: self.shape_storage.shape_json(idx).unwrap(),
+            });
+            for (offset, child) in group.children().enumerate() {
+                commands.push(Command::Insert {
+                    idx: idx + offset,
+                    shape: on_layer(&child.get_json(), layer),
+                });
             }
         }
+        if commands.is_empty() {
+            return false;
+        }
+        self.history
+            .execute(Command::Batch(commands), &mut self.shape_storage);
+
+        true
     }
+
+    // applies `edit` and records the changes of the shapes at `idxs` in the history
+    fn record_edits(&mut self, idxs: &[usize], merge: bool, edit: impl FnOnce(&mut ShapeStorage)) {
+        let snapshot = |storage: &ShapeStorage| -> Vec<Option<String>> {
+            idxs.iter()
+                .map(|idx| storage.get_shape(*idx).map(|shape| shape.get_json()))
+                .collect()
+        };
+        let before = snapshot(&self.shape_storage);
+        edit(&mut self.shape_storage);
+        let after = snapshot(&self.shape_storage);
+
+        let mut commands: Vec<Command> = idxs
+            .iter()
+            .zip(before.into_iter().zip(after))
+            .filter_map(|(idx, snapshots)| match snapshots {
+                (Some(before), Some(after)) if before != after => Some(Command::Edit {
+                    idx: *idx,
+                    before,
+                    after,
+                }),
+                _ => None,
+            })
+            .collect();
+        let command = match commands.len() {
+            0 => return,
+            1 => commands.pop().unwrap(),
+            _ => Command::Batch(commands),
+        };
+        if merge {
+            self.history.push_merged(command);
+        } else {
+            self.history.push(command);
+        }
+    }
+
+    // `merge` folds the edits of a single mouse drag into one history entry
+    fn edit_selected(&mut self, merge: bool, edit: impl Fn(&mut dyn Shape)) {
+        let idxs = self.shape_storage.get_selected_idxs().to_vec();
+        self.record_edits(&idxs, merge, |storage| {
+            for idx in idxs.iter() {
+                if let Some(shape) = storage.get_shape_mut(*idx) {
+                    edit(shape);
+                }
+            }
+        });
+    }
+
+    fn select_at(&mut self, x: f64, y: f64, toggle: bool) {
+        if toggle {
+            self.shape_storage.intersect_and_toggle(x, y);
+        } else {
+            self.shape_storage.intersect_and_select(x, y);
+        }
+        self.pivot = None;
+    }
+
+    fn delete_selected(&mut self) {
+        let mut idxs = self.shape_storage.get_selected_idxs().to_vec();
+        // removing from the back keeps the remaining indices valid
+        idxs.sort_unstable_by(|a, b| b.cmp(a));
+        let commands: Vec<Command> = idxs
+            .iter()
+            .filter_map(|idx| {
+                let shape = self.shape_storage.shape_json(*idx)?;
+                Some(Command::Remove { idx: *idx, shape })
+            })
+            .collect();
+        if !commands.is_empty() {
+            self.history
+                .execute(Command::Batch(commands), &mut self.shape_storage);
+        }
+    }
+
+    // replaces the selected shapes with their parts inside the primary selected one
+    fn clip_selected(&mut self) {
+        let clip_idx = match self.shape_storage.get_selected_idx() {
+            Some(idx) => idx,
+            None => return,
+        };
+        let clip = self.shape_storage.get_shape(clip_idx).unwrap();
+        let mut idxs: Vec<usize> = self
+            .shape_storage
+            .get_selected_idxs()
+            .iter()
+            .copied()
+            .filter(|idx| *idx != clip_idx)
+            .collect();
+        // working from the back keeps the remaining indices valid
+        idxs.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut commands = Vec::new();
+        for idx in idxs {
+            let target = self.shape_storage.get_shape(idx).unwrap();
+            let pieces = match clip_shape(target, clip) {
+                Some(pieces) => pieces,
+                None => continue,
+            };
+            // the pieces stay on the layer of the shape they came from
+            let layer = self.shape_storage.layer_of(idx).unwrap();
+            commands.push(Command::Remove {
+                idx,
+                shape: self.shape_storage.shape_json(idx).unwrap(),
+            });
+            for (offset, piece) in pieces.iter().enumerate() {
+                commands.push(Command::Insert {
+                    idx: idx + offset,
+                    shape: on_layer(&piece.get_json(), layer),
+                });
+            }
+        }
+        if !commands.is_empty() {
+            self.history
+                .execute(Command::Batch(commands), &mut self.shape_storage);
+        }
+    }
+
+    // replaces the selected closed shapes with the result of `op` on them, in selection order,
+    // placed where the lowest of them was
+    fn combine_selected(&mut self, op: BooleanOp) {
+        let mut idxs: Vec<usize> = self
+            .shape_storage
+            .get_selected_idxs()
+            .iter()
+            .copied()
+            .filter(|idx| is_combinable(self.shape_storage.get_shape(*idx).unwrap()))
+            .collect();
+        if idxs.len() < 2 {
+            return;
+        }
+
+        let operands: Vec<&dyn Shape> = idxs
+            .iter()
+            .map(|idx| self.shape_storage.get_shape(*idx).unwrap())
+            .collect();
+        let result = combine_shapes(op, &operands, self.flatten_tolerance);
+
+        // working from the back keeps the remaining indices valid
+        idxs.sort_unstable_by(|a, b| b.cmp(a));
+        let mut commands: Vec<Command> = idxs
+            .iter()
+            .map(|idx| Command::Remove {
+                idx: *idx,
+                shape: self.shape_storage.shape_json(*idx).unwrap(),
+            })
+            .collect();
+        // nothing left, e.g. the intersection of shapes apart, removes them all
+        if let Some(result) = result {
+            let idx = *idxs.last().unwrap();
+            let layer = self.shape_storage.layer_of(idx).unwrap();
+            commands.push(Command::Insert {
+                idx,
+                shape: on_layer(&result.get_json(), layer),
+            });
+        }
+        self.history
+            .execute(Command::Batch(commands), &mut self.shape_storage);
+    }
+
+    // properties every selected shape has, values that differ are left empty
+    fn shared_props(&self) -> Vec<(String, String)> {
+        let mut selection = self.shape_storage.get_selection();
+        let mut props = match selection.next() {
+            Some(shape) => shape.get_props(),
+            None => return Vec::new(),
+        };
+        for shape in selection {
+            let other_props = shape.get_props();
+            props.retain_mut(|(key, value)| {
+                match other_props.iter().find(|(other_key, _)| other_key == key) {
+                    Some((_, other_value)) => {
+                        if other_value != value {
+                            value.clear();
+                        }
+                        true
+                    }
+                    None => false,
+                }
+            });
+        }
+
+        props
+    }
+
+    // user-placed pivot, or the center of the selection
+    fn get_pivot(&self) -> Option<(f64, f64)> {
+        let (x1, y1, x2, y2) = self.shape_storage.get_selection_bounds()?;
+        self.pivot.or(Some(((x1 + x2) / 2.0, (y1 + y2) / 2.0)))
+    }
+
+    // dragging away from the pivot grows the shape, dragging towards it shrinks it
+    fn drag_scale_factor(&self, pivot: (f64, f64), cursor: (f64, f64)) -> (f64, f64) {
+        let (lx, ly) = (
+            self.last_cursor_pos.0 - pivot.0,
+            self.last_cursor_pos.1 - pivot.1,
+        );
+        let (cx, cy) = (cursor.0 - pivot.0, cursor.1 - pivot.1);
+        if self.uniform_scale {
+            let last = (lx * lx + ly * ly).sqrt();
+            let current = (cx * cx + cy * cy).sqrt();
+            if last < 1.0 || current < 1.0 {
+                return (1.0, 1.0);
+            }
+            let factor = current / last;
+            return (factor, factor);
+        }
+
+        // too close to the pivot on an axis to get a meaningful ratio
+        let ratio = |last: f64, current: f64| {
+            if last.abs() < 1.0 || current.abs() < 1.0 {
+                1.0
+            } else {
+                current / last
+            }
+        };
+        return (ratio(lx, cx), ratio(ly, cy));
+    }
+}
+
+// edges clipped to the clip window, for lines and rectangles
+fn clip_edges(shape: &dyn Shape) -> Option<Vec<Segment>> {
+    let outline = shape.get_outline();
+    match shape.get_type() {
+        ShapeType::Line if outline.len() == 2 => Some(vec![(outline[0], outline[1])]),
+        ShapeType::Rectangle => Some(
+            (0..outline.len())
+                .map(|i| (outline[i], outline[(i + 1) % outline.len()]))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+// a copy of the shape drawn faintly
+fn dimmed(shape: &dyn Shape) -> Box<dyn Shape> {
+    let mut dimmed = shape.to_data().into_shape();
+    let mut style = shape.get_style();
+    style.opacity *= CLIPPED_OPACITY;
+    dimmed.set_style(style);
+    dimmed
+}
+
+// whether the event comes from typing in the side panel
+fn from_input(event: &Event) -> bool {
+    event
+        .target()
+        .and_then(|target| target.dyn_into::<HtmlElement>().ok())
+        .map(|element| element.tag_name() == "INPUT" || element.tag_name() == "TEXTAREA")
+        .unwrap_or(false)
+}
+
+// a canvas outside the page to draw on
+fn offscreen_canvas(
+    width: u32,
+    height: u32,
+) -> Result<(HtmlCanvasElement, CanvasRenderingContext2d), JsValue> {
+    let canvas = window()
+        .unwrap()
+        .document()
+        .unwrap()
+        .create_element("canvas")?
+        .dyn_into::<HtmlCanvasElement>()
+        .map_err(JsValue::from)?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    let rendering_context = canvas
+        .get_context("2d")?
+        .ok_or("no 2d context")?
+        .dyn_into::<CanvasRenderingContext2d>()
+        .map_err(JsValue::from)?;
+
+    Ok((canvas, rendering_context))
+}
+
+// the Clipboard API is reached through JavaScript, web-sys only binds it as unstable
+fn call_clipboard(method: &str, args: &js_sys::Array) -> Option<js_sys::Promise> {
+    let navigator = window()?.navigator();
+    let clipboard = js_sys::Reflect::get(&navigator, &JsValue::from_str("clipboard")).ok()?;
+    let function = js_sys::Reflect::get(&clipboard, &JsValue::from_str(method)).ok()?;
+    let function: js_sys::Function = function.dyn_into().ok()?;
+    function.apply(&clipboard, args).ok()?.dyn_into().ok()
+}
+
+fn write_clipboard(text: &str) {
+    if let Some(promise) =
+        call_clipboard("writeText", &js_sys::Array::of1(&JsValue::from_str(text)))
+    {
+        let failed = Closure::once(|error: JsValue| {
+            log::warn!("Couldn't copy to the clipboard: {:?}", error)
+        });
+        let _ = promise.catch(&failed);
+        failed.forget();
+    }
+}
+
+// `done` gets the text on the system clipboard, or None if it can't be read
+fn read_clipboard(done: Callback<Option<String>>) {
+    let promise = match call_clipboard("readText", &js_sys::Array::new()) {
+        Some(promise) => promise,
+        None => return done.emit(None),
+    };
+    let read = done.clone();
+    let resolved = Closure::once(move |text: JsValue| read.emit(text.as_string()));
+    let rejected = Closure::once(move |_: JsValue| done.emit(None));
+    let _ = promise.then2(&resolved, &rejected);
+    resolved.forget();
+    rejected.forget();
+}
+
+// lets the browser save `content` as a file
+fn download(filename: &str, mime_type: &str, content: &str) {
+    // colors like #ff0000 would otherwise end the data URL early
+    let content = String::from(js_sys::encode_uri_component(content));
+    download_url(
+        filename,
+        &format!("data:{};charset=utf-8,{}", mime_type, content),
+    );
+}
+
+fn download_url(filename: &str, url: &str) {
+    let a = window()
+        .unwrap()
+        .document()
+        .unwrap()
+        .create_element("a")
+        .unwrap();
+    a.set_attribute("href", url).unwrap();
+    a.set_attribute("download", filename).unwrap();
+    let a_element = a.dyn_into::<HtmlElement>().unwrap();
+    a_element.click();
+    a_element.remove();
 }
 
 fn main() {
@@ -0,0 +1,353 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::ShapeData,
+    geometry::{bounds_of, distance, distance_to_polyline, flatten_bezier, point_in_polygon},
+    polygon::MAX_VERTICES,
+    renderer::Renderer,
+    shape::{parse_point_key, point_key, Shape, ShapeState, ShapeType},
+    style::Style,
+    svg,
+    transform::Transform,
+};
+
+pub const CONTROL_POINT_COUNT_KEY: &str = "Control points";
+pub const CONTROL_POINT_KEY_PREFIX: &str = "Control point";
+
+const SEGMENTS: usize = 64;
+const MIN_CONTROL_POINTS: usize = 3;
+
+/// Bézier curve of arbitrary degree, defined by its control points.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Bezier {
+    control_points: Vec<(f64, f64)>,
+    #[serde(skip)]
+    cursor: Option<(f64, f64)>,
+    #[serde(default)]
+    transform: Transform,
+    #[serde(default)]
+    style: Style,
+    state: ShapeState,
+}
+
+impl Bezier {
+    pub fn new() -> Self {
+        Self {
+            control_points: Vec::new(),
+            cursor: None,
+            transform: Transform::identity(),
+            style: Style::new(),
+            state: ShapeState::New,
+        }
+    }
+
+    // control points including the point following the cursor while drawing
+    fn drawn_control_points(&self) -> Vec<(f64, f64)> {
+        let mut points = self.control_points.clone();
+        if self.state == ShapeState::Drawing {
+            if let Some(cursor) = self.cursor {
+                points.push(cursor);
+            }
+        }
+
+        points
+    }
+
+    // Bézier curves are affine invariant, so transforming the samples is exact
+    fn world_curve(&self) -> Vec<(f64, f64)> {
+        flatten_bezier(&self.control_points, SEGMENTS)
+            .iter()
+            .map(|point| self.transform.apply(*point))
+            .collect()
+    }
+}
+
+impl Shape for Bezier {
+    fn build_path(&self, renderer: &mut dyn Renderer) {
+        // the renderer draws quadratic and cubic curves itself, like `get_svg`
+        match self.drawn_control_points().as_slice() {
+            [start, control, end] => {
+                renderer.move_to(start.0, start.1);
+                renderer.quadratic_curve_to(*control, *end);
+            }
+            [start, first, second, end] => {
+                renderer.move_to(start.0, start.1);
+                renderer.bezier_curve_to(*first, *second, *end);
+            }
+            points => {
+                for (x, y) in flatten_bezier(points, SEGMENTS).iter() {
+                    renderer.line_to(*x, *y);
+                }
+            }
+        }
+    }
+
+    fn draw_selected(&self, renderer: &mut dyn Renderer) {
+        self.draw(renderer);
+        self.draw_outline(renderer, "red", self.style.stroke_width + 2.0);
+    }
+
+    // the control polygon, with a square on every control point
+    fn draw_handles(&self, renderer: &mut dyn Renderer, pixel: f64) {
+        let points: Vec<(f64, f64)> = self
+            .drawn_control_points()
+            .iter()
+            .map(|point| self.transform.apply(*point))
+            .collect();
+        let half = 3.0 * pixel;
+        renderer.save();
+        renderer.set_line_width(pixel);
+        renderer.set_stroke_color("gray");
+        renderer.begin_path();
+        for (x, y) in points.iter() {
+            renderer.line_to(*x, *y);
+        }
+        renderer.stroke();
+        for (x, y) in points.iter() {
+            renderer.stroke_rect(x - half, y - half, 2.0 * half, 2.0 * half);
+        }
+        renderer.restore();
+    }
+
+    fn add_point(&mut self, x: f64, y: f64) {
+        self.control_points.push((x, y));
+        self.cursor = Some((x, y));
+        self.state = ShapeState::Drawing;
+    }
+
+    fn finish(&mut self) {
+        // the clicks preceding a double click land on the same spot
+        self.control_points.dedup();
+        if self.control_points.len() >= MIN_CONTROL_POINTS {
+            self.cursor = None;
+            self.state = ShapeState::Complete;
+        }
+    }
+
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Bezier
+    }
+
+    fn init_from_points(&mut self, origin: (f64, f64), end: (f64, f64)) {
+        self.control_points = vec![origin, end];
+    }
+
+    fn set_end(&mut self, x: f64, y: f64) {
+        self.cursor = Some((x, y));
+    }
+
+    fn get_prop_str(&self) -> String {
+        let mut string = String::new();
+        string += &format!("Type: {:?}\n", self.get_type());
+        string += &format!("Degree: {}\n", self.control_points.len().saturating_sub(1));
+        for (i, point) in self.control_points.iter().enumerate() {
+            string += &format!("Control point {}: {:.0?}\n", i + 1, point);
+        }
+
+        return string;
+    }
+
+    fn get_state(&self) -> ShapeState {
+        self.state
+    }
+
+    fn is_drawable(&self) -> bool {
+        match self.state {
+            ShapeState::Drawing => self.drawn_control_points().len() >= 2,
+            ShapeState::Complete => self.control_points.len() >= 2,
+            ShapeState::New => false,
+        }
+    }
+
+    fn contains(&self, x: f64, y: f64, tolerance: f64) -> bool {
+        if !self.is_drawable() {
+            return false;
+        }
+
+        // a filled curve is closed by the chord between its end points
+        let curve = flatten_bezier(&self.control_points, SEGMENTS);
+        if self.style.is_filled() && point_in_polygon(self.to_local(x, y), &curve) {
+            return true;
+        }
+
+        // measured on the canvas, so the tolerance doesn't scale with the shape
+        distance_to_polyline((x, y), &self.world_curve(), false)
+            <= self.style.hit_tolerance(tolerance)
+    }
+
+    fn get_origin(&self) -> Option<(f64, f64)> {
+        self.control_points.first().copied()
+    }
+
+    fn get_end(&self) -> Option<(f64, f64)> {
+        if self.control_points.len() < MIN_CONTROL_POINTS {
+            return None;
+        }
+
+        self.control_points.last().copied()
+    }
+
+    fn get_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        bounds_of(&self.world_curve())
+    }
+
+    fn get_outline(&self) -> Vec<(f64, f64)> {
+        self.world_curve()
+    }
+
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    fn get_props(&self) -> Vec<(String, String)> {
+        let mut map = Vec::new();
+        map.push((
+            CONTROL_POINT_COUNT_KEY.to_string(),
+            self.control_points.len().to_string(),
+        ));
+        for (i, (x, y)) in self.control_points.iter().enumerate() {
+            map.push((point_key(CONTROL_POINT_KEY_PREFIX, i, 'x'), x.to_string()));
+            map.push((point_key(CONTROL_POINT_KEY_PREFIX, i, 'y'), y.to_string()));
+        }
+        map.extend(self.style.get_props());
+
+        return map;
+    }
+
+    fn set_prop(&mut self, key: &str, value: &str) {
+        if self.style.set_prop(key, value) {
+            return;
+        }
+
+        if key == CONTROL_POINT_COUNT_KEY {
+            if let Ok(count) = value.parse::<usize>() {
+                // a straight line at the least
+                let count = count.clamp(2, MAX_VERTICES);
+                let last = self.control_points.last().copied().unwrap_or((0.0, 0.0));
+                self.control_points.resize(count, last);
+            }
+            return;
+        }
+
+        if let Some((idx, axis)) = parse_point_key(CONTROL_POINT_KEY_PREFIX, key) {
+            if let (Some(point), Ok(value)) = (self.control_points.get_mut(idx), value.parse()) {
+                match axis {
+                    'x' => point.0 = value,
+                    _ => point.1 = value,
+                }
+            }
+        }
+    }
+
+    fn resize(&mut self, change: (f64, f64), origin: (f64, f64), tolerance: f64) {
+        let transform = self.transform;
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        // pick the control point closest to the cursor on the canvas, move it in local coordinates
+        let closest = self
+            .control_points
+            .iter_mut()
+            .map(|point| (distance(transform.apply(*point), origin), point))
+            .filter(|(dist, _)| *dist < tolerance)
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((_, point)) = closest {
+            let change = inverse.apply_vector(change);
+            point.0 += change.0;
+            point.1 += change.1;
+        }
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn get_style(&self) -> Style {
+        self.style.clone()
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
+    fn set_state(&mut self, state: ShapeState) {
+        self.state = state;
+    }
+
+    fn to_data(&self) -> ShapeData {
+        ShapeData::Bezier(self.clone())
+    }
+
+    fn get_svg(&self) -> String {
+        // SVG paths stop at cubic curves, higher degrees are written flattened
+        let path = match self.control_points.as_slice() {
+            [start, control, end] => format!(
+                "M {} Q {}",
+                svg::points(&[*start]),
+                svg::points(&[*control, *end])
+            ),
+            [start, first, second, end] => format!(
+                "M {} C {}",
+                svg::points(&[*start]),
+                svg::points(&[*first, *second, *end])
+            ),
+            points => {
+                let curve = flatten_bezier(points, SEGMENTS);
+                format!(
+                    "M {} L {}",
+                    svg::points(&curve[..1]),
+                    svg::points(&curve[1..])
+                )
+            }
+        };
+        svg::element("path", &[("d", path)], &self.style, &self.transform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::recording::{DrawCall, RecordingRenderer};
+
+    #[test]
+    fn keeps_typed_control_point_counts_in_range() {
+        let mut bezier = Bezier::new();
+        for (x, y) in [(0.0, 0.0), (10.0, 20.0), (30.0, 0.0)] {
+            bezier.add_point(x, y);
+        }
+        bezier.finish();
+
+        bezier.set_prop(CONTROL_POINT_COUNT_KEY, "5");
+        assert_eq!(bezier.control_points.len(), 5);
+        assert_eq!(bezier.control_points[4], (30.0, 0.0));
+        bezier.set_prop(CONTROL_POINT_COUNT_KEY, "0");
+        assert_eq!(bezier.control_points.len(), 2);
+        assert!(bezier.is_drawable());
+        bezier.set_prop(CONTROL_POINT_COUNT_KEY, "18446744073709551615");
+        assert_eq!(bezier.control_points.len(), MAX_VERTICES);
+        bezier.set_prop(CONTROL_POINT_COUNT_KEY, "");
+        assert_eq!(bezier.control_points.len(), MAX_VERTICES);
+    }
+
+    #[test]
+    fn keeps_handles_the_same_size_on_screen() {
+        let mut bezier = Bezier::new();
+        for (x, y) in [(0.0, 0.0), (10.0, 20.0), (30.0, 0.0)] {
+            bezier.add_point(x, y);
+        }
+        bezier.finish();
+
+        // zoomed in 4 times
+        let mut renderer = RecordingRenderer::new();
+        bezier.draw_handles(&mut renderer, 0.25);
+        assert!(renderer.calls.contains(&DrawCall::SetLineWidth(0.25)));
+        assert!(renderer
+            .calls
+            .contains(&DrawCall::StrokeRect(9.25, 19.25, 1.5, 1.5)));
+    }
+}
@@ -0,0 +1,586 @@
+//! Boolean operations on regions bounded by closed contours. Every edge is split where it meets
+//! the edges of the other region, kept or dropped depending on which side of the other region
+//! it lies, and the kept edges are chained back into contours. Holes come out running the other
+//! way round than their outline, so results fill the same by either fill rule.
+
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    clip::Segment,
+    geometry::{contours_contain, cross, signed_area, FillRule},
+    path::Path,
+    polygon::Polygon,
+    shape::Shape,
+};
+
+// parameters this close to an end of an edge snap to it
+const EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BooleanOp {
+    Union,
+    Intersection,
+    /// The first region minus the second.
+    Difference,
+    /// Parts covered by exactly one of the regions.
+    Xor,
+}
+
+/// Area enclosed by `contours` under `fill_rule`.
+#[derive(Debug, Clone, Copy)]
+pub struct Region<'a> {
+    pub contours: &'a [Vec<(f64, f64)>],
+    pub fill_rule: FillRule,
+}
+
+/// Contours bounding the result of `op` on the two regions.
+pub fn combine(op: BooleanOp, a: Region, b: Region) -> Vec<Vec<(f64, f64)>> {
+    let a_contours = oriented(a);
+    let b_contours = oriented(b);
+    let (a_edges, b_edges) = split_edges(&edges_of(&a_contours), &edges_of(&b_contours));
+    let a = Region {
+        contours: &a_contours,
+        fill_rule: a.fill_rule,
+    };
+    let b = Region {
+        contours: &b_contours,
+        fill_rule: b.fill_rule,
+    };
+
+    let mut kept = Vec::new();
+    for (edges, other, other_edges, from_a) in [
+        (&a_edges, b, &b_edges, true),
+        (&b_edges, a, &a_edges, false),
+    ] {
+        let other_edges: HashSet<_> = other_edges
+            .iter()
+            .map(|(s, e)| (key(*s), key(*e)))
+            .collect();
+        for (start, end) in edges.iter() {
+            let side = if other_edges.contains(&(key(*start), key(*end))) {
+                Side::SharedSame
+            } else if other_edges.contains(&(key(*end), key(*start))) {
+                Side::SharedOpposite
+            } else if contours_contain(midpoint(*start, *end), other.contours, other.fill_rule) {
+                Side::Inside
+            } else {
+                Side::Outside
+            };
+
+            match keeps(op, side, from_a) {
+                Some(false) => kept.push((*start, *end)),
+                Some(true) => kept.push((*end, *start)),
+                None => {}
+            }
+        }
+    }
+
+    chain(&kept)
+}
+
+/// Combines the closed shapes in order, the first minus all others for a difference, with
+/// curves flattened to within `tolerance` pixels. The result takes the style of the first shape
+/// and is a polygon if it's a single contour, a path otherwise. `None` if nothing is left.
+pub fn combine_shapes(
+    op: BooleanOp,
+    shapes: &[&dyn Shape],
+    tolerance: f64,
+) -> Option<Box<dyn Shape>> {
+    let mut operands = shapes
+        .iter()
+        .map(|shape| (shape.get_contours(tolerance), shape.get_fill_rule()));
+    let (mut contours, mut fill_rule) = operands.next()?;
+    for (other, other_rule) in operands {
+        contours = combine(
+            op,
+            Region {
+                contours: &contours,
+                fill_rule,
+            },
+            Region {
+                contours: &other,
+                fill_rule: other_rule,
+            },
+        );
+        // the orientation of the result holds the holes
+        fill_rule = FillRule::NonZero;
+    }
+
+    let style = shapes.first()?.get_style();
+    match contours.len() {
+        0 => None,
+        1 => Some(Box::new(Polygon::from_vertices(
+            contours.pop()?,
+            true,
+            style,
+        ))),
+        _ => Some(Box::new(Path::from_contours(contours, fill_rule, style))),
+    }
+}
+
+/// Whether the shape bounds an area the operations can work with.
+pub fn is_combinable(shape: &dyn Shape) -> bool {
+    !shape.get_contours(1.0).is_empty()
+}
+
+// where an edge lies relative to the other region
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Inside,
+    Outside,
+    // the other region has the same edge running the same way
+    SharedSame,
+    SharedOpposite,
+}
+
+// whether an edge bounds the result, and if it has to be reversed to keep the inside on its
+// right; of shared edges only the copy of the first region counts
+fn keeps(op: BooleanOp, side: Side, from_a: bool) -> Option<bool> {
+    match (op, side, from_a) {
+        (_, Side::SharedSame | Side::SharedOpposite, false) => None,
+        (BooleanOp::Union, Side::Outside | Side::SharedSame, _) => Some(false),
+        (BooleanOp::Intersection, Side::Inside | Side::SharedSame, _) => Some(false),
+        (BooleanOp::Difference, Side::Outside | Side::SharedOpposite, true) => Some(false),
+        (BooleanOp::Difference, Side::Inside, false) => Some(true),
+        (BooleanOp::Xor, Side::Outside, _) => Some(false),
+        (BooleanOp::Xor, Side::Inside, _) => Some(true),
+        _ => None,
+    }
+}
+
+// the contours turned so the inside of the region lies to the right of every edge on the
+// canvas, contours that don't separate inside from outside under the fill rule are dropped
+fn oriented(region: Region) -> Vec<Vec<(f64, f64)>> {
+    region
+        .contours
+        .iter()
+        .filter(|contour| contour.len() >= 3 && signed_area(contour) != 0.0)
+        .filter_map(|contour| {
+            let (start, end) = edges_of(std::slice::from_ref(contour))
+                .into_iter()
+                .max_by(|a, b| length(*a).total_cmp(&length(*b)))?;
+            let (dx, dy) = (
+                (end.0 - start.0) / length((start, end)),
+                (end.1 - start.1) / length((start, end)),
+            );
+            let (mx, my) = midpoint(start, end);
+            let offset = (length((start, end)) * 1e-3).min(1e-3);
+            let right = (mx - dy * offset, my + dx * offset);
+            let left = (mx + dy * offset, my - dx * offset);
+
+            let mut contour = contour.clone();
+            match (
+                contours_contain(right, region.contours, region.fill_rule),
+                contours_contain(left, region.contours, region.fill_rule),
+            ) {
+                (true, false) => Some(contour),
+                (false, true) => {
+                    contour.reverse();
+                    Some(contour)
+                }
+                // the same on both sides, e.g. nested in another contour running the same way
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn edges_of(contours: &[Vec<(f64, f64)>]) -> Vec<Segment> {
+    contours
+        .iter()
+        .flat_map(|contour| {
+            (0..contour.len()).map(move |i| (contour[i], contour[(i + 1) % contour.len()]))
+        })
+        .filter(|(start, end)| start != end)
+        .collect()
+}
+
+// splits the edges of both sets where they cross or touch the other set; both sides of a split
+// get the very same point, so the pieces join up exactly
+fn split_edges(a: &[Segment], b: &[Segment]) -> (Vec<Segment>, Vec<Segment>) {
+    let mut a_splits: Vec<Vec<(f64, (f64, f64))>> = vec![Vec::new(); a.len()];
+    let mut b_splits: Vec<Vec<(f64, (f64, f64))>> = vec![Vec::new(); b.len()];
+    for (i, (p, p2)) in a.iter().enumerate() {
+        for (j, (q, q2)) in b.iter().enumerate() {
+            let r = (p2.0 - p.0, p2.1 - p.1);
+            let s = (q2.0 - q.0, q2.1 - q.1);
+            let denominator = r.0 * s.1 - r.1 * s.0;
+            if denominator == 0.0 {
+                // overlapping collinear edges split at each other's ends
+                if cross(*p, *p2, *q) != 0.0 {
+                    continue;
+                }
+                for point in [*q, *q2] {
+                    if let Some(t) = interior_parameter(*p, *p2, point) {
+                        a_splits[i].push((t, point));
+                    }
+                }
+                for point in [*p, *p2] {
+                    if let Some(u) = interior_parameter(*q, *q2, point) {
+                        b_splits[j].push((u, point));
+                    }
+                }
+                continue;
+            }
+
+            let d = (q.0 - p.0, q.1 - p.1);
+            let t = (d.0 * s.1 - d.1 * s.0) / denominator;
+            let u = (d.0 * r.1 - d.1 * r.0) / denominator;
+            let within = |value: f64| (-EPSILON..=1.0 + EPSILON).contains(&value);
+            if !within(t) || !within(u) {
+                continue;
+            }
+
+            // an end touching the other edge is taken as it is
+            let point = if u.abs() <= EPSILON {
+                *q
+            } else if (u - 1.0).abs() <= EPSILON {
+                *q2
+            } else if t.abs() <= EPSILON {
+                *p
+            } else if (t - 1.0).abs() <= EPSILON {
+                *p2
+            } else {
+                (p.0 + r.0 * t, p.1 + r.1 * t)
+            };
+            if t > EPSILON && t < 1.0 - EPSILON {
+                a_splits[i].push((t, point));
+            }
+            if u > EPSILON && u < 1.0 - EPSILON {
+                b_splits[j].push((u, point));
+            }
+        }
+    }
+
+    (split_at(a, a_splits), split_at(b, b_splits))
+}
+
+fn split_at(edges: &[Segment], splits: Vec<Vec<(f64, (f64, f64))>>) -> Vec<Segment> {
+    let mut pieces = Vec::new();
+    for ((start, end), mut splits) in edges.iter().zip(splits) {
+        splits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut points = vec![*start];
+        points.extend(splits.into_iter().map(|(_, point)| point));
+        points.push(*end);
+        points.dedup();
+        pieces.extend(points.windows(2).map(|pair| (pair[0], pair[1])));
+    }
+
+    pieces
+}
+
+// where `point` lies along a-b if it's on the segment, strictly between its ends
+fn interior_parameter(a: (f64, f64), b: (f64, f64), point: (f64, f64)) -> Option<f64> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let t = ((point.0 - a.0) * dx + (point.1 - a.1) * dy) / (dx * dx + dy * dy);
+    if t > EPSILON && t < 1.0 - EPSILON {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+// follows the kept edges end to start into closed contours
+fn chain(edges: &[Segment]) -> Vec<Vec<(f64, f64)>> {
+    let mut outgoing: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+    for (i, (start, _)) in edges.iter().enumerate() {
+        outgoing.entry(key(*start)).or_default().push(i);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut contours = Vec::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+
+        used[first] = true;
+        let origin = key(edges[first].0);
+        let mut contour = vec![edges[first].0];
+        let mut current = edges[first].1;
+        let mut closed = true;
+        while key(current) != origin {
+            contour.push(current);
+            let next = outgoing
+                .get(&key(current))
+                .and_then(|candidates| candidates.iter().copied().find(|i| !used[*i]));
+            match next {
+                Some(i) => {
+                    used[i] = true;
+                    current = edges[i].1;
+                }
+                None => {
+                    closed = false;
+                    break;
+                }
+            }
+        }
+
+        let contour = without_collinear(contour);
+        if closed && contour.len() >= 3 && signed_area(&contour) != 0.0 {
+            contours.push(contour);
+        }
+    }
+
+    contours
+}
+
+// drops vertices in the middle of straight runs, left over from splitting
+fn without_collinear(contour: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    let count = contour.len();
+    (0..count)
+        .filter(|i| {
+            let previous = contour[(i + count - 1) % count];
+            let next = contour[(i + 1) % count];
+            let scale = length((previous, contour[*i])) * length((contour[*i], next));
+            cross(previous, contour[*i], next).abs() > EPSILON * scale
+        })
+        .map(|i| contour[i])
+        .collect()
+}
+
+fn key(point: (f64, f64)) -> (u64, u64) {
+    // -0.0 and 0.0 are the same point
+    ((point.0 + 0.0).to_bits(), (point.1 + 0.0).to_bits())
+}
+
+fn midpoint(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)
+}
+
+fn length((a, b): Segment) -> f64 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        circle::Circle,
+        document::ShapeData,
+        geometry::{bounds_of, segments_for_tolerance},
+        rectangle::Rectangle,
+        shape::{ShapeType, HIT_TOLERANCE},
+        style::{Style, FILLED_KEY},
+    };
+
+    fn square(x1: f64, y1: f64, x2: f64, y2: f64) -> Vec<Vec<(f64, f64)>> {
+        vec![vec![(x1, y1), (x2, y1), (x2, y2), (x1, y2)]]
+    }
+
+    fn region(contours: &[Vec<(f64, f64)>]) -> Region<'_> {
+        Region {
+            contours,
+            fill_rule: FillRule::NonZero,
+        }
+    }
+
+    // holes run the other way round, so they count negative
+    fn area(contours: &[Vec<(f64, f64)>]) -> f64 {
+        contours
+            .iter()
+            .map(|contour| signed_area(contour))
+            .sum::<f64>()
+            .abs()
+    }
+
+    fn filled(mut shape: Box<dyn Shape>, points: &[(f64, f64)]) -> Box<dyn Shape> {
+        for (x, y) in points {
+            shape.add_point(*x, *y);
+        }
+        shape.set_prop(FILLED_KEY, "true");
+        shape
+    }
+
+    #[test]
+    fn combines_overlapping_squares() {
+        let a = square(0.0, 0.0, 10.0, 10.0);
+        let b = square(5.0, 5.0, 15.0, 15.0);
+        let cases = [
+            (BooleanOp::Union, 175.0, 1),
+            (BooleanOp::Intersection, 25.0, 1),
+            (BooleanOp::Difference, 75.0, 1),
+        ];
+
+        for (op, expected_area, count) in cases {
+            let result = combine(op, region(&a), region(&b));
+            assert_eq!(result.len(), count, "{:?}", op);
+            assert_eq!(area(&result), expected_area, "{:?}", op);
+        }
+
+        let xor = combine(BooleanOp::Xor, region(&a), region(&b));
+        assert_eq!(area(&xor), 150.0);
+        for (point, inside) in [
+            ((2.0, 2.0), true),
+            ((7.0, 7.0), false),
+            ((12.0, 12.0), true),
+        ] {
+            assert_eq!(contours_contain(point, &xor, FillRule::NonZero), inside);
+            assert_eq!(contours_contain(point, &xor, FillRule::EvenOdd), inside);
+        }
+    }
+
+    #[test]
+    fn leaves_holes_running_the_other_way() {
+        let outer = square(0.0, 0.0, 10.0, 10.0);
+        let inner = square(3.0, 3.0, 7.0, 7.0);
+
+        let ring = combine(BooleanOp::Difference, region(&outer), region(&inner));
+        assert_eq!(ring.len(), 2);
+        assert!(signed_area(&ring[0]) * signed_area(&ring[1]) < 0.0);
+        assert_eq!(area(&ring), 84.0);
+        for rule in [FillRule::NonZero, FillRule::EvenOdd] {
+            assert!(contours_contain((1.0, 5.0), &ring, rule));
+            assert!(!contours_contain((5.0, 5.0), &ring, rule));
+        }
+
+        // filling the hole again, read by either rule
+        for fill_rule in [FillRule::NonZero, FillRule::EvenOdd] {
+            let ring = Region {
+                contours: &ring,
+                fill_rule,
+            };
+            let patch = square(2.0, 2.0, 8.0, 8.0);
+            let union = combine(BooleanOp::Union, ring, region(&patch));
+            assert_eq!(union.len(), 1);
+            assert_eq!(area(&union), 100.0);
+        }
+    }
+
+    #[test]
+    fn even_odd_regions_keep_nested_contours_as_holes() {
+        // the same direction for both contours, only the even-odd rule makes the inner a hole
+        let mut contours = square(0.0, 0.0, 10.0, 10.0);
+        contours.extend(square(3.0, 3.0, 7.0, 7.0));
+        let even_odd = Region {
+            contours: &contours,
+            fill_rule: FillRule::EvenOdd,
+        };
+        let everything = square(-1.0, -1.0, 11.0, 11.0);
+
+        assert_eq!(
+            area(&combine(
+                BooleanOp::Intersection,
+                even_odd,
+                region(&everything)
+            )),
+            84.0
+        );
+        assert_eq!(
+            area(&combine(
+                BooleanOp::Intersection,
+                region(&contours),
+                region(&everything)
+            )),
+            100.0
+        );
+    }
+
+    #[test]
+    fn merges_shared_edges() {
+        let left = square(0.0, 0.0, 10.0, 10.0);
+        let right = square(10.0, 0.0, 20.0, 10.0);
+        let shifted = square(10.0, 5.0, 20.0, 15.0);
+
+        let union = combine(BooleanOp::Union, region(&left), region(&right));
+        assert_eq!(union.len(), 1);
+        assert_eq!(union[0].len(), 4);
+        assert_eq!(bounds_of(&union[0]), Some((0.0, 0.0, 20.0, 10.0)));
+        assert_eq!(
+            area(&combine(BooleanOp::Union, region(&left), region(&shifted))),
+            200.0
+        );
+
+        assert!(combine(BooleanOp::Intersection, region(&left), region(&right)).is_empty());
+        assert_eq!(
+            area(&combine(
+                BooleanOp::Difference,
+                region(&left),
+                region(&right)
+            )),
+            100.0
+        );
+        assert_eq!(
+            area(&combine(BooleanOp::Xor, region(&left), region(&left))),
+            0.0
+        );
+        assert_eq!(
+            area(&combine(BooleanOp::Union, region(&left), region(&left))),
+            100.0
+        );
+    }
+
+    #[test]
+    fn flattens_circles_within_the_tolerance() {
+        for (radius, tolerance) in [(100.0, 0.5), (100.0, 0.1), (10.0, 0.25), (1000.0, 1.0)] {
+            let segments = segments_for_tolerance(radius, tolerance);
+            let sagitta = radius * (1.0 - (std::f64::consts::PI / segments as f64).cos());
+            assert!(
+                sagitta <= tolerance,
+                "{} segments for r = {}",
+                segments,
+                radius
+            );
+        }
+        assert_eq!(segments_for_tolerance(1.0, 5.0), 8);
+
+        let mut circle = Circle::new();
+        circle.add_point(0.0, 0.0);
+        circle.add_point(50.0, 0.0);
+        let coarse = circle.get_contours(5.0)[0].len();
+        let fine = circle.get_contours(0.1)[0].len();
+        assert!(coarse < fine);
+        assert_eq!(fine, segments_for_tolerance(50.0, 0.1));
+    }
+
+    #[test]
+    fn combines_shapes_into_polygons_and_paths() {
+        let rectangle = filled(Box::new(Rectangle::new()), &[(0.0, 0.0), (100.0, 100.0)]);
+        let circle = filled(Box::new(Circle::new()), &[(50.0, 50.0), (80.0, 50.0)]);
+        let corner = filled(Box::new(Rectangle::new()), &[(90.0, 90.0), (120.0, 120.0)]);
+
+        let union = combine_shapes(
+            BooleanOp::Union,
+            &[rectangle.as_ref(), corner.as_ref()],
+            0.5,
+        )
+        .unwrap();
+        assert_eq!(union.get_type(), ShapeType::Polygon);
+        assert!(union.contains(110.0, 110.0, HIT_TOLERANCE));
+
+        let shapes = [rectangle.as_ref(), circle.as_ref(), corner.as_ref()];
+        let punched = combine_shapes(BooleanOp::Difference, &shapes, 0.5).unwrap();
+        assert_eq!(punched.get_type(), ShapeType::Path);
+        assert!(punched.contains(10.0, 10.0, HIT_TOLERANCE));
+        assert!(!punched.contains(50.0, 50.0, HIT_TOLERANCE));
+        assert!(!punched.contains(95.0, 95.0, HIT_TOLERANCE));
+        assert_eq!(
+            punched.get_style().fill_color,
+            rectangle.get_style().fill_color
+        );
+
+        // the path survives saving with its holes
+        let loaded = ShapeData::from_json(&punched.get_json())
+            .unwrap()
+            .into_shape();
+        assert_eq!(loaded.get_type(), ShapeType::Path);
+        assert!(!loaded.contains(50.0, 50.0, HIT_TOLERANCE));
+        assert!(loaded.contains(10.0, 10.0, HIT_TOLERANCE));
+
+        let apart = filled(
+            Box::new(Rectangle::new()),
+            &[(200.0, 200.0), (210.0, 210.0)],
+        );
+        assert!(combine_shapes(
+            BooleanOp::Intersection,
+            &[rectangle.as_ref(), apart.as_ref()],
+            0.5
+        )
+        .is_none());
+        assert!(!is_combinable(&Polygon::from_vertices(
+            vec![(0.0, 0.0), (1.0, 1.0)],
+            false,
+            Style::new()
+        )));
+    }
+}
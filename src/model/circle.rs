@@ -1,12 +1,30 @@
-use std::{f64::consts, str::FromStr};
+use std::f64::consts;
 
-use super::shape::{Shape, ShapeState, ShapeType, ORIGIN_X_KEY, ORIGIN_Y_KEY};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::ShapeData,
+    geometry::{distance, distance_to_polyline, flatten_ellipse, segments_for_tolerance},
+    raster::{to_pixel, Framebuffer, Rgba},
+    renderer::Renderer,
+    shape::{Shape, ShapeState, ShapeType, ORIGIN_X_KEY, ORIGIN_Y_KEY},
+    style::Style,
+    svg,
+    transform::Transform,
+};
 
 pub const RADIUS_KEY: &str = "Radius";
 
+const SEGMENTS: usize = 64;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Circle {
     origin: Option<(f64, f64)>,
     radius: f64,
+    #[serde(default)]
+    transform: Transform,
+    #[serde(default)]
+    style: Style,
     state: ShapeState,
 }
 
@@ -15,22 +33,40 @@ impl Circle {
         Self {
             origin: None,
             radius: 0.0,
+            transform: Transform::identity(),
+            style: Style::new(),
             state: ShapeState::New,
         }
     }
 }
 
 impl Shape for Circle {
-    fn draw(&self, ctx: &web_sys::CanvasRenderingContext2d) {
+    fn build_path(&self, renderer: &mut dyn Renderer) {
+        let (ox, oy) = self.origin.unwrap();
+        renderer.arc(ox, oy, self.radius, 0.0, 2.0 * consts::PI);
+    }
+
+    fn rasterize(&self, framebuffer: &mut Framebuffer, color: Rgba, anti_aliased: bool) {
         if !self.is_drawable() {
             return;
         }
 
-        let (ox, oy) = self.origin.unwrap();
-        ctx.begin_path();
-        ctx.arc(ox, oy, self.radius, 0.0, 2.0 * consts::PI)
-            .expect("Couldn't arc!");
-        ctx.stroke();
+        if let Some(fill) = self.style.fill_rgba() {
+            framebuffer.fill_polygon(&self.get_outline(), fill);
+        }
+        match (self.origin, self.transform.uniform_scale()) {
+            (Some(origin), Some(scale)) if anti_aliased => {
+                framebuffer.draw_circle_wu(self.transform.apply(origin), self.radius * scale, color)
+            }
+            (Some(origin), Some(scale)) => framebuffer.draw_circle(
+                to_pixel(self.transform.apply(origin)),
+                (self.radius * scale).round() as i64,
+                color,
+            ),
+            // a skewed or stretched circle is an ellipse, traced along its outline
+            _ if anti_aliased => framebuffer.draw_polyline_wu(&self.get_outline(), true, color),
+            _ => framebuffer.draw_polyline(&self.get_outline(), true, color),
+        }
     }
 
     fn add_point(&mut self, x: f64, y: f64) {
@@ -56,15 +92,14 @@ impl Shape for Circle {
     }
 
     fn set_end(&mut self, x: f64, y: f64) {
-        let (ox, oy) = self.origin.unwrap();
-        self.radius = ((ox - x).powf(2.0) + (oy - y).powf(2.0)).sqrt();
+        self.radius = distance(self.origin.unwrap(), self.to_local(x, y));
     }
 
     fn get_prop_str(&self) -> String {
         let mut string = String::new();
         string += &format!("Type: {:?}\n", self.get_type());
-        if self.origin.is_some() {
-            string += &format!("Origin: {:.0?}\n", self.origin.unwrap());
+        if let Some(origin) = self.origin {
+            string += &format!("Origin: {:.0?}\n", origin);
         }
         string += &format!("Radius: {:.2}\n", self.radius);
 
@@ -77,14 +112,23 @@ impl Shape for Circle {
 
     fn is_drawable(&self) -> bool {
         (self.state == ShapeState::Complete || self.state == ShapeState::Drawing)
+            && self.origin.is_some()
             && self.radius != 0.0
     }
 
-    fn contains(&self, x: f64, y: f64) -> bool {
+    fn contains(&self, x: f64, y: f64, tolerance: f64) -> bool {
         if self.origin.is_none() {
             return false;
         }
 
+        if !self.style.is_filled() {
+            // only the outline counts, measured on the canvas
+            let outline = self.get_outline();
+            return distance_to_polyline((x, y), &outline, true)
+                <= self.style.hit_tolerance(tolerance);
+        }
+
+        let (x, y) = self.to_local(x, y);
         let (ox, oy) = self.origin.unwrap();
         let distance = ((ox - x).powf(2.0) + (oy - y).powf(2.0)).sqrt();
         distance <= self.radius
@@ -95,12 +139,52 @@ impl Shape for Circle {
     }
 
     fn get_end(&self) -> Option<(f64, f64)> {
-        if self.origin.is_none() {
-            return None;
+        let (ox, oy) = self.origin?;
+        Some((ox + self.radius, oy))
+    }
+
+    fn get_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        let (ox, oy) = self.transform.apply(self.origin?);
+        // half extents of the circle mapped through the linear part of the transform
+        let (m00, m01) = (self.transform.get(0, 0), self.transform.get(0, 1));
+        let (m10, m11) = (self.transform.get(1, 0), self.transform.get(1, 1));
+        let half_width = self.radius * (m00 * m00 + m01 * m01).sqrt();
+        let half_height = self.radius * (m10 * m10 + m11 * m11).sqrt();
+        Some((
+            ox - half_width,
+            oy - half_height,
+            ox + half_width,
+            oy + half_height,
+        ))
+    }
+
+    fn get_outline(&self) -> Vec<(f64, f64)> {
+        match self.origin {
+            Some(origin) => flatten_ellipse(origin, self.radius, self.radius, SEGMENTS)
+                .iter()
+                .map(|point| self.transform.apply(*point))
+                .collect(),
+            None => Vec::new(),
         }
+    }
 
-        let (ox, oy) = self.origin.unwrap();
-        Some((ox + self.radius, oy))
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn get_contours(&self, tolerance: f64) -> Vec<Vec<(f64, f64)>> {
+        let origin = match self.origin {
+            Some(origin) if self.is_drawable() => origin,
+            _ => return Vec::new(),
+        };
+
+        // flattened by the largest radius the transform stretches it to
+        let radius = self.radius.abs() * self.transform.max_scale();
+        let segments = segments_for_tolerance(radius, tolerance);
+        vec![flatten_ellipse(origin, self.radius, self.radius, segments)
+            .iter()
+            .map(|point| self.transform.apply(*point))
+            .collect()]
     }
 
     fn get_props(&self) -> Vec<(String, String)> {
@@ -109,92 +193,84 @@ impl Shape for Circle {
         map.push((ORIGIN_X_KEY.to_string(), ox.to_string()));
         map.push((ORIGIN_Y_KEY.to_string(), oy.to_string()));
         map.push((RADIUS_KEY.to_string(), self.radius.to_string()));
+        map.extend(self.style.get_props());
 
         map
     }
 
     fn set_prop(&mut self, key: &str, value: &str) {
+        if self.style.set_prop(key, value) {
+            return;
+        }
+
+        let value: f64 = match value.parse() {
+            Ok(value) => value,
+            Err(_) => return,
+        };
         match key {
             ORIGIN_X_KEY => {
                 if let Some(origin) = self.origin {
-                    self.origin = Some((value.parse().unwrap(), origin.1));
+                    self.origin = Some((value, origin.1));
                 } else {
-                    self.origin = Some((value.parse().unwrap(), 0.0));
+                    self.origin = Some((value, 0.0));
                 }
             }
             ORIGIN_Y_KEY => {
                 if let Some(origin) = self.origin {
-                    self.origin = Some((origin.0, value.parse().unwrap()));
+                    self.origin = Some((origin.0, value));
                 } else {
-                    self.origin = Some((0.0, value.parse().unwrap()));
+                    self.origin = Some((0.0, value));
                 }
             }
             RADIUS_KEY => {
-                self.radius = value.parse().unwrap();
+                self.radius = value;
             }
             _ => {}
         }
     }
 
-    fn move_by(&mut self, x: f64, y: f64) {
-        if let Some(origin) = self.origin {
-            self.origin = Some((origin.0 + x, origin.1 + y));
+    fn resize(&mut self, change: (f64, f64), origin: (f64, f64), _tolerance: f64) {
+        if let Some(center) = self.origin {
+            let cursor = self.to_local(origin.0 + change.0, origin.1 + change.1);
+            self.radius = distance(center, cursor);
         }
     }
 
-    fn resize(&mut self, change: (f64, f64), origin: (f64, f64)) {
-        if let Some((ox, oy)) = self.origin {
-            let (cx, cy) = change;
-            let (ox, oy) = (ox - origin.0, oy - origin.1);
-            let (nx, ny) = (ox + cx, oy + cy);
-            let new_radius = ((nx.powf(2.0) + ny.powf(2.0)).sqrt()).abs();
-            self.radius = new_radius;
-        }
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn get_style(&self) -> Style {
+        self.style.clone()
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.style = style;
     }
 
     fn set_state(&mut self, state: ShapeState) {
         self.state = state;
     }
 
-    fn get_json(&self) -> String {
-        let mut map = serde_json::Map::new();
-        map.insert("type".to_string(), serde_json::Value::String("circle".to_string()));
-        map.insert("state".to_string(), serde_json::Value::String(self.state.to_string()));
-        map.insert(
-            "origin".to_string(),
-            serde_json::Value::Array(vec![
-                serde_json::Value::Number(serde_json::Number::from_f64(self.origin.unwrap().0).unwrap()),
-                serde_json::Value::Number(serde_json::Number::from_f64(self.origin.unwrap().1).unwrap()),
-            ]),
-        );
-        map.insert(
-            "radius".to_string(),
-            serde_json::Value::Number(serde_json::Number::from_f64(self.radius).unwrap()),
-        );
-
-        serde_json::to_string(&map).unwrap()
-    }
-
-    fn from_json(&mut self, json: &str) {
-        let map: serde_json::Map<String, serde_json::Value> = serde_json::from_str(json).unwrap();
-        if let Some(origin) = map.get("origin") {
-            if let Some(origin) = origin.as_array() {
-                let x = origin[0].as_f64().unwrap();
-                let y = origin[1].as_f64().unwrap();
-                self.origin = Some((x, y));
-            }
-        }
-
-        if let Some(radius) = map.get("radius") {
-            if let Some(radius) = radius.as_f64() {
-                self.radius = radius;
-            }
-        }
+    fn to_data(&self) -> ShapeData {
+        ShapeData::Circle(self.clone())
+    }
 
-        if let Some(state) = map.get("state") {
-            if let Some(state) = state.as_str() {
-                self.state = ShapeState::from_str(state).unwrap();
-            }
-        }
+    fn get_svg(&self) -> String {
+        let (ox, oy) = self.origin.unwrap_or((0.0, 0.0));
+        svg::element(
+            "circle",
+            &[
+                ("cx", svg::number(ox)),
+                ("cy", svg::number(oy)),
+                ("r", svg::number(self.radius)),
+            ],
+            &self.style,
+            &self.transform,
+        )
     }
 }
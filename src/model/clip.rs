@@ -0,0 +1,334 @@
+//! Line clipping against an axis-aligned window. Coordinates are the canvas', y grows downwards,
+//! so the top edge of the window is at `y_min`.
+
+pub const INSIDE: u8 = 0b0000;
+pub const LEFT: u8 = 0b0001;
+pub const RIGHT: u8 = 0b0010;
+pub const BOTTOM: u8 = 0b0100;
+pub const TOP: u8 = 0b1000;
+
+/// A line segment as its start and end point.
+pub type Segment = ((f64, f64), (f64, f64));
+
+/// Which algorithm clips lines to the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipAlgorithm {
+    CohenSutherland,
+    LiangBarsky,
+}
+
+/// The clipping rectangle, its edges count as inside.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipWindow {
+    pub x_min: f64,
+    pub y_min: f64,
+    pub x_max: f64,
+    pub y_max: f64,
+}
+
+impl ClipWindow {
+    /// The window spanned by two opposite corners, in any order.
+    pub fn from_corners(a: (f64, f64), b: (f64, f64)) -> Self {
+        Self {
+            x_min: a.0.min(b.0),
+            y_min: a.1.min(b.1),
+            x_max: a.0.max(b.0),
+            y_max: a.1.max(b.1),
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.x_max - self.x_min
+    }
+
+    pub fn height(&self) -> f64 {
+        self.y_max - self.y_min
+    }
+
+    /// Cohen–Sutherland region code of `p`, a combination of `LEFT`, `RIGHT`, `BOTTOM` and `TOP`.
+    pub fn outcode(&self, p: (f64, f64)) -> u8 {
+        let mut code = INSIDE;
+        if p.0 < self.x_min {
+            code |= LEFT;
+        } else if p.0 > self.x_max {
+            code |= RIGHT;
+        }
+        if p.1 < self.y_min {
+            code |= TOP;
+        } else if p.1 > self.y_max {
+            code |= BOTTOM;
+        }
+
+        code
+    }
+
+    /// The part of the segment `a`-`b` inside the window, or `None` if it's entirely outside.
+    pub fn clip(&self, algorithm: ClipAlgorithm, a: (f64, f64), b: (f64, f64)) -> Option<Segment> {
+        match algorithm {
+            ClipAlgorithm::CohenSutherland => self.cohen_sutherland(a, b),
+            ClipAlgorithm::LiangBarsky => {
+                let (t0, t1) = self.liang_barsky(a, b)?;
+                Some((lerp(a, b, t0), lerp(a, b, t1)))
+            }
+        }
+    }
+
+    /// Repeatedly moves an end point outside the window onto the edge it's beyond, until both
+    /// are inside or share an outside region.
+    pub fn cohen_sutherland(&self, mut a: (f64, f64), mut b: (f64, f64)) -> Option<Segment> {
+        let mut code_a = self.outcode(a);
+        let mut code_b = self.outcode(b);
+        loop {
+            if code_a | code_b == INSIDE {
+                return Some((a, b));
+            }
+            if code_a & code_b != INSIDE {
+                return None;
+            }
+
+            // a point outside with the other one inside or in a different region, so the
+            // segment isn't parallel to the edges it gets intersected with
+            let code = if code_a != INSIDE { code_a } else { code_b };
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let point = if code & TOP != 0 {
+                (a.0 + dx * (self.y_min - a.1) / dy, self.y_min)
+            } else if code & BOTTOM != 0 {
+                (a.0 + dx * (self.y_max - a.1) / dy, self.y_max)
+            } else if code & RIGHT != 0 {
+                (self.x_max, a.1 + dy * (self.x_max - a.0) / dx)
+            } else {
+                (self.x_min, a.1 + dy * (self.x_min - a.0) / dx)
+            };
+
+            if code == code_a {
+                a = point;
+                code_a = self.outcode(a);
+            } else {
+                b = point;
+                code_b = self.outcode(b);
+            }
+        }
+    }
+
+    /// Parameters (t0, t1) of the visible part of `a + t * (b - a)`, `0 <= t0 <= t1 <= 1`.
+    /// Each edge narrows the range from the side the segment enters or leaves it through.
+    pub fn liang_barsky(&self, a: (f64, f64), b: (f64, f64)) -> Option<(f64, f64)> {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let edges = [
+            (-dx, a.0 - self.x_min),
+            (dx, self.x_max - a.0),
+            (-dy, a.1 - self.y_min),
+            (dy, self.y_max - a.1),
+        ];
+
+        let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+        for (p, q) in edges {
+            if p == 0.0 {
+                // parallel to the edge, either entirely inside or outside of it
+                if q < 0.0 {
+                    return None;
+                }
+                continue;
+            }
+
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+            if t0 > t1 {
+                return None;
+            }
+        }
+
+        Some((t0, t1))
+    }
+}
+
+/// Outcode as its four bits, top, bottom, right and left.
+pub fn outcode_bits(code: u8) -> String {
+    format!("{:04b}", code)
+}
+
+fn lerp(a: (f64, f64), b: (f64, f64), t: f64) -> (f64, f64) {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [ClipAlgorithm; 2] =
+        [ClipAlgorithm::CohenSutherland, ClipAlgorithm::LiangBarsky];
+
+    fn window() -> ClipWindow {
+        ClipWindow::from_corners((40.0, 30.0), (10.0, 10.0))
+    }
+
+    fn assert_clips(a: (f64, f64), b: (f64, f64), expected: Option<Segment>) {
+        for algorithm in ALGORITHMS {
+            let clipped = window().clip(algorithm, a, b);
+            match (clipped, expected) {
+                (Some((start, end)), Some((expected_start, expected_end))) => {
+                    for (point, expected) in [(start, expected_start), (end, expected_end)] {
+                        assert!(
+                            (point.0 - expected.0).abs() < 1e-9
+                                && (point.1 - expected.1).abs() < 1e-9,
+                            "{:?} clipped {:?}-{:?} to {:?}-{:?}",
+                            algorithm,
+                            a,
+                            b,
+                            start,
+                            end
+                        );
+                    }
+                }
+                (clipped, expected) => assert_eq!(
+                    clipped.is_some(),
+                    expected.is_some(),
+                    "{:?} clipped {:?}-{:?} to {:?}",
+                    algorithm,
+                    a,
+                    b,
+                    clipped
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn normalizes_window_corners() {
+        let window = window();
+
+        assert_eq!(
+            (window.x_min, window.y_min, window.x_max, window.y_max),
+            (10.0, 10.0, 40.0, 30.0)
+        );
+        assert_eq!((window.width(), window.height()), (30.0, 20.0));
+    }
+
+    #[test]
+    fn computes_outcodes_of_all_nine_regions() {
+        let window = window();
+        let cases = [
+            ((0.0, 0.0), TOP | LEFT),
+            ((20.0, 0.0), TOP),
+            ((50.0, 0.0), TOP | RIGHT),
+            ((0.0, 20.0), LEFT),
+            ((20.0, 20.0), INSIDE),
+            ((50.0, 20.0), RIGHT),
+            ((0.0, 40.0), BOTTOM | LEFT),
+            ((20.0, 40.0), BOTTOM),
+            ((50.0, 40.0), BOTTOM | RIGHT),
+            // the edges are inside
+            ((10.0, 10.0), INSIDE),
+            ((40.0, 30.0), INSIDE),
+        ];
+
+        for (point, code) in cases {
+            assert_eq!(window.outcode(point), code, "{:?}", point);
+        }
+        assert_eq!(outcode_bits(TOP | LEFT), "1001");
+        assert_eq!(outcode_bits(BOTTOM), "0100");
+    }
+
+    #[test]
+    fn keeps_lines_inside_the_window() {
+        assert_clips(
+            (15.0, 15.0),
+            (35.0, 25.0),
+            Some(((15.0, 15.0), (35.0, 25.0))),
+        );
+        // lying on the edges
+        assert_clips(
+            (10.0, 10.0),
+            (40.0, 10.0),
+            Some(((10.0, 10.0), (40.0, 10.0))),
+        );
+        assert_clips(
+            (40.0, 10.0),
+            (40.0, 30.0),
+            Some(((40.0, 10.0), (40.0, 30.0))),
+        );
+    }
+
+    #[test]
+    fn rejects_lines_fully_outside() {
+        // beside one edge
+        assert_clips((0.0, 0.0), (50.0, 5.0), None);
+        assert_clips((0.0, 35.0), (50.0, 40.0), None);
+        assert_clips((0.0, 0.0), (5.0, 40.0), None);
+        assert_clips((45.0, 0.0), (50.0, 40.0), None);
+        // across two outside regions, past a corner
+        assert_clips((0.0, 15.0), (15.0, 0.0), None);
+        assert_clips((35.0, 40.0), (50.0, 25.0), None);
+    }
+
+    #[test]
+    fn clips_horizontal_and_vertical_lines() {
+        assert_clips(
+            (0.0, 20.0),
+            (50.0, 20.0),
+            Some(((10.0, 20.0), (40.0, 20.0))),
+        );
+        assert_clips(
+            (50.0, 20.0),
+            (20.0, 20.0),
+            Some(((40.0, 20.0), (20.0, 20.0))),
+        );
+        assert_clips(
+            (25.0, 0.0),
+            (25.0, 50.0),
+            Some(((25.0, 10.0), (25.0, 30.0))),
+        );
+        assert_clips(
+            (25.0, 25.0),
+            (25.0, 0.0),
+            Some(((25.0, 25.0), (25.0, 10.0))),
+        );
+        // parallel to an edge outside of the window
+        assert_clips((0.0, 5.0), (50.0, 5.0), None);
+        assert_clips((45.0, 0.0), (45.0, 50.0), None);
+    }
+
+    #[test]
+    fn clips_diagonal_lines_keeping_their_direction() {
+        assert_clips((0.0, 0.0), (50.0, 50.0), Some(((10.0, 10.0), (30.0, 30.0))));
+        assert_clips((50.0, 50.0), (0.0, 0.0), Some(((30.0, 30.0), (10.0, 10.0))));
+        assert_clips((0.0, 40.0), (40.0, 0.0), Some(((10.0, 30.0), (30.0, 10.0))));
+        // touching a corner only
+        assert_clips((0.0, 20.0), (20.0, 0.0), Some(((10.0, 10.0), (10.0, 10.0))));
+    }
+
+    #[test]
+    fn clips_degenerate_lines_to_a_point_or_nothing() {
+        assert_clips(
+            (20.0, 20.0),
+            (20.0, 20.0),
+            Some(((20.0, 20.0), (20.0, 20.0))),
+        );
+        assert_clips(
+            (10.0, 30.0),
+            (10.0, 30.0),
+            Some(((10.0, 30.0), (10.0, 30.0))),
+        );
+        assert_clips((0.0, 20.0), (0.0, 20.0), None);
+        assert_clips((50.0, 50.0), (50.0, 50.0), None);
+    }
+
+    #[test]
+    fn reports_liang_barsky_parameters() {
+        let window = window();
+
+        assert_eq!(
+            window.liang_barsky((0.0, 20.0), (50.0, 20.0)),
+            Some((0.2, 0.8))
+        );
+        assert_eq!(
+            window.liang_barsky((20.0, 20.0), (30.0, 20.0)),
+            Some((0.0, 1.0))
+        );
+        assert_eq!(window.liang_barsky((0.0, 0.0), (50.0, 5.0)), None);
+    }
+}
@@ -0,0 +1,134 @@
+//! Shapes on the clipboard, as a JSON list of the shapes' own JSON.
+
+use serde_json::Value;
+
+use super::{
+    document::ShapeData, geometry::bounds_of, shape::Shape, svg_import::import_svg,
+    transform::Transform,
+};
+
+/// How far pasted shapes are moved from where they were copied, so they don't hide the originals.
+pub const PASTE_OFFSET: f64 = 10.0;
+
+pub fn to_clipboard<'a>(shapes: impl Iterator<Item = &'a dyn Shape>) -> String {
+    let shapes: Vec<String> = shapes.map(|shape| shape.get_json()).collect();
+    format!("[{}]", shapes.join(","))
+}
+
+/// Reads what `to_clipboard` wrote, a single shape, or an SVG document copied from elsewhere.
+pub fn from_clipboard(text: &str) -> Result<Vec<Box<dyn Shape>>, String> {
+    let text = text.trim();
+    if text.starts_with('<') {
+        return import_svg(text).map(|import| import.shapes);
+    }
+
+    let shapes = match serde_json::from_str(text).map_err(|error| error.to_string())? {
+        Value::Array(shapes) => shapes,
+        shape => vec![shape],
+    };
+    shapes
+        .into_iter()
+        .enumerate()
+        .map(|(i, shape)| {
+            let shape = serde_json::from_value::<ShapeData>(shape)
+                .map_err(|error| format!("shape {}: {}", i, error))?
+                .into_shape();
+            // e.g. missing the points it's drawn from
+            match shape.is_drawable() {
+                true => Ok(shape),
+                false => Err(format!("shape {}: nothing to draw", i)),
+            }
+        })
+        .collect()
+}
+
+/// Moves the shapes together so their bounds are centered on `point`, or by `PASTE_OFFSET`
+/// without one.
+pub fn place(shapes: &mut [Box<dyn Shape>], point: Option<(f64, f64)>) {
+    let (dx, dy) = match point {
+        Some((x, y)) => {
+            let corners: Vec<(f64, f64)> = shapes
+                .iter()
+                .filter_map(|shape| shape.get_bounds())
+                .flat_map(|(x1, y1, x2, y2)| [(x1, y1), (x2, y2)])
+                .collect();
+            match bounds_of(&corners) {
+                Some((x1, y1, x2, y2)) => (x - (x1 + x2) / 2.0, y - (y1 + y2) / 2.0),
+                None => return,
+            }
+        }
+        None => (PASTE_OFFSET, PASTE_OFFSET),
+    };
+    for shape in shapes.iter_mut() {
+        shape.apply_transform(Transform::translation(dx, dy));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{line::Line, rectangle::Rectangle, shape::ShapeType, test_util::drawn};
+
+    fn bounds(shapes: &[Box<dyn Shape>]) -> Vec<(f64, f64, f64, f64)> {
+        shapes
+            .iter()
+            .map(|shape| shape.get_bounds().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn pastes_copies_next_to_the_originals_or_at_a_point() {
+        let shapes = [
+            drawn(Box::new(Line::new()), &[(0.0, 0.0), (100.0, 0.0)]),
+            drawn(Box::new(Rectangle::new()), &[(20.0, 20.0), (60.0, 40.0)]),
+        ];
+        let text = to_clipboard(shapes.iter().map(|shape| shape.as_ref()));
+
+        let mut pasted = from_clipboard(&text).unwrap();
+        assert_eq!(pasted[1].get_json(), shapes[1].get_json());
+        place(&mut pasted, None);
+        assert_eq!(
+            bounds(&pasted),
+            [(10.0, 10.0, 110.0, 10.0), (30.0, 30.0, 70.0, 50.0)]
+        );
+
+        let mut pasted = from_clipboard(&text).unwrap();
+        place(&mut pasted, Some((300.0, 200.0)));
+        assert_eq!(
+            bounds(&pasted),
+            [(250.0, 180.0, 350.0, 180.0), (270.0, 200.0, 310.0, 220.0)]
+        );
+    }
+
+    #[test]
+    fn reads_single_shapes_and_svg() {
+        let line = drawn(Box::new(Line::new()), &[(0.0, 0.0), (100.0, 0.0)]);
+        assert_eq!(from_clipboard(&line.get_json()).unwrap().len(), 1);
+
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg"><rect x="1" y="2" width="3" height="4"/></svg>"#;
+        let pasted = from_clipboard(svg).unwrap();
+        assert_eq!(pasted[0].get_type(), ShapeType::Rectangle);
+
+        assert!(from_clipboard("some text").is_err());
+        let text = format!(r#"[{},{{"type":"spline"}}]"#, line.get_json());
+        assert!(from_clipboard(&text)
+            .err()
+            .unwrap()
+            .starts_with("shape 1: unknown variant `spline`"));
+    }
+
+    #[test]
+    fn rejects_shapes_that_cant_be_drawn() {
+        let text = r#"{"type":"line","state":"Complete","end":[1,2]}"#;
+        assert_eq!(
+            from_clipboard(text).err().unwrap(),
+            "shape 0: nothing to draw"
+        );
+        let text =
+            r#"[{"type":"group","children":[{"type":"circle","state":"Complete","radius":5}]}]"#;
+        assert_eq!(
+            from_clipboard(text).err().unwrap(),
+            "shape 0: nothing to draw"
+        );
+    }
+}
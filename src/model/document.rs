@@ -0,0 +1,530 @@
+use std::{error::Error, fmt};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{
+    bezier::Bezier, circle::Circle, ellipse::Ellipse, fill::Fill, group::Group, layer::Layer,
+    line::Line, path::Path, polygon::Polygon, rectangle::Rectangle, shape::Shape,
+    transform::Transform,
+};
+
+pub const CURRENT_VERSION: u32 = 2;
+
+const SHAPE_TYPES: [&str; 10] = [
+    "line",
+    "rectangle",
+    "circle",
+    "polygon",
+    "polyline",
+    "bezier",
+    "ellipse",
+    "fill",
+    "path",
+    "group",
+];
+
+/// A shape as it's stored in a document, tagged with its type.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShapeData {
+    Line(Line),
+    Rectangle(Rectangle),
+    Circle(Circle),
+    Polygon(Polygon),
+    Polyline(Polygon),
+    Bezier(Bezier),
+    Ellipse(Ellipse),
+    Fill(Fill),
+    Path(Path),
+    Group(Group),
+}
+
+impl ShapeData {
+    pub fn into_shape(self) -> Box<dyn Shape> {
+        match self {
+            ShapeData::Line(line) => Box::new(line),
+            ShapeData::Rectangle(rectangle) => Box::new(rectangle),
+            ShapeData::Circle(circle) => Box::new(circle),
+            ShapeData::Polygon(polygon) => Box::new(polygon.with_closed(true)),
+            ShapeData::Polyline(polygon) => Box::new(polygon.with_closed(false)),
+            ShapeData::Bezier(bezier) => Box::new(bezier),
+            ShapeData::Ellipse(ellipse) => Box::new(ellipse),
+            ShapeData::Fill(fill) => Box::new(fill),
+            ShapeData::Path(path) => Box::new(path),
+            ShapeData::Group(group) => Box::new(group),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, DocumentError> {
+        let value = serde_json::from_str(json)?;
+        shape_from_value(0, value)
+    }
+}
+
+/// Size of the drawing area, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+        }
+    }
+}
+
+/// A layer as it's stored in a document, with its shapes from the bottom up.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LayerData {
+    #[serde(flatten)]
+    pub layer: Layer,
+    pub shapes: Vec<ShapeData>,
+}
+
+/// Everything saved to a JSON file, the version is bumped on breaking changes of the format.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Document {
+    pub version: u32,
+    pub canvas: Canvas,
+    /// From the bottom layer up.
+    pub layers: Vec<LayerData>,
+}
+
+impl Document {
+    pub fn new(canvas: Canvas, layers: Vec<LayerData>) -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            canvas,
+            layers,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Couldn't serialize the document!")
+    }
+
+    /// Reads a document of any known version, unversioned files are plain shape lists and
+    /// version 1 files have no layers.
+    pub fn from_json(json: &str) -> Result<Self, DocumentError> {
+        let (canvas, layers) = match serde_json::from_str(json)? {
+            Value::Array(shapes) => (
+                Canvas::default(),
+                vec![(Layer::numbered(1), migrate_v0(shapes)?)],
+            ),
+            Value::Object(mut map) => {
+                let version = match map.get("version") {
+                    Some(Value::Number(version)) => version.as_u64().ok_or_else(|| {
+                        DocumentError::Invalid(format!("bad version {}", version))
+                    })?,
+                    Some(_) => {
+                        return Err(DocumentError::Invalid(
+                            "the version isn't a number".to_string(),
+                        ))
+                    }
+                    None => return Err(DocumentError::MissingVersion),
+                };
+                if version == 0 || version > CURRENT_VERSION as u64 {
+                    return Err(DocumentError::UnsupportedVersion(version));
+                }
+                let canvas = match map.remove("canvas") {
+                    Some(canvas) => serde_json::from_value(canvas)
+                        .map_err(|error| DocumentError::Invalid(format!("canvas: {}", error)))?,
+                    None => Canvas::default(),
+                };
+                match (version, map.remove("shapes"), map.remove("layers")) {
+                    (1, Some(Value::Array(shapes)), _) => {
+                        (canvas, vec![(Layer::numbered(1), shapes)])
+                    }
+                    (1, _, _) => {
+                        return Err(DocumentError::Invalid(
+                            "the shape list is missing".to_string(),
+                        ))
+                    }
+                    (_, _, Some(Value::Array(layers))) => {
+                        let layers = layers
+                            .into_iter()
+                            .enumerate()
+                            .map(|(index, layer)| layer_from_value(index, layer))
+                            .collect::<Result<Vec<_>, DocumentError>>()?;
+                        (canvas, layers)
+                    }
+                    _ => {
+                        return Err(DocumentError::Invalid(
+                            "the layer list is missing".to_string(),
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(DocumentError::Invalid(
+                    "expected a document or a list of shapes".to_string(),
+                ))
+            }
+        };
+
+        // shapes are numbered across the layers in errors
+        let mut index = 0;
+        let mut layer_data = Vec::new();
+        for (layer, shapes) in layers {
+            let shapes = shapes
+                .into_iter()
+                .map(|shape| {
+                    index += 1;
+                    shape_from_value(index - 1, shape)
+                })
+                .collect::<Result<Vec<ShapeData>, DocumentError>>()?;
+            layer_data.push(LayerData { layer, shapes });
+        }
+
+        Ok(Document::new(canvas, layer_data))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocumentError {
+    /// The file isn't JSON at all.
+    Syntax(String),
+    MissingVersion,
+    UnsupportedVersion(u64),
+    /// The JSON doesn't have the layout of a document.
+    Invalid(String),
+    UnknownShapeType(String),
+    InvalidShape {
+        index: usize,
+        reason: String,
+    },
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentError::Syntax(error) => write!(f, "not valid JSON ({})", error),
+            DocumentError::MissingVersion => write!(f, "the document has no version"),
+            DocumentError::UnsupportedVersion(version) => write!(
+                f,
+                "version {} isn't supported, the newest known one is {}",
+                version, CURRENT_VERSION
+            ),
+            DocumentError::Invalid(reason) => write!(f, "{}", reason),
+            DocumentError::UnknownShapeType(shape_type) => {
+                write!(f, "unknown shape type \"{}\"", shape_type)
+            }
+            DocumentError::InvalidShape { index, reason } => {
+                write!(f, "shape {} is invalid: {}", index + 1, reason)
+            }
+        }
+    }
+}
+
+impl Error for DocumentError {}
+
+impl From<serde_json::Error> for DocumentError {
+    fn from(error: serde_json::Error) -> Self {
+        if error.is_data() {
+            DocumentError::Invalid(error.to_string())
+        } else {
+            DocumentError::Syntax(error.to_string())
+        }
+    }
+}
+
+// checks the type first, so an unknown one isn't reported as a generic serde error
+fn shape_from_value(index: usize, value: Value) -> Result<ShapeData, DocumentError> {
+    match value.get("type") {
+        Some(Value::String(shape_type)) if !SHAPE_TYPES.contains(&shape_type.as_str()) => {
+            return Err(DocumentError::UnknownShapeType(shape_type.clone()));
+        }
+        Some(Value::String(_)) => {}
+        _ => {
+            return Err(DocumentError::InvalidShape {
+                index,
+                reason: "the type is missing".to_string(),
+            })
+        }
+    }
+
+    serde_json::from_value(value).map_err(|error| DocumentError::InvalidShape {
+        index,
+        reason: error.to_string(),
+    })
+}
+
+// the layer without its shapes, they're read on their own to report errors by index
+fn layer_from_value(index: usize, value: Value) -> Result<(Layer, Vec<Value>), DocumentError> {
+    let invalid = |reason: &str| DocumentError::Invalid(format!("layer {}: {}", index + 1, reason));
+
+    let mut map = match value {
+        Value::Object(map) => map,
+        _ => return Err(invalid("expected an object")),
+    };
+    let shapes = match map.remove("shapes") {
+        Some(Value::Array(shapes)) => shapes,
+        _ => return Err(invalid("the shape list is missing")),
+    };
+    let layer =
+        serde_json::from_value(Value::Object(map)).map_err(|error| invalid(&error.to_string()))?;
+
+    Ok((layer, shapes))
+}
+
+/// Unversioned files stored points as separate coordinates and rotation as an angle.
+fn migrate_v0(shapes: Vec<Value>) -> Result<Vec<Value>, DocumentError> {
+    shapes
+        .into_iter()
+        .enumerate()
+        .map(|(index, shape)| match shape {
+            Value::Object(map) => migrate_v0_shape(index, map).map(Value::Object),
+            _ => Err(DocumentError::InvalidShape {
+                index,
+                reason: "expected an object".to_string(),
+            }),
+        })
+        .collect()
+}
+
+fn migrate_v0_shape(
+    index: usize,
+    mut map: Map<String, Value>,
+) -> Result<Map<String, Value>, DocumentError> {
+    let invalid = |reason: &str| DocumentError::InvalidShape {
+        index,
+        reason: reason.to_string(),
+    };
+
+    for name in ["origin", "end"] {
+        let x = map.remove(&format!("{}_x", name));
+        let y = map.remove(&format!("{}_y", name));
+        match (x, y) {
+            (Some(x), Some(y)) => {
+                map.insert(name.to_string(), Value::Array(vec![x, y]));
+            }
+            (None, None) => {}
+            _ => return Err(invalid(&format!("{} has a single coordinate", name))),
+        }
+    }
+
+    // rotation used to be stored on its own, around the origin
+    if let Some(angle) = map.remove("angle") {
+        let angle = angle
+            .as_f64()
+            .ok_or_else(|| invalid("the angle isn't a number"))?;
+        let origin: (f64, f64) = match map.get("origin") {
+            Some(origin) => serde_json::from_value(origin.clone())
+                .map_err(|_| invalid("the origin isn't a point"))?,
+            None => return Err(invalid("a rotated shape has no origin")),
+        };
+        let transform: Transform = match map.get("transform") {
+            Some(transform) => serde_json::from_value(transform.clone())
+                .map_err(|_| invalid("the transform isn't a 3x3 matrix"))?,
+            None => Transform::identity(),
+        };
+        let rotation = Transform::around(Transform::rotation(angle), origin);
+        map.insert(
+            "transform".to_string(),
+            serde_json::to_value(rotation.multiply(&transform))
+                .expect("Couldn't serialize the transform!"),
+        );
+    }
+
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        recording::RecordingRenderer,
+        shape::{ShapeState, ShapeType, HIT_TOLERANCE},
+    };
+
+    #[test]
+    fn round_trips_every_shape_type() {
+        let shapes: Vec<Box<dyn Shape>> = vec![
+            Box::new(Line::new()),
+            Box::new(Rectangle::new()),
+            Box::new(Circle::new()),
+            Box::new(Polygon::new(true)),
+            Box::new(Polygon::new(false)),
+            Box::new(Bezier::new()),
+            Box::new(Ellipse::new()),
+        ];
+        let mut shapes: Vec<ShapeData> = shapes
+            .into_iter()
+            .map(|mut shape| {
+                for (x, y) in [(10.0, 20.0), (60.0, 80.0), (110.0, 20.0)] {
+                    shape.add_point(x, y);
+                }
+                shape.finish();
+                shape.move_by(5.0, -5.0);
+                shape.to_data()
+            })
+            .collect();
+        shapes.push(Fill::from_pixels(&[(3, 4), (4, 4), (3, 5)], "#00ff00").to_data());
+        let layer = LayerData {
+            layer: Layer::numbered(1),
+            shapes,
+        };
+        let document = Document::new(
+            Canvas {
+                width: 320,
+                height: 200,
+            },
+            vec![layer],
+        );
+
+        let json = document.to_json();
+        let loaded = Document::from_json(&json).unwrap();
+
+        assert_eq!(loaded.version, CURRENT_VERSION);
+        assert_eq!(
+            loaded.canvas,
+            Canvas {
+                width: 320,
+                height: 200
+            }
+        );
+        let types: Vec<ShapeType> = loaded.layers[0]
+            .shapes
+            .iter()
+            .map(|shape| shape.clone().into_shape().get_type())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ShapeType::Line,
+                ShapeType::Rectangle,
+                ShapeType::Circle,
+                ShapeType::Polygon,
+                ShapeType::Polyline,
+                ShapeType::Bezier,
+                ShapeType::Ellipse,
+                ShapeType::Fill,
+            ]
+        );
+        assert_eq!(loaded.to_json(), json);
+    }
+
+    #[test]
+    fn migrates_unversioned_files() {
+        let json = r#"[
+            {"type":"line","state":"Complete","origin_x":10.0,"origin_y":20.0,"end_x":30.0,"end_y":40.0},
+            {"type":"rectangle","state":"Complete","origin_x":0.0,"origin_y":0.0,"width":50.0,"height":20.0,"angle":1.5707963267948966},
+            {"type":"circle","state":"Complete","origin":[100.0,100.0],"radius":25.0}
+        ]"#;
+
+        let document = Document::from_json(json).unwrap();
+
+        assert_eq!(document.version, CURRENT_VERSION);
+        assert_eq!(document.canvas, Canvas::default());
+        let shapes: Vec<Box<dyn Shape>> = document.layers[0]
+            .shapes
+            .iter()
+            .cloned()
+            .map(ShapeData::into_shape)
+            .collect();
+        assert_eq!(shapes[0].get_origin(), Some((10.0, 20.0)));
+        assert_eq!(shapes[0].get_end(), Some((30.0, 40.0)));
+        // the angle turned into a rotation around the origin
+        let (x, y) = shapes[1].get_transform().apply((50.0, 0.0));
+        assert!(x.abs() < 1e-9 && (y - 50.0).abs() < 1e-9);
+        assert_eq!(shapes[2].get_state(), ShapeState::Complete);
+        assert!(shapes[2].contains(125.0, 100.0, HIT_TOLERANCE));
+    }
+
+    #[test]
+    fn keeps_layers_and_reads_version_1_into_one() {
+        let json = r#"{"version":1,"canvas":{"width":800,"height":600},"shapes":[
+            {"type":"line","state":"Complete","origin":[0.0,0.0],"end":[10.0,10.0]}
+        ]}"#;
+        let document = Document::from_json(json).unwrap();
+        assert_eq!(document.layers.len(), 1);
+        assert_eq!(document.layers[0].layer, Layer::numbered(1));
+        assert_eq!(document.layers[0].shapes.len(), 1);
+
+        let mut hidden = Layer::new("Sketch");
+        hidden.visible = false;
+        hidden.locked = true;
+        let layers = vec![
+            LayerData {
+                layer: hidden.clone(),
+                shapes: document.layers[0].shapes.clone(),
+            },
+            LayerData {
+                layer: Layer::new("Empty"),
+                shapes: Vec::new(),
+            },
+        ];
+        let json = Document::new(Canvas::default(), layers).to_json();
+        let loaded = Document::from_json(&json).unwrap();
+
+        let layers: Vec<&Layer> = loaded.layers.iter().map(|layer| &layer.layer).collect();
+        assert_eq!(layers, [&hidden, &Layer::new("Empty")]);
+        assert_eq!(loaded.layers[0].shapes.len(), 1);
+        assert_eq!(loaded.to_json(), json);
+    }
+
+    #[test]
+    fn loads_shapes_without_points_as_undrawable() {
+        let json = r#"[
+            {"type":"line","state":"Complete","end":[1.0,2.0]},
+            {"type":"rectangle","state":"Complete","width":10.0,"height":10.0},
+            {"type":"circle","state":"Complete","radius":10.0},
+            {"type":"ellipse","state":"Drawing","radius_x":10.0,"radius_y":5.0}
+        ]"#;
+        let mut document = Document::from_json(json).unwrap();
+
+        let mut renderer = RecordingRenderer::new();
+        for shape in document.layers.remove(0).shapes {
+            let shape = shape.into_shape();
+            assert!(!shape.is_drawable(), "{}", shape.get_type());
+            shape.draw(&mut renderer);
+            shape.draw_selected(&mut renderer);
+            assert!(!shape.contains(0.0, 0.0, HIT_TOLERANCE));
+            assert_eq!(shape.get_bounds(), None);
+        }
+        assert!(renderer.calls.is_empty());
+    }
+
+    #[test]
+    fn reports_errors_instead_of_panicking() {
+        let error = |json: &str| Document::from_json(json).err().unwrap();
+
+        assert!(matches!(error("{\"version\":"), DocumentError::Syntax(_)));
+        assert_eq!(error("{\"shapes\":[]}"), DocumentError::MissingVersion);
+        assert_eq!(
+            error("{\"version\":7,\"shapes\":[]}"),
+            DocumentError::UnsupportedVersion(7)
+        );
+        assert!(matches!(
+            error("{\"version\":1}"),
+            DocumentError::Invalid(_)
+        ));
+        assert!(matches!(error("42"), DocumentError::Invalid(_)));
+        assert_eq!(
+            error("[{\"type\":\"star\"}]"),
+            DocumentError::UnknownShapeType("star".to_string())
+        );
+        assert!(matches!(
+            error("{\"version\":1,\"shapes\":[{\"type\":\"line\",\"state\":\"Complete\"},{\"type\":\"circle\"}]}"),
+            DocumentError::InvalidShape { index: 1, .. }
+        ));
+        assert!(matches!(
+            error("{\"version\":2,\"shapes\":[]}"),
+            DocumentError::Invalid(_)
+        ));
+        // shapes are counted across layers
+        assert!(matches!(
+            error("{\"version\":2,\"layers\":[{\"name\":\"A\",\"shapes\":[{\"type\":\"line\",\"state\":\"Complete\"}]},{\"name\":\"B\",\"shapes\":[{\"type\":\"circle\"}]}]}"),
+            DocumentError::InvalidShape { index: 1, .. }
+        ));
+        assert!(matches!(
+            error("[{\"type\":\"line\",\"state\":\"Complete\",\"origin_x\":1.0}]"),
+            DocumentError::InvalidShape { index: 0, .. }
+        ));
+    }
+}
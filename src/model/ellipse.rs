@@ -0,0 +1,259 @@
+use std::f64::consts;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::ShapeData,
+    geometry::{distance_to_polyline, flatten_ellipse, segments_for_tolerance},
+    renderer::Renderer,
+    shape::{Shape, ShapeState, ShapeType, ORIGIN_X_KEY, ORIGIN_Y_KEY},
+    style::Style,
+    svg,
+    transform::Transform,
+};
+
+pub const RADIUS_X_KEY: &str = "Radius x";
+pub const RADIUS_Y_KEY: &str = "Radius y";
+
+const SEGMENTS: usize = 64;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Ellipse {
+    origin: Option<(f64, f64)>,
+    radius_x: f64,
+    radius_y: f64,
+    #[serde(default)]
+    transform: Transform,
+    #[serde(default)]
+    style: Style,
+    state: ShapeState,
+}
+
+impl Ellipse {
+    pub fn new() -> Self {
+        Self {
+            origin: None,
+            radius_x: 0.0,
+            radius_y: 0.0,
+            transform: Transform::identity(),
+            style: Style::new(),
+            state: ShapeState::New,
+        }
+    }
+}
+
+impl Shape for Ellipse {
+    fn build_path(&self, renderer: &mut dyn Renderer) {
+        let (ox, oy) = self.origin.unwrap();
+        renderer.ellipse(ox, oy, self.radius_x, self.radius_y, 0.0, 2.0 * consts::PI);
+    }
+
+    fn add_point(&mut self, x: f64, y: f64) {
+        match self.origin {
+            Some(_) => {
+                self.set_end(x, y);
+                self.state = ShapeState::Complete;
+            }
+            None => {
+                self.origin = Some((x, y));
+                self.state = ShapeState::Drawing;
+            }
+        }
+    }
+
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Ellipse
+    }
+
+    fn init_from_points(&mut self, origin: (f64, f64), end: (f64, f64)) {
+        self.origin = Some(origin);
+        self.set_end(end.0, end.1);
+    }
+
+    fn set_end(&mut self, x: f64, y: f64) {
+        let (ox, oy) = self.origin.unwrap();
+        let (x, y) = self.to_local(x, y);
+        self.radius_x = (x - ox).abs();
+        self.radius_y = (y - oy).abs();
+    }
+
+    fn get_prop_str(&self) -> String {
+        let mut string = String::new();
+        string += &format!("Type: {:?}\n", self.get_type());
+        if let Some(origin) = self.origin {
+            string += &format!("Origin: {:.0?}\n", origin);
+        }
+        string += &format!("Radius x: {:.2}\n", self.radius_x);
+        string += &format!("Radius y: {:.2}\n", self.radius_y);
+
+        return string;
+    }
+
+    fn get_state(&self) -> ShapeState {
+        self.state
+    }
+
+    fn is_drawable(&self) -> bool {
+        (self.state == ShapeState::Complete || self.state == ShapeState::Drawing)
+            && self.origin.is_some()
+            && self.radius_x != 0.0
+            && self.radius_y != 0.0
+    }
+
+    fn contains(&self, x: f64, y: f64, tolerance: f64) -> bool {
+        if self.origin.is_none() || !self.is_drawable() {
+            return false;
+        }
+
+        let (ox, oy) = self.origin.unwrap();
+        if !self.style.is_filled() {
+            // only the outline counts, measured on the canvas
+            let outline = self.get_outline();
+            return distance_to_polyline((x, y), &outline, true)
+                <= self.style.hit_tolerance(tolerance);
+        }
+
+        let (x, y) = self.to_local(x, y);
+        ((x - ox) / self.radius_x).powf(2.0) + ((y - oy) / self.radius_y).powf(2.0) <= 1.0
+    }
+
+    fn get_origin(&self) -> Option<(f64, f64)> {
+        self.origin
+    }
+
+    fn get_end(&self) -> Option<(f64, f64)> {
+        let (ox, oy) = self.origin?;
+        Some((ox + self.radius_x, oy + self.radius_y))
+    }
+
+    fn get_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        let (ox, oy) = self.transform.apply(self.origin?);
+        // half extents of the ellipse mapped through the linear part of the transform
+        let (m00, m01) = (self.transform.get(0, 0), self.transform.get(0, 1));
+        let (m10, m11) = (self.transform.get(1, 0), self.transform.get(1, 1));
+        let half_width = ((self.radius_x * m00).powf(2.0) + (self.radius_y * m01).powf(2.0)).sqrt();
+        let half_height =
+            ((self.radius_x * m10).powf(2.0) + (self.radius_y * m11).powf(2.0)).sqrt();
+        Some((
+            ox - half_width,
+            oy - half_height,
+            ox + half_width,
+            oy + half_height,
+        ))
+    }
+
+    fn get_outline(&self) -> Vec<(f64, f64)> {
+        match self.origin {
+            Some(origin) => flatten_ellipse(origin, self.radius_x, self.radius_y, SEGMENTS)
+                .iter()
+                .map(|point| self.transform.apply(*point))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn get_contours(&self, tolerance: f64) -> Vec<Vec<(f64, f64)>> {
+        let origin = match self.origin {
+            Some(origin) if self.is_drawable() => origin,
+            _ => return Vec::new(),
+        };
+
+        // flattened by the largest radius the transform stretches it to
+        let radius = self.radius_x.abs().max(self.radius_y.abs()) * self.transform.max_scale();
+        let segments = segments_for_tolerance(radius, tolerance);
+        vec![
+            flatten_ellipse(origin, self.radius_x, self.radius_y, segments)
+                .iter()
+                .map(|point| self.transform.apply(*point))
+                .collect(),
+        ]
+    }
+
+    fn get_props(&self) -> Vec<(String, String)> {
+        let mut map = Vec::new();
+        let (ox, oy) = self.origin.unwrap_or((0.0, 0.0));
+        map.push((ORIGIN_X_KEY.to_string(), ox.to_string()));
+        map.push((ORIGIN_Y_KEY.to_string(), oy.to_string()));
+        map.push((RADIUS_X_KEY.to_string(), self.radius_x.to_string()));
+        map.push((RADIUS_Y_KEY.to_string(), self.radius_y.to_string()));
+        map.extend(self.style.get_props());
+
+        map
+    }
+
+    fn set_prop(&mut self, key: &str, value: &str) {
+        if self.style.set_prop(key, value) {
+            return;
+        }
+
+        let value: f64 = match value.parse() {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+        match key {
+            ORIGIN_X_KEY => {
+                let (_, oy) = self.origin.unwrap_or((0.0, 0.0));
+                self.origin = Some((value, oy));
+            }
+            ORIGIN_Y_KEY => {
+                let (ox, _) = self.origin.unwrap_or((0.0, 0.0));
+                self.origin = Some((ox, value));
+            }
+            RADIUS_X_KEY => self.radius_x = value,
+            RADIUS_Y_KEY => self.radius_y = value,
+            _ => {}
+        }
+    }
+
+    fn resize(&mut self, change: (f64, f64), origin: (f64, f64), _tolerance: f64) {
+        if let Some((ox, oy)) = self.origin {
+            // the dragged point decides the new radii along the ellipse's own axes
+            let (x, y) = self.to_local(origin.0 + change.0, origin.1 + change.1);
+            self.radius_x = (x - ox).abs().max(1.0);
+            self.radius_y = (y - oy).abs().max(1.0);
+        }
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn get_style(&self) -> Style {
+        self.style.clone()
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
+    fn set_state(&mut self, state: ShapeState) {
+        self.state = state;
+    }
+
+    fn to_data(&self) -> ShapeData {
+        ShapeData::Ellipse(self.clone())
+    }
+
+    fn get_svg(&self) -> String {
+        let (ox, oy) = self.origin.unwrap_or((0.0, 0.0));
+        svg::element(
+            "ellipse",
+            &[
+                ("cx", svg::number(ox)),
+                ("cy", svg::number(oy)),
+                ("rx", svg::number(self.radius_x)),
+                ("ry", svg::number(self.radius_y)),
+            ],
+            &self.style,
+            &self.transform,
+        )
+    }
+}
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::ShapeData,
+    geometry::bounds_of,
+    raster::{spans_of, to_pixel, Framebuffer, Rgba, Span},
+    renderer::Renderer,
+    shape::{Shape, ShapeState, ShapeType},
+    style::{Style, FILL_COLOR_KEY, OPACITY_KEY},
+    svg,
+    transform::Transform,
+};
+
+/// Region painted with the fill bucket, stored as the horizontal spans of its pixels.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Fill {
+    spans: Vec<Span>,
+    #[serde(default)]
+    transform: Transform,
+    #[serde(default)]
+    style: Style,
+    state: ShapeState,
+}
+
+impl Fill {
+    pub fn new() -> Self {
+        Self {
+            spans: Vec::new(),
+            transform: Transform::identity(),
+            style: Style::new(),
+            state: ShapeState::New,
+        }
+    }
+
+    /// A complete fill of `pixels` with `color`, a CSS color.
+    pub fn from_pixels(pixels: &[(i64, i64)], color: &str) -> Self {
+        let mut style = Style::new();
+        style.fill_color = Some(color.to_string());
+        // only painted inside, SVG readers shouldn't outline it
+        style.stroke_width = 0.0;
+
+        Self {
+            spans: spans_of(pixels),
+            transform: Transform::identity(),
+            style,
+            state: ShapeState::Complete,
+        }
+    }
+
+    fn pixel_count(&self) -> u64 {
+        self.spans.iter().map(|span| span.length as u64).sum()
+    }
+
+    // bounds of the painted pixels in local coordinates, as corners
+    fn local_corners(&self) -> Option<[(f64, f64); 4]> {
+        let x1 = self.spans.iter().map(|span| span.x).min()? as f64;
+        let x2 = self
+            .spans
+            .iter()
+            .map(|span| span.x + span.length as i64)
+            .max()? as f64;
+        let y1 = self.spans.first()?.y as f64;
+        let y2 = self.spans.last()?.y as f64 + 1.0;
+
+        Some([(x1, y1), (x2, y1), (x2, y2), (x1, y2)])
+    }
+}
+
+impl Shape for Fill {
+    fn build_path(&self, renderer: &mut dyn Renderer) {
+        for span in self.spans.iter() {
+            renderer.rect(span.x as f64, span.y as f64, span.length as f64, 1.0);
+        }
+    }
+
+    fn draw(&self, renderer: &mut dyn Renderer) {
+        if !self.is_drawable() {
+            return;
+        }
+
+        self.trace_path(renderer);
+        renderer.save();
+        self.style.apply(renderer);
+        renderer.fill();
+        renderer.restore();
+    }
+
+    // outlining every span would stripe the region, its bounds are outlined instead
+    fn draw_outline(&self, renderer: &mut dyn Renderer, color: &str, width: f64) {
+        if !self.is_drawable() {
+            return;
+        }
+
+        renderer.save();
+        renderer.begin_path();
+        for (x, y) in self.get_outline() {
+            renderer.line_to(x, y);
+        }
+        renderer.close_path();
+        renderer.set_stroke_color(color);
+        renderer.set_line_width(width.max(1.0));
+        renderer.stroke();
+        renderer.restore();
+    }
+
+    fn rasterize(&self, framebuffer: &mut Framebuffer, _color: Rgba, _anti_aliased: bool) {
+        let color = match (self.is_drawable(), self.style.fill_rgba()) {
+            (true, Some(color)) => color,
+            _ => return,
+        };
+
+        // each span as the strip of pixels it covers, so a scaled up fill stays without gaps
+        for span in self.spans.iter() {
+            let (x1, y1) = (span.x as f64, span.y as f64);
+            let x2 = x1 + span.length as f64;
+            let strip: Vec<(f64, f64)> = [(x1, y1), (x2, y1), (x2, y1 + 1.0), (x1, y1 + 1.0)]
+                .iter()
+                .map(|corner| self.transform.apply(*corner))
+                .collect();
+            framebuffer.fill_polygon(&strip, color);
+        }
+    }
+
+    // like `draw_outline`, the bounds are marked rather than every span
+    fn rasterize_selected(&self, framebuffer: &mut Framebuffer, color: Rgba, anti_aliased: bool) {
+        if !self.is_drawable() {
+            return;
+        }
+
+        let outline = self.get_outline();
+        if anti_aliased {
+            framebuffer.draw_polyline_wu(&outline, true, color);
+        } else {
+            framebuffer.draw_polyline(&outline, true, color);
+        }
+    }
+
+    fn add_point(&mut self, _x: f64, _y: f64) {}
+
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Fill
+    }
+
+    fn init_from_points(&mut self, _origin: (f64, f64), _end: (f64, f64)) {}
+
+    fn set_end(&mut self, _x: f64, _y: f64) {}
+
+    fn get_prop_str(&self) -> String {
+        let mut string = String::new();
+        string += &format!("Type: {:?}\n", self.get_type());
+        string += &format!("Pixels: {}\n", self.pixel_count());
+
+        return string;
+    }
+
+    fn get_state(&self) -> ShapeState {
+        self.state
+    }
+
+    fn is_drawable(&self) -> bool {
+        self.state == ShapeState::Complete && !self.spans.is_empty()
+    }
+
+    fn contains(&self, x: f64, y: f64, _tolerance: f64) -> bool {
+        if !self.is_drawable() {
+            return false;
+        }
+
+        let (x, y) = to_pixel(self.to_local(x, y));
+        self.spans
+            .iter()
+            .any(|span| span.y == y && x >= span.x && x < span.x + span.length as i64)
+    }
+
+    fn get_origin(&self) -> Option<(f64, f64)> {
+        Some(self.local_corners()?[0])
+    }
+
+    fn get_end(&self) -> Option<(f64, f64)> {
+        Some(self.transform.apply(self.local_corners()?[2]))
+    }
+
+    fn get_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        bounds_of(&self.get_outline())
+    }
+
+    fn get_outline(&self) -> Vec<(f64, f64)> {
+        self.local_corners()
+            .map(|corners| {
+                corners
+                    .iter()
+                    .map(|corner| self.transform.apply(*corner))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    // pixels rather than a region with an outline
+    fn get_contours(&self, _tolerance: f64) -> Vec<Vec<(f64, f64)>> {
+        Vec::new()
+    }
+
+    fn get_props(&self) -> Vec<(String, String)> {
+        // the outline of a fill isn't drawn, only its color matters
+        self.style
+            .get_props()
+            .into_iter()
+            .filter(|(key, _)| key == FILL_COLOR_KEY || key == OPACITY_KEY)
+            .collect()
+    }
+
+    fn set_prop(&mut self, key: &str, value: &str) {
+        if key == FILL_COLOR_KEY || key == OPACITY_KEY {
+            self.style.set_prop(key, value);
+        }
+    }
+
+    // a painted region has no handles to drag
+    fn resize(&mut self, _change: (f64, f64), _origin: (f64, f64), _tolerance: f64) {}
+
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn get_style(&self) -> Style {
+        self.style.clone()
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
+    fn set_state(&mut self, state: ShapeState) {
+        self.state = state;
+    }
+
+    fn to_data(&self) -> ShapeData {
+        ShapeData::Fill(self.clone())
+    }
+
+    fn get_svg(&self) -> String {
+        let path: Vec<String> = self
+            .spans
+            .iter()
+            .map(|span| {
+                format!(
+                    "M {} {} h {} v 1 h -{} Z",
+                    span.x, span.y, span.length, span.length
+                )
+            })
+            .collect();
+        svg::element(
+            "path",
+            &[("d", path.join(" "))],
+            &self.style,
+            &self.transform,
+        )
+    }
+}
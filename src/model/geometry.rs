@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+
+// bounds of `segments_for_tolerance`
+const MIN_SEGMENTS: usize = 8;
+const MAX_SEGMENTS: usize = 1024;
+
+pub fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powf(2.0) + (a.1 - b.1).powf(2.0)).sqrt()
+}
+
+pub fn distance_to_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let dx = b.0 - a.0;
+    let dy = b.1 - a.1;
+    let len_sq = dx * dx + dy * dy;
+    if len_sq == 0.0 {
+        return distance(p, a);
+    }
+
+    let t = (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len_sq).clamp(0.0, 1.0);
+
+    distance(p, (a.0 + t * dx, a.1 + t * dy))
+}
+
+pub fn distance_to_polyline(p: (f64, f64), points: &[(f64, f64)], closed: bool) -> f64 {
+    let mut min = f64::INFINITY;
+    for pair in points.windows(2) {
+        min = min.min(distance_to_segment(p, pair[0], pair[1]));
+    }
+    if closed && points.len() > 2 {
+        min = min.min(distance_to_segment(p, points[points.len() - 1], points[0]));
+    }
+    if points.len() == 1 {
+        min = distance(p, points[0]);
+    }
+
+    min
+}
+
+/// Which points a set of closed contours encloses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FillRule {
+    /// Points circled an odd number of times, nested contours alternate between filled and holes.
+    EvenOdd,
+    /// Points the contours wind around at all, holes have to run the other way round.
+    #[default]
+    NonZero,
+}
+
+// even-odd ray casting
+pub fn point_in_polygon(p: (f64, f64), vertices: &[(f64, f64)]) -> bool {
+    let mut inside = false;
+    let mut j = vertices.len().wrapping_sub(1);
+    for i in 0..vertices.len() {
+        let (xi, yi) = vertices[i];
+        let (xj, yj) = vertices[j];
+        if (yi > p.1) != (yj > p.1) && p.0 < (xj - xi) * (p.1 - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+
+    inside
+}
+
+/// How many times the contours wind around `p`, counting clockwise turns on the canvas as positive.
+pub fn winding_number(p: (f64, f64), contours: &[Vec<(f64, f64)>]) -> i32 {
+    let mut winding = 0;
+    for contour in contours {
+        for (i, a) in contour.iter().enumerate() {
+            let b = contour[(i + 1) % contour.len()];
+            if a.1 <= p.1 && b.1 > p.1 && cross(*a, b, p) > 0.0 {
+                winding += 1;
+            } else if a.1 > p.1 && b.1 <= p.1 && cross(*a, b, p) < 0.0 {
+                winding -= 1;
+            }
+        }
+    }
+
+    winding
+}
+
+/// Whether the contours enclose `p` under `rule`.
+pub fn contours_contain(p: (f64, f64), contours: &[Vec<(f64, f64)>], rule: FillRule) -> bool {
+    match rule {
+        FillRule::EvenOdd => {
+            contours
+                .iter()
+                .filter(|contour| point_in_polygon(p, contour))
+                .count()
+                % 2
+                == 1
+        }
+        FillRule::NonZero => winding_number(p, contours) != 0,
+    }
+}
+
+/// Segments needed for a circle of `radius` to stay within `tolerance` of its flattened outline.
+pub fn segments_for_tolerance(radius: f64, tolerance: f64) -> usize {
+    if radius <= tolerance || tolerance <= 0.0 {
+        return MIN_SEGMENTS;
+    }
+
+    // a chord spanning `angle` lies radius * (1 - cos(angle / 2)) inside the arc
+    let angle = 2.0 * (1.0 - tolerance / radius).acos();
+    ((2.0 * std::f64::consts::PI / angle).ceil() as usize).clamp(MIN_SEGMENTS, MAX_SEGMENTS)
+}
+
+/// Evaluates the Bézier curve defined by `points` at `t` using de Casteljau's algorithm.
+pub fn de_casteljau(points: &[(f64, f64)], t: f64) -> (f64, f64) {
+    let mut points = points.to_vec();
+    for level in (1..points.len()).rev() {
+        for i in 0..level {
+            let (ax, ay) = points[i];
+            let (bx, by) = points[i + 1];
+            points[i] = (ax + (bx - ax) * t, ay + (by - ay) * t);
+        }
+    }
+
+    points.first().copied().unwrap_or((0.0, 0.0))
+}
+
+/// Samples the Bézier curve defined by `points` into `segments` straight segments.
+pub fn flatten_bezier(points: &[(f64, f64)], segments: usize) -> Vec<(f64, f64)> {
+    (0..=segments)
+        .map(|i| de_casteljau(points, i as f64 / segments as f64))
+        .collect()
+}
+
+/// Axis-aligned bounds of `points` as (min x, min y, max x, max y).
+pub fn bounds_of(points: &[(f64, f64)]) -> Option<(f64, f64, f64, f64)> {
+    let (first, rest) = points.split_first()?;
+    let mut bounds = (first.0, first.1, first.0, first.1);
+    for (x, y) in rest {
+        bounds.0 = bounds.0.min(*x);
+        bounds.1 = bounds.1.min(*y);
+        bounds.2 = bounds.2.max(*x);
+        bounds.3 = bounds.3.max(*y);
+    }
+
+    Some(bounds)
+}
+
+/// Samples the outline of an axis-aligned ellipse into `segments` points.
+pub fn flatten_ellipse(center: (f64, f64), rx: f64, ry: f64, segments: usize) -> Vec<(f64, f64)> {
+    (0..segments)
+        .map(|i| {
+            let angle = 2.0 * std::f64::consts::PI * i as f64 / segments as f64;
+            (center.0 + rx * angle.cos(), center.1 + ry * angle.sin())
+        })
+        .collect()
+}
+
+/// Area of a simple polygon, positive when its vertices run clockwise on the canvas.
+pub fn signed_area(vertices: &[(f64, f64)]) -> f64 {
+    let mut area = 0.0;
+    for (i, (x1, y1)) in vertices.iter().enumerate() {
+        let (x2, y2) = vertices[(i + 1) % vertices.len()];
+        area += x1 * y2 - x2 * y1;
+    }
+
+    area / 2.0
+}
+
+/// Whether every turn along the polygon goes the same way, collinear vertices are allowed.
+pub fn is_convex(vertices: &[(f64, f64)]) -> bool {
+    let mut sign = 0.0;
+    for i in 0..vertices.len() {
+        let turn = cross(
+            vertices[i],
+            vertices[(i + 1) % vertices.len()],
+            vertices[(i + 2) % vertices.len()],
+        );
+        if turn != 0.0 {
+            if sign * turn < 0.0 {
+                return false;
+            }
+            sign = turn;
+        }
+    }
+
+    vertices.len() >= 3
+}
+
+pub fn cross(o: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+/// Whether the segments `a`-`b` and `c`-`d` touch or cross.
+pub fn segments_intersect(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> bool {
+    let d1 = cross(c, d, a);
+    let d2 = cross(c, d, b);
+    let d3 = cross(a, b, c);
+    let d4 = cross(a, b, d);
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+    {
+        return true;
+    }
+
+    // collinear end points lying on the other segment
+    let on_segment = |p: (f64, f64), q: (f64, f64), r: (f64, f64)| {
+        r.0 >= p.0.min(q.0) && r.0 <= p.0.max(q.0) && r.1 >= p.1.min(q.1) && r.1 <= p.1.max(q.1)
+    };
+    (d1 == 0.0 && on_segment(c, d, a))
+        || (d2 == 0.0 && on_segment(c, d, b))
+        || (d3 == 0.0 && on_segment(a, b, c))
+        || (d4 == 0.0 && on_segment(a, b, d))
+}
+
+/// Point where the segments `a`-`b` and `c`-`d` meet, `None` if they don't or are parallel.
+pub fn segment_intersection(
+    a: (f64, f64),
+    b: (f64, f64),
+    c: (f64, f64),
+    d: (f64, f64),
+) -> Option<(f64, f64)> {
+    let r = (b.0 - a.0, b.1 - a.1);
+    let s = (d.0 - c.0, d.1 - c.1);
+    let denominator = r.0 * s.1 - r.1 * s.0;
+    if denominator == 0.0 {
+        return None;
+    }
+
+    let t = ((c.0 - a.0) * s.1 - (c.1 - a.1) * s.0) / denominator;
+    let u = ((c.0 - a.0) * r.1 - (c.1 - a.1) * r.0) / denominator;
+    if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+        Some((a.0 + r.0 * t, a.1 + r.1 * t))
+    } else {
+        None
+    }
+}
+
+/// Whether any part of the polyline lies inside the rectangle given as bounds.
+pub fn polyline_intersects_rect(
+    points: &[(f64, f64)],
+    closed: bool,
+    rect: (f64, f64, f64, f64),
+) -> bool {
+    let (x1, y1, x2, y2) = rect;
+    let inside = |(x, y): (f64, f64)| x >= x1 && x <= x2 && y >= y1 && y <= y2;
+    if points.iter().any(|point| inside(*point)) {
+        return true;
+    }
+
+    let corners = [(x1, y1), (x2, y1), (x2, y2), (x1, y2)];
+    let mut segments: Vec<((f64, f64), (f64, f64))> =
+        points.windows(2).map(|pair| (pair[0], pair[1])).collect();
+    if closed && points.len() > 2 {
+        segments.push((points[points.len() - 1], points[0]));
+    }
+    segments
+        .iter()
+        .any(|(a, b)| (0..4).any(|i| segments_intersect(*a, *b, corners[i], corners[(i + 1) % 4])))
+}
@@ -0,0 +1,524 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::ShapeData,
+    geometry::{bounds_of, distance},
+    raster::{Framebuffer, Rgba},
+    renderer::Renderer,
+    shape::{Shape, ShapeState, ShapeType},
+    style::Style,
+    transform::Transform,
+};
+
+/// Shapes selected, moved and styled as one, e.g. other groups. Transforming it transforms the
+/// children, its own transform only keeps track of what they went through since grouping.
+///
+/// After double-clicking into a group, one child is edited on its own: the properties, style,
+/// transform, bounds and handles of the group are then those of that child.
+#[derive(Serialize, Deserialize)]
+pub struct Group {
+    #[serde(with = "children")]
+    children: Vec<Box<dyn Shape>>,
+    #[serde(default)]
+    transform: Transform,
+    // the child edited on its own, not saved
+    #[serde(skip)]
+    entered: Option<usize>,
+}
+
+impl Clone for Group {
+    fn clone(&self) -> Self {
+        Self {
+            children: self
+                .children
+                .iter()
+                .map(|child| child.to_data().into_shape())
+                .collect(),
+            transform: self.transform,
+            entered: self.entered,
+        }
+    }
+}
+
+impl Group {
+    pub fn new(children: Vec<Box<dyn Shape>>) -> Self {
+        Self {
+            children,
+            transform: Transform::identity(),
+            entered: None,
+        }
+    }
+
+    /// From the bottom up.
+    pub fn children(&self) -> impl Iterator<Item = &dyn Shape> {
+        self.children.iter().map(|child| child.as_ref())
+    }
+
+    pub fn is_entered(&self) -> bool {
+        self.entered.is_some()
+    }
+
+    /// Enters the group to edit the child at the point on its own. Inside an entered child that
+    /// is a group itself, goes one level deeper.
+    pub fn enter(&mut self, x: f64, y: f64, tolerance: f64) -> bool {
+        match self.entered {
+            Some(child) if self.children[child].contains(x, y, tolerance) => {
+                match self.children[child].as_group_mut() {
+                    Some(group) => group.enter(x, y, tolerance),
+                    None => true,
+                }
+            }
+            _ => self.pick(x, y, tolerance),
+        }
+    }
+
+    /// Switches the edited child to the topmost one at the point, at the deepest entered level.
+    /// Returns false when nothing there is hit.
+    pub fn pick(&mut self, x: f64, y: f64, tolerance: f64) -> bool {
+        if let Some(child) = self.entered {
+            if let Some(group) = self.children[child].as_group_mut() {
+                if group.is_entered() {
+                    return group.pick(x, y, tolerance);
+                }
+            }
+        }
+
+        let hit = (0..self.children.len())
+            .rev()
+            .find(|child| self.children[*child].contains(x, y, tolerance));
+        if hit.is_some() {
+            self.leave();
+            self.entered = hit;
+        }
+
+        hit.is_some()
+    }
+
+    /// Goes back to editing the group as a whole, from any depth.
+    pub fn leave(&mut self) {
+        if let Some(child) = self.entered.take() {
+            if let Some(group) = self.children[child].as_group_mut() {
+                group.leave();
+            }
+        }
+    }
+
+    // the child edited on its own, if entered
+    fn target(&self) -> Option<&dyn Shape> {
+        Some(self.children[self.entered?].as_ref())
+    }
+
+    fn target_mut(&mut self) -> Option<&mut dyn Shape> {
+        Some(self.children[self.entered?].as_mut())
+    }
+
+    fn union_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        let corners: Vec<(f64, f64)> = self
+            .children
+            .iter()
+            .filter_map(|child| child.get_bounds())
+            .flat_map(|(x1, y1, x2, y2)| [(x1, y1), (x2, y2)])
+            .collect();
+        bounds_of(&corners)
+    }
+}
+
+impl Shape for Group {
+    fn build_path(&self, renderer: &mut dyn Renderer) {
+        for child in self.children.iter().filter(|child| child.is_drawable()) {
+            child.build_path(renderer);
+        }
+    }
+
+    fn draw(&self, renderer: &mut dyn Renderer) {
+        for child in self.children.iter() {
+            child.draw(renderer);
+        }
+    }
+
+    fn draw_outline(&self, renderer: &mut dyn Renderer, color: &str, width: f64) {
+        for child in self.children.iter() {
+            child.draw_outline(renderer, color, width);
+        }
+    }
+
+    // only the edited child is marked inside an entered group
+    fn draw_selected(&self, renderer: &mut dyn Renderer) {
+        match self.entered {
+            Some(child) => {
+                self.draw(renderer);
+                self.children[child].draw_selected(renderer);
+            }
+            None => {
+                self.draw(renderer);
+                self.draw_outline(renderer, "red", self.get_style().stroke_width + 2.0);
+            }
+        }
+    }
+
+    fn draw_handles(&self, renderer: &mut dyn Renderer, pixel: f64) {
+        if let Some(child) = self.target() {
+            child.draw_handles(renderer, pixel);
+        }
+    }
+
+    // the children keep their own colors
+    fn rasterize(&self, framebuffer: &mut Framebuffer, _color: Rgba, anti_aliased: bool) {
+        for child in self.children.iter() {
+            child.rasterize(framebuffer, child.get_style().stroke_rgba(), anti_aliased);
+        }
+    }
+
+    fn rasterize_selected(&self, framebuffer: &mut Framebuffer, color: Rgba, anti_aliased: bool) {
+        match self.entered {
+            Some(child) => {
+                self.children[child].rasterize_selected(framebuffer, color, anti_aliased)
+            }
+            None => {
+                for child in self.children.iter() {
+                    child.rasterize_selected(framebuffer, color, anti_aliased);
+                }
+            }
+        }
+    }
+
+    fn add_point(&mut self, _x: f64, _y: f64) {}
+
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Group
+    }
+
+    fn init_from_points(&mut self, _origin: (f64, f64), _end: (f64, f64)) {}
+
+    fn set_end(&mut self, _x: f64, _y: f64) {}
+
+    fn get_prop_str(&self) -> String {
+        let mut string = String::new();
+        string += &format!("Type: {:?}\n", self.get_type());
+        string += &format!("Children: {}\n", self.children.len());
+        if let Some(child) = self.target() {
+            string += &format!("Editing: {:?}\n", child.get_type());
+        }
+
+        return string;
+    }
+
+    fn get_state(&self) -> ShapeState {
+        ShapeState::Complete
+    }
+
+    fn is_drawable(&self) -> bool {
+        self.children.iter().any(|child| child.is_drawable())
+    }
+
+    fn contains(&self, x: f64, y: f64, tolerance: f64) -> bool {
+        self.children
+            .iter()
+            .any(|child| child.contains(x, y, tolerance))
+    }
+
+    fn get_origin(&self) -> Option<(f64, f64)> {
+        let (x1, y1, _, _) = self.get_bounds()?;
+        Some((x1, y1))
+    }
+
+    fn get_end(&self) -> Option<(f64, f64)> {
+        let (_, _, x2, y2) = self.get_bounds()?;
+        Some((x2, y2))
+    }
+
+    fn get_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        match self.target() {
+            Some(child) => child.get_bounds(),
+            None => self.union_bounds(),
+        }
+    }
+
+    // the bounds of the children, a group has no outline of its own
+    fn get_outline(&self) -> Vec<(f64, f64)> {
+        match self.union_bounds() {
+            Some((x1, y1, x2, y2)) => vec![(x1, y1), (x2, y1), (x2, y2), (x1, y2)],
+            None => Vec::new(),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    // the children don't make up a single region
+    fn get_contours(&self, _tolerance: f64) -> Vec<Vec<(f64, f64)>> {
+        Vec::new()
+    }
+
+    // the style the children share is taken from the first one
+    fn get_props(&self) -> Vec<(String, String)> {
+        match self.target() {
+            Some(child) => child.get_props(),
+            None => self.get_style().get_props(),
+        }
+    }
+
+    fn set_prop(&mut self, key: &str, value: &str) {
+        if let Some(child) = self.target_mut() {
+            return child.set_prop(key, value);
+        }
+
+        // only style properties apply to every child
+        if Style::new().set_prop(key, value) {
+            for child in self.children.iter_mut() {
+                child.set_prop(key, value);
+            }
+        }
+    }
+
+    // the corner of the bounds closest to the cursor moves, scaling the children together
+    fn resize(&mut self, change: (f64, f64), origin: (f64, f64), tolerance: f64) {
+        if let Some(child) = self.target_mut() {
+            return child.resize(change, origin, tolerance);
+        }
+
+        let (x1, y1, x2, y2) = match self.union_bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let corners = [(x1, y1), (x2, y1), (x2, y2), (x1, y2)];
+        let closest = (0..corners.len())
+            .map(|i| (distance(corners[i], origin), i))
+            .filter(|(dist, _)| *dist < tolerance)
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((_, i)) = closest {
+            let (corner, fixed) = (corners[i], corners[(i + 2) % corners.len()]);
+            // a side of no length can't be scaled
+            let factor = |moved: f64, fixed: f64, change: f64| {
+                let size = moved - fixed;
+                if size == 0.0 {
+                    1.0
+                } else {
+                    (size + change) / size
+                }
+            };
+            self.scale(
+                (
+                    factor(corner.0, fixed.0, change.0),
+                    factor(corner.1, fixed.1, change.1),
+                ),
+                fixed,
+            );
+        }
+    }
+
+    fn get_transform(&self) -> Transform {
+        match self.target() {
+            Some(child) => child.get_transform(),
+            None => self.transform,
+        }
+    }
+
+    fn get_style(&self) -> Style {
+        match self
+            .target()
+            .or(self.children.first().map(|child| child.as_ref()))
+        {
+            Some(child) => child.get_style(),
+            None => Style::new(),
+        }
+    }
+
+    // the children go through the change from the current transform to the new one
+    fn set_transform(&mut self, transform: Transform) {
+        if let Some(child) = self.target_mut() {
+            return child.set_transform(transform);
+        }
+
+        // children flattened by a singular transform can't be brought back
+        if let Some(inverse) = self.transform.inverse() {
+            self.apply_transform(transform.multiply(&inverse));
+            self.transform = transform;
+        }
+    }
+
+    fn set_style(&mut self, style: Style) {
+        match self.target_mut() {
+            Some(child) => child.set_style(style),
+            None => {
+                for child in self.children.iter_mut() {
+                    child.set_style(style.clone());
+                }
+            }
+        }
+    }
+
+    fn apply_transform(&mut self, transform: Transform) {
+        match self.target_mut() {
+            Some(child) => child.apply_transform(transform),
+            None => {
+                for child in self.children.iter_mut() {
+                    child.apply_transform(transform);
+                }
+                self.transform = transform.multiply(&self.transform);
+            }
+        }
+    }
+
+    fn set_state(&mut self, _state: ShapeState) {}
+
+    fn as_group(&self) -> Option<&Group> {
+        Some(self)
+    }
+
+    fn as_group_mut(&mut self) -> Option<&mut Group> {
+        Some(self)
+    }
+
+    fn to_data(&self) -> ShapeData {
+        ShapeData::Group(self.clone())
+    }
+
+    fn get_svg(&self) -> String {
+        let children: Vec<String> = self.children.iter().map(|child| child.get_svg()).collect();
+        format!("<g>{}</g>", children.join(""))
+    }
+}
+
+// children are stored like the shapes of a document, tagged with their type
+mod children {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{Shape, ShapeData};
+
+    pub fn serialize<S: Serializer>(
+        children: &[Box<dyn Shape>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(children.iter().map(|child| child.to_data()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Box<dyn Shape>>, D::Error> {
+        let children = Vec::<ShapeData>::deserialize(deserializer)?;
+        Ok(children.into_iter().map(ShapeData::into_shape).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        document::{Canvas, Document, LayerData},
+        layer::Layer,
+        line::Line,
+        rectangle::Rectangle,
+        shape::ShapeStorage,
+        test_util::drawn,
+    };
+
+    fn group() -> Group {
+        Group::new(vec![
+            drawn(Box::new(Line::new()), &[(0.0, 0.0), (100.0, 0.0)]),
+            drawn(Box::new(Rectangle::new()), &[(50.0, 20.0), (150.0, 60.0)]),
+        ])
+    }
+
+    #[test]
+    fn moves_and_resizes_the_children_together() {
+        let mut group = group();
+        assert_eq!(group.get_bounds(), Some((0.0, 0.0, 150.0, 60.0)));
+
+        group.apply_transform(Transform::translation(10.0, 5.0));
+        assert_eq!(group.get_bounds(), Some((10.0, 5.0, 160.0, 65.0)));
+        assert_eq!(group.get_transform(), Transform::translation(10.0, 5.0));
+
+        // dragging the bottom right corner doubles the size, the top left one stays
+        group.resize((150.0, 60.0), (160.0, 65.0), 5.0);
+        assert_eq!(group.get_bounds(), Some((10.0, 5.0, 310.0, 125.0)));
+        let bounds: Vec<_> = group
+            .children()
+            .map(|child| child.get_bounds().unwrap())
+            .collect();
+        assert_eq!(
+            bounds,
+            [(10.0, 5.0, 210.0, 5.0), (110.0, 45.0, 310.0, 125.0)]
+        );
+    }
+
+    #[test]
+    fn replaces_its_transform() {
+        let mut group = group();
+        let transform = Transform::translation(20.0, 0.0).multiply(&Transform::scaling(2.0, 1.0));
+
+        group.set_transform(transform);
+        let once = group.get_json();
+        assert_eq!(group.get_bounds(), Some((20.0, 0.0, 320.0, 60.0)));
+        group.set_transform(transform);
+        assert_eq!(group.get_json(), once);
+        assert_eq!(group.get_transform(), transform);
+
+        group.set_transform(Transform::identity());
+        assert_eq!(group.get_bounds(), Some((0.0, 0.0, 150.0, 60.0)));
+    }
+
+    #[test]
+    fn saves_nested_groups() {
+        let nested = Group::new(vec![
+            Box::new(group()),
+            drawn(Box::new(Line::new()), &[(0.0, 90.0), (10.0, 90.0)]),
+        ]);
+        let layers = vec![LayerData {
+            layer: Layer::numbered(1),
+            shapes: vec![nested.to_data()],
+        }];
+        let json = Document::new(Canvas::default(), layers).to_json();
+        let mut loaded = Document::from_json(&json).unwrap();
+
+        let loaded = loaded.layers.remove(0).shapes.remove(0).into_shape();
+        let outer = loaded.as_group().unwrap();
+        let types: Vec<ShapeType> = outer.children().map(|child| child.get_type()).collect();
+        assert_eq!(types, [ShapeType::Group, ShapeType::Line]);
+        assert_eq!(
+            outer
+                .children()
+                .next()
+                .unwrap()
+                .as_group()
+                .unwrap()
+                .children()
+                .count(),
+            2
+        );
+        assert_eq!(loaded.get_bounds(), Some((0.0, 0.0, 150.0, 90.0)));
+        assert_eq!(loaded.get_json(), nested.get_json());
+    }
+
+    #[test]
+    fn edits_a_single_child_once_entered() {
+        let mut storage = ShapeStorage::new();
+        storage.add_shapes(vec![Box::new(group())]);
+
+        assert!(storage.intersect_and_select(50.0, 0.0).is_some());
+        assert_eq!(storage.get_selected_idxs(), [0]);
+        assert!(!storage
+            .get_shape(0)
+            .unwrap()
+            .as_group()
+            .unwrap()
+            .is_entered());
+
+        assert!(storage.enter_group_at(50.0, 0.0));
+        let shape = storage.get_shape_mut(0).unwrap();
+        assert_eq!(shape.get_bounds(), Some((0.0, 0.0, 100.0, 0.0)));
+        shape.apply_transform(Transform::translation(0.0, 10.0));
+        assert_eq!(shape.get_bounds(), Some((0.0, 10.0, 100.0, 10.0)));
+
+        // clicking another child inside the group edits that one instead
+        storage.intersect_and_select(150.0, 40.0);
+        let shape = storage.get_shape(0).unwrap();
+        assert_eq!(shape.get_bounds(), Some((50.0, 20.0, 150.0, 60.0)));
+
+        storage.leave_groups();
+        let shape = storage.get_shape(0).unwrap();
+        assert_eq!(shape.get_bounds(), Some((0.0, 10.0, 150.0, 60.0)));
+        assert!(!storage.enter_group_at(300.0, 300.0));
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+
+use super::shape::ShapeStorage;
+
+/// A reversible change to the shape storage, shapes are kept as their JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// The shape at `idx` changed from `before` to `after`.
+    Edit {
+        idx: usize,
+        before: String,
+        after: String,
+    },
+    /// `shape` was added at `idx`.
+    Insert { idx: usize, shape: String },
+    /// `shape` was taken out from `idx`.
+    Remove { idx: usize, shape: String },
+    /// Several commands done as one, e.g. on every selected shape.
+    Batch(Vec<Command>),
+    /// The whole document changed, e.g. it was cleared or loaded from a file.
+    Replace { before: String, after: String },
+}
+
+impl Command {
+    pub fn undo(&self, storage: &mut ShapeStorage) {
+        match self {
+            Command::Edit { idx, before, .. } => storage.replace_shape(*idx, before),
+            Command::Insert { idx, .. } => storage.remove_shape(*idx),
+            Command::Remove { idx, shape } => storage.insert_shape(*idx, shape),
+            Command::Batch(commands) => {
+                for command in commands.iter().rev() {
+                    command.undo(storage);
+                }
+            }
+            Command::Replace { before, .. } => storage.replace_all(before),
+        }
+    }
+
+    pub fn redo(&self, storage: &mut ShapeStorage) {
+        match self {
+            Command::Edit { idx, after, .. } => storage.replace_shape(*idx, after),
+            Command::Insert { idx, shape } => storage.insert_shape(*idx, shape),
+            Command::Remove { idx, .. } => storage.remove_shape(*idx),
+            Command::Batch(commands) => {
+                for command in commands.iter() {
+                    command.redo(storage);
+                }
+            }
+            Command::Replace { after, .. } => storage.replace_all(after),
+        }
+    }
+
+    fn edited_idx(&self) -> Option<usize> {
+        match self {
+            Command::Edit { idx, .. } => Some(*idx),
+            _ => None,
+        }
+    }
+
+    // folds a later edit of the same shape into this one
+    fn merge(&mut self, next: &Command) -> bool {
+        match (self, next) {
+            (
+                Command::Edit { idx, after, .. },
+                Command::Edit {
+                    idx: next_idx,
+                    after: next_after,
+                    ..
+                },
+            ) if idx == next_idx => {
+                *after = next_after.clone();
+                true
+            }
+            (Command::Batch(commands), Command::Batch(next_commands)) => {
+                let same_shapes = commands.len() == next_commands.len()
+                    && commands
+                        .iter()
+                        .zip(next_commands.iter())
+                        .all(|(command, next)| {
+                            command.edited_idx().is_some()
+                                && command.edited_idx() == next.edited_idx()
+                        });
+                if same_shapes {
+                    for (command, next) in commands.iter_mut().zip(next_commands.iter()) {
+                        command.merge(next);
+                    }
+                }
+                same_shapes
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Undo and redo stacks of commands, the oldest commands are dropped past `depth`.
+pub struct History {
+    undo_stack: VecDeque<Command>,
+    redo_stack: Vec<Command>,
+    depth: usize,
+    merging: bool,
+}
+
+impl History {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            depth,
+            merging: false,
+        }
+    }
+
+    /// Applies `command` to the storage and records it.
+    pub fn execute(&mut self, command: Command, storage: &mut ShapeStorage) {
+        command.redo(storage);
+        self.push(command);
+    }
+
+    /// Records a command that has already been applied to the storage.
+    pub fn push(&mut self, command: Command) {
+        self.merging = false;
+        self.redo_stack.clear();
+        self.undo_stack.push_back(command);
+        if self.undo_stack.len() > self.depth {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    /// Like `push`, but keeps folding commands into one entry until `end_merge` is called.
+    pub fn push_merged(&mut self, command: Command) {
+        if self.merging {
+            if let Some(last) = self.undo_stack.back_mut() {
+                if last.merge(&command) {
+                    return;
+                }
+            }
+        }
+
+        self.push(command);
+        self.merging = true;
+    }
+
+    pub fn end_merge(&mut self) {
+        self.merging = false;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn undo(&mut self, storage: &mut ShapeStorage) -> bool {
+        self.merging = false;
+        match self.undo_stack.pop_back() {
+            Some(command) => {
+                command.undo(storage);
+                self.redo_stack.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, storage: &mut ShapeStorage) -> bool {
+        self.merging = false;
+        match self.redo_stack.pop() {
+            Some(command) => {
+                command.redo(storage);
+                self.undo_stack.push_back(command);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{line::Line, shape::Shape};
+
+    fn edit(idx: usize, before: &str, after: &str) -> Command {
+        Command::Edit {
+            idx,
+            before: before.to_string(),
+            after: after.to_string(),
+        }
+    }
+
+    fn line_json(y: f64) -> String {
+        let mut line = Line::new();
+        line.add_point(0.0, y);
+        line.add_point(100.0, y);
+        line.get_json()
+    }
+
+    #[test]
+    fn merges_the_edits_of_a_drag() {
+        let mut history = History::new(10);
+        history.push_merged(edit(0, "a", "b"));
+        history.push_merged(edit(0, "b", "c"));
+        assert_eq!(history.undo_stack, [edit(0, "a", "c")]);
+
+        // another shape, or a new drag, starts a new entry
+        history.push_merged(edit(1, "x", "y"));
+        history.end_merge();
+        history.push_merged(edit(1, "y", "z"));
+        assert_eq!(history.undo_stack.len(), 3);
+
+        history.push(Command::Batch(vec![edit(0, "c", "d"), edit(1, "z", "w")]));
+        history.push_merged(Command::Batch(vec![edit(0, "d", "e"), edit(1, "w", "v")]));
+        history.push_merged(Command::Batch(vec![edit(0, "e", "f"), edit(1, "v", "u")]));
+        assert_eq!(
+            history.undo_stack.back(),
+            Some(&Command::Batch(vec![edit(0, "d", "f"), edit(1, "w", "u")]))
+        );
+        history.push_merged(Command::Batch(vec![edit(0, "f", "g"), edit(2, "s", "t")]));
+        assert_eq!(history.undo_stack.len(), 6);
+    }
+
+    #[test]
+    fn drops_the_oldest_entry_past_the_depth() {
+        let mut history = History::new(2);
+        for idx in 0..3 {
+            history.push(edit(idx, "a", "b"));
+        }
+
+        assert_eq!(history.undo_stack, [edit(1, "a", "b"), edit(2, "a", "b")]);
+    }
+
+    #[test]
+    fn clears_redo_after_a_new_change() {
+        let mut storage = ShapeStorage::new();
+        let mut history = History::new(10);
+        history.execute(
+            Command::Insert {
+                idx: 0,
+                shape: line_json(0.0),
+            },
+            &mut storage,
+        );
+        assert!(history.undo(&mut storage));
+        assert_eq!(storage.shape_count(), 0);
+        assert!(history.can_redo());
+        assert!(history.redo(&mut storage));
+        assert_eq!(storage.get_shape(0).unwrap().get_json(), line_json(0.0));
+
+        history.undo(&mut storage);
+        history.execute(
+            Command::Insert {
+                idx: 0,
+                shape: line_json(10.0),
+            },
+            &mut storage,
+        );
+        assert!(!history.can_redo());
+        assert!(!history.redo(&mut storage));
+        assert_eq!(storage.get_shape(0).unwrap().get_json(), line_json(10.0));
+    }
+}
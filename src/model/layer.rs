@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const LAYER_KEY: &str = "layer";
+
+/// A named group of shapes drawn together, layers further up the stack are drawn on top.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Layer {
+    pub name: String,
+    #[serde(default = "visible_by_default")]
+    pub visible: bool,
+    /// The shapes of a locked layer are drawn but can't be picked on the canvas.
+    #[serde(default)]
+    pub locked: bool,
+}
+
+impl Layer {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            visible: true,
+            locked: false,
+        }
+    }
+
+    /// "Layer 3" and so on, the name new layers get.
+    pub fn numbered(number: usize) -> Self {
+        Self::new(&format!("Layer {}", number))
+    }
+
+    /// Whether its shapes can be hit on the canvas.
+    pub fn is_editable(&self) -> bool {
+        self.visible && !self.locked
+    }
+}
+
+fn visible_by_default() -> bool {
+    true
+}
+
+/// The JSON of a shape tagged with the index of its layer, so undoing a removal puts it back
+/// where it was.
+pub fn on_layer(json: &str, layer: usize) -> String {
+    match serde_json::from_str(json) {
+        Ok(Value::Object(mut map)) => {
+            map.insert(LAYER_KEY.to_string(), Value::from(layer));
+            Value::Object(map).to_string()
+        }
+        _ => json.to_string(),
+    }
+}
+
+/// The layer a shape was tagged with by `on_layer`.
+pub fn layer_of_json(json: &str) -> Option<usize> {
+    let value: Value = serde_json::from_str(json).ok()?;
+    value.get(LAYER_KEY)?.as_u64().map(|layer| layer as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        history::{Command, History},
+        line::Line,
+        shape::{Shape, ShapeStorage},
+    };
+
+    fn line(y: f64) -> Box<dyn Shape> {
+        let mut line = Line::new();
+        line.add_point(0.0, y);
+        line.add_point(100.0, y);
+        Box::new(line)
+    }
+
+    // the layer every shape is on, identified by the height of the line
+    fn layout(storage: &ShapeStorage) -> Vec<(usize, f64)> {
+        (0..storage.shape_count())
+            .map(|idx| {
+                let shape = storage.get_shape(idx).unwrap();
+                (
+                    storage.layer_of(idx).unwrap(),
+                    shape.get_origin().unwrap().1,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tags_shapes_with_their_layer() {
+        let json = line(10.0).get_json();
+        assert_eq!(layer_of_json(&json), None);
+        assert_eq!(layer_of_json(&on_layer(&json, 3)), Some(3));
+    }
+
+    #[test]
+    fn keeps_shapes_grouped_by_layer() {
+        let mut storage = ShapeStorage::new();
+        storage.add_shapes(vec![line(0.0), line(10.0)]);
+        storage.add_layer();
+        storage.add_shapes(vec![line(20.0)]);
+        storage.set_active_layer(0);
+        storage.add_shapes(vec![line(30.0)]);
+        assert_eq!(
+            layout(&storage),
+            [(0, 0.0), (0, 10.0), (0, 30.0), (1, 20.0)]
+        );
+
+        storage.move_layer(1, 0);
+        assert_eq!(
+            layout(&storage),
+            [(0, 20.0), (1, 0.0), (1, 10.0), (1, 30.0)]
+        );
+        assert_eq!(storage.get_active_layer(), 1);
+        assert_eq!(storage.get_layers()[0].name, "Layer 2");
+
+        storage.intersect_and_select(50.0, 10.0);
+        storage.move_to_layer(&[1, 2], 0);
+        assert_eq!(
+            layout(&storage),
+            [(0, 20.0), (0, 0.0), (0, 10.0), (1, 30.0)]
+        );
+        assert_eq!(storage.get_selected_idxs(), [2]);
+
+        storage.remove_layer(0);
+        assert_eq!(layout(&storage), [(0, 30.0)]);
+        assert_eq!(storage.get_active_layer(), 0);
+        storage.remove_layer(0);
+        assert_eq!(storage.get_layers().len(), 1);
+    }
+
+    #[test]
+    fn hides_and_locks_layers() {
+        let mut storage = ShapeStorage::new();
+        storage.add_shapes(vec![line(0.0)]);
+        storage.add_layer();
+        storage.add_shapes(vec![line(0.0)]);
+
+        assert!(storage.intersect_and_select(50.0, 0.0).is_some());
+        assert_eq!(storage.get_selected_idxs(), [1]);
+        storage.set_layer_locked(1, true);
+        assert!(storage.get_selected_idxs().is_empty());
+        storage.intersect_and_select(50.0, 0.0);
+        assert_eq!(storage.get_selected_idxs(), [0]);
+
+        storage.set_layer_visible(0, false);
+        assert!(storage.intersect_and_select(50.0, 0.0).is_none());
+        assert_eq!(storage.get_visible_shapes().count(), 1);
+        assert!(!storage.is_shape_visible(0) && storage.is_shape_visible(1));
+    }
+
+    #[test]
+    fn undoes_removals_onto_the_same_layer() {
+        let mut storage = ShapeStorage::new();
+        let mut history = History::new(10);
+        storage.add_shapes(vec![line(0.0)]);
+        storage.add_layer();
+        storage.add_shapes(vec![line(10.0)]);
+        storage.set_active_layer(0);
+
+        // the first shape of the top layer sits at the index where the bottom one ends
+        let shape = storage.shape_json(1).unwrap();
+        history.execute(Command::Remove { idx: 1, shape }, &mut storage);
+        assert_eq!(layout(&storage), [(0, 0.0)]);
+        history.undo(&mut storage);
+        assert_eq!(layout(&storage), [(0, 0.0), (1, 10.0)]);
+
+        let before = storage.serialize_to_json();
+        storage.move_layer(0, 1);
+        let after = storage.serialize_to_json();
+        history.push(Command::Replace { before, after });
+        history.undo(&mut storage);
+        assert_eq!(layout(&storage), [(0, 0.0), (1, 10.0)]);
+        assert_eq!(storage.get_layers()[1], Layer::numbered(2));
+    }
+}
@@ -1,13 +1,26 @@
-use std::str::FromStr;
-
-use super::shape::{Shape, ShapeState, ShapeType, ORIGIN_X_KEY, ORIGIN_Y_KEY};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::ShapeData,
+    geometry::bounds_of,
+    renderer::Renderer,
+    shape::{Shape, ShapeState, ShapeType, ORIGIN_X_KEY, ORIGIN_Y_KEY},
+    style::Style,
+    svg,
+    transform::Transform,
+};
 
 pub const END_X_KEY: &str = "End x";
 pub const END_Y_KEY: &str = "End y";
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Line {
     origin: Option<(f64, f64)>,
     end: Option<(f64, f64)>,
+    #[serde(default)]
+    transform: Transform,
+    #[serde(default)]
+    style: Style,
     state: ShapeState,
 }
 
@@ -16,23 +29,19 @@ impl Line {
         Self {
             origin: None,
             end: None,
+            transform: Transform::identity(),
+            style: Style::new(),
             state: ShapeState::New,
         }
     }
 }
 
 impl Shape for Line {
-    fn draw(&self, ctx: &web_sys::CanvasRenderingContext2d) {
-        if !self.is_drawable() {
-            return;
-        }
-
+    fn build_path(&self, renderer: &mut dyn Renderer) {
         let (ox, oy) = self.origin.unwrap();
         let (ex, ey) = self.end.unwrap();
-        ctx.begin_path();
-        ctx.line_to(ox, oy);
-        ctx.line_to(ex, ey);
-        ctx.stroke();
+        renderer.line_to(ox, oy);
+        renderer.line_to(ex, ey);
     }
 
     fn add_point(&mut self, x: f64, y: f64) {
@@ -66,11 +75,11 @@ impl Shape for Line {
     fn get_prop_str(&self) -> String {
         let mut string = String::new();
         string += &format!("Type: {:?}\n", self.get_type());
-        if self.origin.is_some() {
-            string += format!("Origin: {:.0?}\n", self.origin.unwrap()).as_str();
+        if let Some(origin) = self.origin {
+            string += format!("Origin: {:.0?}\n", origin).as_str();
         }
-        if self.end.is_some() {
-            string += format!("End: {:.0?}\n", self.end.unwrap()).as_str();
+        if let Some(end) = self.end {
+            string += format!("End: {:.0?}\n", end).as_str();
         }
 
         return string;
@@ -82,16 +91,18 @@ impl Shape for Line {
 
     fn is_drawable(&self) -> bool {
         (self.state == ShapeState::Complete || self.state == ShapeState::Drawing)
+            && self.origin.is_some()
             && self.end.is_some()
     }
 
-    fn contains(&self, x: f64, y: f64) -> bool {
+    fn contains(&self, x: f64, y: f64, tolerance: f64) -> bool {
         if !self.is_drawable() {
             return false;
         }
 
-        let (ox, oy) = self.origin.unwrap();
-        let (ex, ey) = self.end.unwrap();
+        // measured on the canvas, so the tolerance doesn't scale with the shape
+        let (ox, oy) = self.transform.apply(self.origin.unwrap());
+        let (ex, ey) = self.transform.apply(self.end.unwrap());
 
         let dx = ex - ox;
         let dy = ey - oy;
@@ -100,7 +111,7 @@ impl Shape for Line {
 
         let t = ((x - ox) * dx + (y - oy) * dy) / (dist * dist);
 
-        if t < 0.0 || t > 1.0 {
+        if !(0.0..=1.0).contains(&t) {
             return false;
         }
 
@@ -109,7 +120,7 @@ impl Shape for Line {
 
         let d = ((x - px) * (x - px) + (y - py) * (y - py)).sqrt();
 
-        return d <= 5.0;
+        return d <= self.style.hit_tolerance(tolerance);
     }
 
     fn get_origin(&self) -> Option<(f64, f64)> {
@@ -120,6 +131,26 @@ impl Shape for Line {
         self.end
     }
 
+    fn get_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        bounds_of(&[
+            self.transform.apply(self.origin?),
+            self.transform.apply(self.end?),
+        ])
+    }
+
+    fn get_outline(&self) -> Vec<(f64, f64)> {
+        match (self.origin, self.end) {
+            (Some(origin), Some(end)) => {
+                vec![self.transform.apply(origin), self.transform.apply(end)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        false
+    }
+
     fn get_props(&self) -> Vec<(String, String)> {
         let mut map = Vec::new();
         let (ox, oy) = self.origin.unwrap_or((0.0, 0.0));
@@ -128,95 +159,104 @@ impl Shape for Line {
         map.push((ORIGIN_Y_KEY.to_string(), oy.to_string()));
         map.push((END_X_KEY.to_string(), ex.to_string()));
         map.push((END_Y_KEY.to_string(), ey.to_string()));
+        map.extend(self.style.get_props());
 
         return map;
     }
 
     fn set_prop(&mut self, key: &str, value: &str) {
+        if self.style.set_prop(key, value) {
+            return;
+        }
+
+        let value: f64 = match value.parse() {
+            Ok(value) => value,
+            Err(_) => return,
+        };
         if key == ORIGIN_X_KEY {
             if let Some((_, y)) = self.origin {
-                self.origin = Some((value.parse().unwrap(), y));
+                self.origin = Some((value, y));
             } else {
-                self.origin = Some((value.parse().unwrap(), 0.0));
+                self.origin = Some((value, 0.0));
             }
         } else if key == ORIGIN_Y_KEY {
             if let Some((x, _)) = self.origin {
-                self.origin = Some((x, value.parse().unwrap()));
+                self.origin = Some((x, value));
             } else {
-                self.origin = Some((0.0, value.parse().unwrap()));
+                self.origin = Some((0.0, value));
             }
         } else if key == END_X_KEY {
             if let Some((_, y)) = self.end {
-                self.end = Some((value.parse().unwrap(), y));
+                self.end = Some((value, y));
             } else {
-                self.end = Some((value.parse().unwrap(), 0.0));
+                self.end = Some((value, 0.0));
             }
         } else if key == END_Y_KEY {
             if let Some((x, _)) = self.end {
-                self.end = Some((x, value.parse().unwrap()));
+                self.end = Some((x, value));
             } else {
-                self.end = Some((0.0, value.parse().unwrap()));
+                self.end = Some((0.0, value));
             }
         }
     }
 
-    fn move_by(&mut self, x: f64, y: f64) {
-        if let Some((ox, oy)) = self.origin {
-            self.origin = Some((ox + x, oy + y));
-        }
-        if let Some((ex, ey)) = self.end {
-            self.end = Some((ex + x, ey + y));
-        }
-    }
-
-    fn resize(&mut self, change: (f64, f64), origin: (f64, f64)) {
-        let epsilon = 5.0;
+    fn resize(&mut self, change: (f64, f64), origin: (f64, f64), tolerance: f64) {
+        let change = match self.transform.inverse() {
+            Some(inverse) => inverse.apply_vector(change),
+            None => return,
+        };
+        // endpoints are picked on the canvas and moved in local coordinates
         if let Some((ox, oy)) = self.origin {
-            if (ox - origin.0).abs() < epsilon && (oy - origin.1).abs() < epsilon {
+            let (x, y) = self.transform.apply((ox, oy));
+            if (x - origin.0).abs() < tolerance && (y - origin.1).abs() < tolerance {
                 self.origin = Some((ox + change.0, oy + change.1));
             }
         }
         if let Some((ex, ey)) = self.end {
-            if (ex - origin.0).abs() < epsilon && (ey - origin.1).abs() < epsilon {
+            let (x, y) = self.transform.apply((ex, ey));
+            if (x - origin.0).abs() < tolerance && (y - origin.1).abs() < tolerance {
                 self.end = Some((ex + change.0, ey + change.1));
             }
         }
     }
 
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn get_style(&self) -> Style {
+        self.style.clone()
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
     fn set_state(&mut self, state: ShapeState) {
         self.state = state;
     }
 
-    fn get_json(&self) -> String {
-        let mut map = serde_json::Map::new();
-        map.insert("type".to_string(), serde_json::Value::String("line".to_string()));
-        map.insert("state".to_string(), serde_json::Value::String(self.state.to_string()));
-        if let Some((ox, oy)) = self.origin {
-            map.insert("origin_x".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(ox).unwrap()));
-            map.insert("origin_y".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(oy).unwrap()));
-        }
-        if let Some((ex, ey)) = self.end {
-            map.insert("end_x".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(ex).unwrap()));
-            map.insert("end_y".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(ey).unwrap()));
-        }
-
-        return serde_json::to_string(&map).unwrap();
+    fn to_data(&self) -> ShapeData {
+        ShapeData::Line(self.clone())
     }
 
-    fn from_json(&mut self, json: &str) {
-        let map: serde_json::Map<String, serde_json::Value> = serde_json::from_str(json).unwrap();
-        if let Some(serde_json::Value::Number(ox)) = map.get("origin_x") {
-            if let Some(serde_json::Value::Number(oy)) = map.get("origin_y") {
-                self.origin = Some((ox.as_f64().unwrap(), oy.as_f64().unwrap()));
-            }
-        }
-        if let Some(serde_json::Value::Number(ex)) = map.get("end_x") {
-            if let Some(serde_json::Value::Number(ey)) = map.get("end_y") {
-                self.end = Some((ex.as_f64().unwrap(), ey.as_f64().unwrap()));
-            }
-        }
-        if let Some(serde_json::Value::String(state)) = map.get("state") {
-            self.state = ShapeState::from_str(state).unwrap();
-        }
+    fn get_svg(&self) -> String {
+        let (ox, oy) = self.origin.unwrap_or((0.0, 0.0));
+        let (ex, ey) = self.end.unwrap_or((0.0, 0.0));
+        svg::element(
+            "line",
+            &[
+                ("x1", svg::number(ox)),
+                ("y1", svg::number(oy)),
+                ("x2", svg::number(ex)),
+                ("y2", svg::number(ey)),
+            ],
+            &self.style,
+            &self.transform,
+        )
     }
 }
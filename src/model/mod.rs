@@ -1,4 +1,30 @@
+pub mod bezier;
+pub mod boolean;
 pub mod circle;
+pub mod clip;
+pub mod clipboard;
+pub mod document;
+pub mod ellipse;
+pub mod fill;
+pub mod geometry;
+pub mod group;
+pub mod history;
+pub mod layer;
 pub mod line;
+pub mod path;
+pub mod polygon;
+pub mod polygon_clip;
+pub mod raster;
+#[cfg(test)]
+pub mod recording;
 pub mod rectangle;
+pub mod renderer;
 pub mod shape;
+pub mod snap;
+pub mod style;
+pub mod svg;
+pub mod svg_import;
+#[cfg(test)]
+pub mod test_util;
+pub mod transform;
+pub mod viewport;
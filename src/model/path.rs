@@ -0,0 +1,282 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::ShapeData,
+    geometry::{
+        bounds_of, contours_contain, distance, distance_to_polyline, signed_area, FillRule,
+    },
+    raster::{Framebuffer, Rgba},
+    renderer::Renderer,
+    shape::{Shape, ShapeState, ShapeType},
+    style::Style,
+    svg,
+    transform::Transform,
+};
+
+pub const EVEN_ODD_KEY: &str = "Even-odd fill";
+
+/// Several closed contours filled as one shape, e.g. an outline with holes.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Path {
+    contours: Vec<Vec<(f64, f64)>>,
+    #[serde(default)]
+    fill_rule: FillRule,
+    #[serde(default)]
+    transform: Transform,
+    #[serde(default)]
+    style: Style,
+    state: ShapeState,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self {
+            contours: Vec::new(),
+            fill_rule: FillRule::default(),
+            transform: Transform::identity(),
+            style: Style::new(),
+            state: ShapeState::New,
+        }
+    }
+
+    /// A complete shape of `contours`, given in canvas coordinates.
+    pub fn from_contours(
+        contours: Vec<Vec<(f64, f64)>>,
+        fill_rule: FillRule,
+        style: Style,
+    ) -> Self {
+        Self {
+            contours,
+            fill_rule,
+            transform: Transform::identity(),
+            style,
+            state: ShapeState::Complete,
+        }
+    }
+
+    fn world_contours(&self) -> Vec<Vec<(f64, f64)>> {
+        self.contours
+            .iter()
+            .map(|contour| {
+                contour
+                    .iter()
+                    .map(|vertex| self.transform.apply(*vertex))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Shape for Path {
+    fn build_path(&self, renderer: &mut dyn Renderer) {
+        for contour in self.contours.iter() {
+            for (i, (x, y)) in contour.iter().enumerate() {
+                if i == 0 {
+                    renderer.move_to(*x, *y);
+                } else {
+                    renderer.line_to(*x, *y);
+                }
+            }
+            renderer.close_path();
+        }
+    }
+
+    fn draw(&self, renderer: &mut dyn Renderer) {
+        if !self.is_drawable() {
+            return;
+        }
+
+        self.trace_path(renderer);
+        renderer.save();
+        self.style.apply(renderer);
+        if self.style.is_filled() {
+            renderer.fill_with_rule(self.fill_rule);
+        }
+        renderer.stroke();
+        renderer.restore();
+    }
+
+    fn rasterize(&self, framebuffer: &mut Framebuffer, color: Rgba, anti_aliased: bool) {
+        if !self.is_drawable() {
+            return;
+        }
+
+        let contours = self.world_contours();
+        if let Some(fill) = self.style.fill_rgba() {
+            framebuffer.fill_contours(&contours, self.fill_rule, fill);
+        }
+        for contour in contours.iter() {
+            if anti_aliased {
+                framebuffer.draw_polyline_wu(contour, true, color);
+            } else {
+                framebuffer.draw_polyline(contour, true, color);
+            }
+        }
+    }
+
+    fn add_point(&mut self, _x: f64, _y: f64) {}
+
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Path
+    }
+
+    fn init_from_points(&mut self, _origin: (f64, f64), _end: (f64, f64)) {}
+
+    fn set_end(&mut self, _x: f64, _y: f64) {}
+
+    fn get_prop_str(&self) -> String {
+        let mut string = String::new();
+        string += &format!("Type: {:?}\n", self.get_type());
+        string += &format!("Contours: {}\n", self.contours.len());
+        string += &format!("Fill rule: {:?}\n", self.fill_rule);
+
+        return string;
+    }
+
+    fn get_state(&self) -> ShapeState {
+        self.state
+    }
+
+    fn is_drawable(&self) -> bool {
+        self.state == ShapeState::Complete && !self.contours.is_empty()
+    }
+
+    fn contains(&self, x: f64, y: f64, tolerance: f64) -> bool {
+        if !self.is_drawable() {
+            return false;
+        }
+
+        if !self.style.is_filled() {
+            // only the outlines count, measured on the canvas
+            return self.world_contours().iter().any(|contour| {
+                distance_to_polyline((x, y), contour, true) <= self.style.hit_tolerance(tolerance)
+            });
+        }
+
+        contours_contain(self.to_local(x, y), &self.contours, self.fill_rule)
+    }
+
+    fn get_origin(&self) -> Option<(f64, f64)> {
+        self.contours.first()?.first().copied()
+    }
+
+    fn get_end(&self) -> Option<(f64, f64)> {
+        let (_, _, x2, y2) = self.get_bounds()?;
+        Some((x2, y2))
+    }
+
+    fn get_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        let points: Vec<(f64, f64)> = self.world_contours().into_iter().flatten().collect();
+        bounds_of(&points)
+    }
+
+    // the largest contour, holes lie inside it
+    fn get_outline(&self) -> Vec<(f64, f64)> {
+        self.world_contours()
+            .into_iter()
+            .max_by(|a, b| signed_area(a).abs().total_cmp(&signed_area(b).abs()))
+            .unwrap_or_default()
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn get_contours(&self, _tolerance: f64) -> Vec<Vec<(f64, f64)>> {
+        if !self.is_drawable() {
+            return Vec::new();
+        }
+
+        self.world_contours()
+    }
+
+    fn get_fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    fn get_props(&self) -> Vec<(String, String)> {
+        let mut map = self.style.get_props();
+        map.push((
+            EVEN_ODD_KEY.to_string(),
+            (self.fill_rule == FillRule::EvenOdd).to_string(),
+        ));
+
+        return map;
+    }
+
+    fn set_prop(&mut self, key: &str, value: &str) {
+        if self.style.set_prop(key, value) {
+            return;
+        }
+
+        if key == EVEN_ODD_KEY {
+            self.fill_rule = match value {
+                "true" => FillRule::EvenOdd,
+                _ => FillRule::NonZero,
+            };
+        }
+    }
+
+    fn resize(&mut self, change: (f64, f64), origin: (f64, f64), tolerance: f64) {
+        let transform = self.transform;
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        // like a polygon, the vertex closest to the cursor on the canvas moves
+        let closest = self
+            .contours
+            .iter_mut()
+            .flatten()
+            .map(|vertex| (distance(transform.apply(*vertex), origin), vertex))
+            .filter(|(dist, _)| *dist < tolerance)
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((_, vertex)) = closest {
+            let change = inverse.apply_vector(change);
+            vertex.0 += change.0;
+            vertex.1 += change.1;
+        }
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn get_style(&self) -> Style {
+        self.style.clone()
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
+    fn set_state(&mut self, state: ShapeState) {
+        self.state = state;
+    }
+
+    fn to_data(&self) -> ShapeData {
+        ShapeData::Path(self.clone())
+    }
+
+    fn get_svg(&self) -> String {
+        let data: Vec<String> = self
+            .contours
+            .iter()
+            .map(|contour| format!("M {} Z", svg::points(contour)))
+            .collect();
+        let fill_rule = match self.fill_rule {
+            FillRule::EvenOdd => "evenodd",
+            FillRule::NonZero => "nonzero",
+        };
+        svg::element(
+            "path",
+            &[("d", data.join(" ")), ("fill-rule", fill_rule.to_string())],
+            &self.style,
+            &self.transform,
+        )
+    }
+}
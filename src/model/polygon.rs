@@ -0,0 +1,314 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::ShapeData,
+    geometry::{bounds_of, distance, distance_to_polyline, point_in_polygon},
+    renderer::Renderer,
+    shape::{parse_point_key, point_key, Shape, ShapeState, ShapeType},
+    style::Style,
+    svg,
+    transform::Transform,
+};
+
+pub const VERTEX_COUNT_KEY: &str = "Vertices";
+pub const VERTEX_KEY_PREFIX: &str = "Vertex";
+/// Typed vertex counts are kept below this, every vertex gets its own inputs.
+pub const MAX_VERTICES: usize = 1000;
+
+/// Shape made of an arbitrary number of vertices, closed (polygon) or open (polyline).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Polygon {
+    vertices: Vec<(f64, f64)>,
+    #[serde(skip)]
+    cursor: Option<(f64, f64)>,
+    #[serde(default)]
+    transform: Transform,
+    #[serde(default)]
+    style: Style,
+    #[serde(skip)]
+    closed: bool,
+    state: ShapeState,
+}
+
+impl Polygon {
+    pub fn new(closed: bool) -> Self {
+        Self {
+            vertices: Vec::new(),
+            cursor: None,
+            transform: Transform::identity(),
+            style: Style::new(),
+            closed,
+            state: ShapeState::New,
+        }
+    }
+
+    /// A complete shape through `vertices`, given in canvas coordinates.
+    pub fn from_vertices(vertices: Vec<(f64, f64)>, closed: bool, style: Style) -> Self {
+        Self {
+            vertices,
+            cursor: None,
+            transform: Transform::identity(),
+            style,
+            closed,
+            state: ShapeState::Complete,
+        }
+    }
+
+    /// Whether the shape is a polygon or a polyline, the flag isn't part of its JSON.
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = closed;
+        self
+    }
+
+    fn min_vertices(&self) -> usize {
+        if self.closed {
+            3
+        } else {
+            2
+        }
+    }
+
+    fn world_vertices(&self) -> Vec<(f64, f64)> {
+        self.vertices
+            .iter()
+            .map(|vertex| self.transform.apply(*vertex))
+            .collect()
+    }
+
+    // vertices including the point following the cursor while drawing
+    fn drawn_vertices(&self) -> Vec<(f64, f64)> {
+        let mut vertices = self.vertices.clone();
+        if self.state == ShapeState::Drawing {
+            if let Some(cursor) = self.cursor {
+                vertices.push(cursor);
+            }
+        }
+
+        vertices
+    }
+}
+
+impl Shape for Polygon {
+    fn build_path(&self, renderer: &mut dyn Renderer) {
+        for (x, y) in self.drawn_vertices().iter() {
+            renderer.line_to(*x, *y);
+        }
+        if self.closed && self.state == ShapeState::Complete {
+            renderer.close_path();
+        }
+    }
+
+    fn add_point(&mut self, x: f64, y: f64) {
+        self.vertices.push((x, y));
+        self.cursor = Some((x, y));
+        self.state = ShapeState::Drawing;
+    }
+
+    fn finish(&mut self) {
+        // the clicks preceding a double click land on the same spot
+        self.vertices.dedup();
+        if self.vertices.len() >= self.min_vertices() {
+            self.cursor = None;
+            self.state = ShapeState::Complete;
+        }
+    }
+
+    fn get_type(&self) -> ShapeType {
+        if self.closed {
+            ShapeType::Polygon
+        } else {
+            ShapeType::Polyline
+        }
+    }
+
+    fn init_from_points(&mut self, origin: (f64, f64), end: (f64, f64)) {
+        self.vertices = vec![origin, end];
+    }
+
+    fn set_end(&mut self, x: f64, y: f64) {
+        self.cursor = Some((x, y));
+    }
+
+    fn get_prop_str(&self) -> String {
+        let mut string = String::new();
+        string += &format!("Type: {:?}\n", self.get_type());
+        string += &format!("Vertices: {}\n", self.vertices.len());
+        for (i, vertex) in self.vertices.iter().enumerate() {
+            string += &format!("Vertex {}: {:.0?}\n", i + 1, vertex);
+        }
+
+        return string;
+    }
+
+    fn get_state(&self) -> ShapeState {
+        self.state
+    }
+
+    fn is_drawable(&self) -> bool {
+        match self.state {
+            ShapeState::Drawing => self.drawn_vertices().len() >= 2,
+            ShapeState::Complete => self.vertices.len() >= 2,
+            ShapeState::New => false,
+        }
+    }
+
+    fn contains(&self, x: f64, y: f64, tolerance: f64) -> bool {
+        if !self.is_drawable() {
+            return false;
+        }
+
+        if self.style.is_filled() && point_in_polygon(self.to_local(x, y), &self.vertices) {
+            return true;
+        }
+
+        // measured on the canvas, so the tolerance doesn't scale with the shape
+        distance_to_polyline((x, y), &self.world_vertices(), self.closed)
+            <= self.style.hit_tolerance(tolerance)
+    }
+
+    fn get_origin(&self) -> Option<(f64, f64)> {
+        self.vertices.first().copied()
+    }
+
+    fn get_end(&self) -> Option<(f64, f64)> {
+        if self.vertices.len() < self.min_vertices() {
+            return None;
+        }
+
+        self.vertices.last().copied()
+    }
+
+    fn get_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        bounds_of(&self.world_vertices())
+    }
+
+    fn get_outline(&self) -> Vec<(f64, f64)> {
+        self.world_vertices()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn get_props(&self) -> Vec<(String, String)> {
+        let mut map = Vec::new();
+        map.push((
+            VERTEX_COUNT_KEY.to_string(),
+            self.vertices.len().to_string(),
+        ));
+        for (i, (x, y)) in self.vertices.iter().enumerate() {
+            map.push((point_key(VERTEX_KEY_PREFIX, i, 'x'), x.to_string()));
+            map.push((point_key(VERTEX_KEY_PREFIX, i, 'y'), y.to_string()));
+        }
+        map.extend(self.style.get_props());
+
+        return map;
+    }
+
+    fn set_prop(&mut self, key: &str, value: &str) {
+        if self.style.set_prop(key, value) {
+            return;
+        }
+
+        if key == VERTEX_COUNT_KEY {
+            if let Ok(count) = value.parse::<usize>() {
+                let count = count.clamp(self.min_vertices(), MAX_VERTICES);
+                let last = self.vertices.last().copied().unwrap_or((0.0, 0.0));
+                self.vertices.resize(count, last);
+            }
+            return;
+        }
+
+        if let Some((idx, axis)) = parse_point_key(VERTEX_KEY_PREFIX, key) {
+            if let (Some(vertex), Ok(value)) = (self.vertices.get_mut(idx), value.parse()) {
+                match axis {
+                    'x' => vertex.0 = value,
+                    _ => vertex.1 = value,
+                }
+            }
+        }
+    }
+
+    fn resize(&mut self, change: (f64, f64), origin: (f64, f64), tolerance: f64) {
+        let transform = self.transform;
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        // pick the vertex closest to the cursor on the canvas, move it in local coordinates
+        let closest = self
+            .vertices
+            .iter_mut()
+            .map(|vertex| (distance(transform.apply(*vertex), origin), vertex))
+            .filter(|(dist, _)| *dist < tolerance)
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((_, vertex)) = closest {
+            let change = inverse.apply_vector(change);
+            vertex.0 += change.0;
+            vertex.1 += change.1;
+        }
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn get_style(&self) -> Style {
+        self.style.clone()
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
+    fn set_state(&mut self, state: ShapeState) {
+        self.state = state;
+    }
+
+    fn to_data(&self) -> ShapeData {
+        if self.closed {
+            ShapeData::Polygon(self.clone())
+        } else {
+            ShapeData::Polyline(self.clone())
+        }
+    }
+
+    fn get_svg(&self) -> String {
+        let name = if self.closed { "polygon" } else { "polyline" };
+        svg::element(
+            name,
+            &[("points", svg::points(&self.vertices))],
+            &self.style,
+            &self.transform,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_typed_vertex_counts_in_range() {
+        let vertices = vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)];
+        let mut polygon = Polygon::from_vertices(vertices, true, Style::new());
+
+        polygon.set_prop(VERTEX_COUNT_KEY, "5");
+        assert_eq!(polygon.vertices.len(), 5);
+        assert_eq!(polygon.vertices[4], (10.0, 10.0));
+        polygon.set_prop(VERTEX_COUNT_KEY, "1");
+        assert_eq!(polygon.vertices.len(), 3);
+        polygon.set_prop(VERTEX_COUNT_KEY, "18446744073709551615");
+        assert_eq!(polygon.vertices.len(), MAX_VERTICES);
+        polygon.set_prop(VERTEX_COUNT_KEY, "");
+        assert_eq!(polygon.vertices.len(), MAX_VERTICES);
+
+        let mut polyline = polygon.with_closed(false);
+        polyline.set_prop(VERTEX_COUNT_KEY, "0");
+        assert_eq!(polyline.vertices.len(), 2);
+    }
+}
@@ -0,0 +1,426 @@
+//! Clipping polygons against polygons, Sutherland–Hodgman when the clip polygon is convex and
+//! Weiler–Atherton when it's concave. Polygons may run either way round.
+
+use super::{
+    geometry::{cross, is_convex, point_in_polygon, signed_area},
+    polygon::Polygon,
+    shape::{Shape, ShapeType},
+};
+
+/// The parts of `subject` inside `clip`.
+pub fn clip_polygon(subject: &[(f64, f64)], clip: &[(f64, f64)]) -> Vec<Vec<(f64, f64)>> {
+    if !is_convex(clip) {
+        return weiler_atherton(subject, clip);
+    }
+
+    let clipped = sutherland_hodgman(subject, clip);
+    if clipped.is_empty() {
+        Vec::new()
+    } else {
+        vec![clipped]
+    }
+}
+
+/// Pieces of `target` inside the outline of `clip`, as polygons in the style of the target.
+/// `None` if either shape isn't a closed outline.
+pub fn clip_shape(target: &dyn Shape, clip: &dyn Shape) -> Option<Vec<Polygon>> {
+    // a fill's outline is only the bounds of its pixels, a path's only its largest contour and a
+    // group's only the bounds of its children
+    let clippable = |shape: &dyn Shape| {
+        shape.is_closed()
+            && !matches!(
+                shape.get_type(),
+                ShapeType::Fill | ShapeType::Path | ShapeType::Group
+            )
+    };
+    if !clippable(target) || !clippable(clip) {
+        return None;
+    }
+
+    let pieces = clip_polygon(&target.get_outline(), &clip.get_outline())
+        .into_iter()
+        .map(|vertices| Polygon::from_vertices(vertices, true, target.get_style()))
+        .collect();
+
+    Some(pieces)
+}
+
+/// Clips `subject` by each edge of the convex polygon `clip` in turn, keeping the vertices on
+/// the inner side and adding the points where the outline crosses the edge. A concave subject
+/// stays in one piece, joined along the edges of `clip`.
+pub fn sutherland_hodgman(subject: &[(f64, f64)], clip: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let orientation = signed_area(clip);
+    if orientation == 0.0 {
+        return Vec::new();
+    }
+
+    let mut output = subject.to_vec();
+    for (i, a) in clip.iter().enumerate() {
+        let b = clip[(i + 1) % clip.len()];
+        let inside = |p: (f64, f64)| cross(*a, b, p) * orientation >= 0.0;
+
+        let input = std::mem::take(&mut output);
+        for (j, current) in input.iter().enumerate() {
+            let previous = input[(j + input.len() - 1) % input.len()];
+            match (inside(previous), inside(*current)) {
+                (true, true) => output.push(*current),
+                (true, false) => output.push(line_crossing(previous, *current, *a, b)),
+                (false, true) => {
+                    output.push(line_crossing(previous, *current, *a, b));
+                    output.push(*current);
+                }
+                (false, false) => {}
+            }
+        }
+    }
+
+    simplified(output)
+}
+
+/// Walks the subject from every point where it enters `clip`, switching to the other outline
+/// at each crossing, until it's back where it started. Every walk is one piece of the result.
+/// Outlines touching at a vertex without crossing aren't handled.
+pub fn weiler_atherton(subject: &[(f64, f64)], clip: &[(f64, f64)]) -> Vec<Vec<(f64, f64)>> {
+    let orientation = signed_area(clip);
+    if subject.len() < 3 || orientation == 0.0 {
+        return Vec::new();
+    }
+
+    // following both outlines forwards keeps the inside of each on the same side
+    let mut subject = subject.to_vec();
+    if signed_area(&subject) * orientation < 0.0 {
+        subject.reverse();
+    }
+
+    let mut crossings = Vec::new();
+    for (i, p) in subject.iter().enumerate() {
+        let p2 = subject[(i + 1) % subject.len()];
+        for (j, q) in clip.iter().enumerate() {
+            let q2 = clip[(j + 1) % clip.len()];
+            if let Some((t, u)) = segment_crossing(*p, p2, *q, q2) {
+                crossings.push(Crossing {
+                    point: (p.0 + (p2.0 - p.0) * t, p.1 + (p2.1 - p.1) * t),
+                    subject_edge: (i, t),
+                    clip_edge: (j, u),
+                    entering: (cross(*q, q2, p2) - cross(*q, q2, *p)) * orientation > 0.0,
+                });
+            }
+        }
+    }
+
+    if crossings.is_empty() {
+        // the outlines don't cross, one may lie inside the other
+        return if point_in_polygon(subject[0], clip) {
+            vec![subject]
+        } else if point_in_polygon(clip[0], &subject) {
+            vec![clip.to_vec()]
+        } else {
+            Vec::new()
+        };
+    }
+
+    let (subject_nodes, subject_positions) =
+        interleave(&subject, &crossings, |crossing| crossing.subject_edge);
+    let (clip_nodes, clip_positions) = interleave(clip, &crossings, |crossing| crossing.clip_edge);
+
+    let mut visited = vec![false; crossings.len()];
+    let mut pieces = Vec::new();
+    for start in 0..crossings.len() {
+        if visited[start] || !crossings[start].entering {
+            continue;
+        }
+
+        visited[start] = true;
+        let mut piece = vec![crossings[start].point];
+        let mut on_subject = true;
+        let mut position = subject_positions[start];
+        let mut closed = false;
+        // every node is passed at most once
+        for _ in 0..subject_nodes.len() + clip_nodes.len() {
+            let nodes = if on_subject {
+                &subject_nodes
+            } else {
+                &clip_nodes
+            };
+            position = (position + 1) % nodes.len();
+            let node = &nodes[position];
+            match node.crossing {
+                Some(crossing) if crossing == start => {
+                    closed = true;
+                    break;
+                }
+                Some(crossing) => {
+                    visited[crossing] = true;
+                    piece.push(node.point);
+                    on_subject = !on_subject;
+                    position = if on_subject {
+                        subject_positions[crossing]
+                    } else {
+                        clip_positions[crossing]
+                    };
+                }
+                None => piece.push(node.point),
+            }
+        }
+
+        let piece = simplified(piece);
+        if closed && !piece.is_empty() {
+            pieces.push(piece);
+        }
+    }
+
+    pieces
+}
+
+// a point where the subject crosses the clip outline
+struct Crossing {
+    point: (f64, f64),
+    // edge index and the parameter along it, for both outlines
+    subject_edge: (usize, f64),
+    clip_edge: (usize, f64),
+    entering: bool,
+}
+
+// a vertex of an outline, or a crossing inserted between its vertices
+struct Node {
+    point: (f64, f64),
+    crossing: Option<usize>,
+}
+
+// the vertices with the crossings inserted in order, and where each crossing ended up
+fn interleave(
+    vertices: &[(f64, f64)],
+    crossings: &[Crossing],
+    edge: impl Fn(&Crossing) -> (usize, f64),
+) -> (Vec<Node>, Vec<usize>) {
+    let mut order: Vec<usize> = (0..crossings.len()).collect();
+    order.sort_by(|a, b| {
+        let (a_edge, a_t) = edge(&crossings[*a]);
+        let (b_edge, b_t) = edge(&crossings[*b]);
+        a_edge.cmp(&b_edge).then(a_t.total_cmp(&b_t))
+    });
+
+    let mut nodes = Vec::new();
+    let mut positions = vec![0; crossings.len()];
+    let mut order = order.into_iter().peekable();
+    for (i, vertex) in vertices.iter().enumerate() {
+        nodes.push(Node {
+            point: *vertex,
+            crossing: None,
+        });
+        while let Some(crossing) = order.next_if(|crossing| edge(&crossings[*crossing]).0 == i) {
+            positions[crossing] = nodes.len();
+            nodes.push(Node {
+                point: crossings[crossing].point,
+                crossing: Some(crossing),
+            });
+        }
+    }
+
+    (nodes, positions)
+}
+
+// parameters along p-p2 and q-q2 of the point where they cross, each in [0, 1)
+fn segment_crossing(
+    p: (f64, f64),
+    p2: (f64, f64),
+    q: (f64, f64),
+    q2: (f64, f64),
+) -> Option<(f64, f64)> {
+    let r = (p2.0 - p.0, p2.1 - p.1);
+    let s = (q2.0 - q.0, q2.1 - q.1);
+    let denominator = r.0 * s.1 - r.1 * s.0;
+    if denominator == 0.0 {
+        return None;
+    }
+
+    let (dx, dy) = (q.0 - p.0, q.1 - p.1);
+    let t = (dx * s.1 - dy * s.0) / denominator;
+    let u = (dx * r.1 - dy * r.0) / denominator;
+    if (0.0..1.0).contains(&t) && (0.0..1.0).contains(&u) {
+        Some((t, u))
+    } else {
+        None
+    }
+}
+
+// where p-q crosses the line through a and b, p and q lie on different sides of it
+fn line_crossing(p: (f64, f64), q: (f64, f64), a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    let (side_p, side_q) = (cross(a, b, p), cross(a, b, q));
+    let t = side_p / (side_p - side_q);
+    (p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t)
+}
+
+// without repeated points, empty if nothing with an area is left
+fn simplified(mut vertices: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    vertices.dedup();
+    if vertices.len() > 1 && vertices.first() == vertices.last() {
+        vertices.pop();
+    }
+    if vertices.len() < 3 || signed_area(&vertices) == 0.0 {
+        return Vec::new();
+    }
+
+    vertices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        document::{Canvas, Document, LayerData},
+        geometry::bounds_of,
+        layer::Layer,
+        rectangle::Rectangle,
+    };
+
+    fn square(x1: f64, y1: f64, x2: f64, y2: f64) -> Vec<(f64, f64)> {
+        vec![(x1, y1), (x2, y1), (x2, y2), (x1, y2)]
+    }
+
+    // arms pointing up at x 0..3 and 7..10 down to y 6, joined below down to y 10
+    fn u_shape() -> Vec<(f64, f64)> {
+        vec![
+            (0.0, 0.0),
+            (3.0, 0.0),
+            (3.0, 6.0),
+            (7.0, 6.0),
+            (7.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+        ]
+    }
+
+    fn summary(pieces: &[Vec<(f64, f64)>]) -> Vec<((f64, f64, f64, f64), f64)> {
+        let mut summary: Vec<_> = pieces
+            .iter()
+            .map(|piece| (bounds_of(piece).unwrap(), signed_area(piece).abs()))
+            .collect();
+        summary.sort_by(|a, b| a.0 .0.total_cmp(&b.0 .0));
+        summary
+    }
+
+    #[test]
+    fn tells_convex_polygons_apart() {
+        assert!(is_convex(&square(0.0, 0.0, 10.0, 10.0)));
+        assert!(is_convex(&[
+            (0.0, 0.0),
+            (5.0, 0.0),
+            (10.0, 0.0),
+            (5.0, 5.0)
+        ]));
+        assert!(!is_convex(&u_shape()));
+        assert!(!is_convex(&[(0.0, 0.0), (10.0, 0.0)]));
+    }
+
+    #[test]
+    fn clips_to_convex_polygons_either_way_round() {
+        let subject = square(0.0, 0.0, 10.0, 10.0);
+        let mut clip = square(5.0, 5.0, 15.0, 15.0);
+
+        for _ in 0..2 {
+            let clipped = sutherland_hodgman(&subject, &clip);
+            assert_eq!(summary(&[clipped]), [((5.0, 5.0, 10.0, 10.0), 25.0)]);
+            clip.reverse();
+        }
+
+        let triangle = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)];
+        let clipped = sutherland_hodgman(&square(5.0, -5.0, 15.0, 5.0), &triangle);
+        assert_eq!(summary(&[clipped]), [((5.0, 0.0, 10.0, 5.0), 12.5)]);
+    }
+
+    #[test]
+    fn keeps_or_drops_subjects_without_crossings() {
+        let subject = square(1.0, 1.0, 2.0, 2.0);
+
+        assert_eq!(
+            sutherland_hodgman(&subject, &square(0.0, 0.0, 10.0, 10.0)),
+            subject
+        );
+        assert!(sutherland_hodgman(&subject, &square(5.0, 5.0, 10.0, 10.0)).is_empty());
+        assert_eq!(weiler_atherton(&subject, &u_shape()), vec![subject]);
+        assert!(weiler_atherton(&square(4.0, 1.0, 6.0, 3.0), &u_shape()).is_empty());
+        // the clip polygon lies inside the subject
+        assert_eq!(
+            summary(&weiler_atherton(
+                &square(-1.0, -1.0, 11.0, 11.0),
+                &u_shape()
+            )),
+            [((0.0, 0.0, 10.0, 10.0), 76.0)]
+        );
+    }
+
+    #[test]
+    fn splits_subjects_crossing_concave_polygons() {
+        let subject = square(-1.0, 1.0, 11.0, 4.0);
+
+        let pieces = weiler_atherton(&subject, &u_shape());
+        assert_eq!(
+            summary(&pieces),
+            [((0.0, 1.0, 3.0, 4.0), 9.0), ((7.0, 1.0, 10.0, 4.0), 9.0)]
+        );
+
+        // the direction of either outline doesn't matter
+        let mut reversed = u_shape();
+        reversed.reverse();
+        assert_eq!(
+            summary(&weiler_atherton(&subject, &reversed)),
+            summary(&pieces)
+        );
+        assert_eq!(
+            summary(&clip_polygon(&subject, &u_shape())),
+            summary(&pieces)
+        );
+    }
+
+    #[test]
+    fn clips_concave_subjects_with_both_algorithms() {
+        // both pick the part of the U below its arms
+        let clip = square(-5.0, 5.0, 15.0, 8.0);
+
+        let clipped = sutherland_hodgman(&u_shape(), &clip);
+        assert_eq!(summary(&[clipped]), [((0.0, 5.0, 10.0, 8.0), 26.0)]);
+        assert_eq!(
+            summary(&weiler_atherton(&u_shape(), &clip)),
+            [((0.0, 5.0, 10.0, 8.0), 26.0)]
+        );
+    }
+
+    #[test]
+    fn clips_shapes_into_polygons_that_survive_saving() {
+        let mut target = Rectangle::new();
+        target.add_point(-1.0, 1.0);
+        target.add_point(11.0, 4.0);
+        let clip = Polygon::from_vertices(u_shape(), true, Default::default());
+
+        let pieces = clip_shape(&target, &clip).unwrap();
+        assert_eq!(pieces.len(), 2);
+        assert!(clip_shape(
+            &Polygon::from_vertices(u_shape(), false, Default::default()),
+            &clip
+        )
+        .is_none());
+
+        let layer = LayerData {
+            layer: Layer::numbered(1),
+            shapes: pieces.iter().map(|piece| piece.to_data()).collect(),
+        };
+        let document = Document::new(Canvas::default(), vec![layer]);
+        let mut loaded = Document::from_json(&document.to_json()).unwrap();
+        let outlines: Vec<Vec<(f64, f64)>> = loaded
+            .layers
+            .remove(0)
+            .shapes
+            .into_iter()
+            .map(|shape| {
+                let shape = shape.into_shape();
+                assert_eq!(shape.get_type(), ShapeType::Polygon);
+                shape.get_outline()
+            })
+            .collect();
+        let expected: Vec<Vec<(f64, f64)>> =
+            pieces.iter().map(|piece| piece.get_outline()).collect();
+        assert_eq!(outlines, expected);
+    }
+}
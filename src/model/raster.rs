@@ -0,0 +1,900 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::geometry::FillRule;
+
+/// A pixel color as red, green, blue and alpha.
+pub type Rgba = [u8; 4];
+
+/// A horizontal run of `length` pixels starting at (x, y).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub y: i64,
+    pub x: i64,
+    pub length: u32,
+}
+
+/// Which neighbours of a pixel the flood fill spreads to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Left, right, up and down.
+    Four,
+    /// The four above plus the diagonals.
+    Eight,
+}
+
+impl Connectivity {
+    fn neighbours(self) -> &'static [(i64, i64)] {
+        match self {
+            Connectivity::Four => &[(1, 0), (-1, 0), (0, 1), (0, -1)],
+            Connectivity::Eight => &[
+                (1, 0),
+                (-1, 0),
+                (0, 1),
+                (0, -1),
+                (1, 1),
+                (1, -1),
+                (-1, 1),
+                (-1, -1),
+            ],
+        }
+    }
+}
+
+// an edge in the edge tables of the scanline fill
+struct Edge {
+    // first scanline below the edge
+    end: i64,
+    // where the edge crosses the current scanline
+    x: f64,
+    inverse_slope: f64,
+    // +1 for edges running down the canvas, -1 for edges running up
+    winding: i32,
+}
+
+/// Parses a CSS hex color like "#ff0000" or "#f00", anything else is taken for black.
+pub fn parse_color(color: &str, opacity: f64) -> Rgba {
+    let alpha = (opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let hex = color.trim().strip_prefix('#').unwrap_or("");
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    let rgb = match hex.len() {
+        6 => (|| {
+            Some([
+                channel(&hex[0..2])?,
+                channel(&hex[2..4])?,
+                channel(&hex[4..6])?,
+            ])
+        })(),
+        3 => (|| {
+            let mut rgb = [0; 3];
+            for (value, digit) in rgb.iter_mut().zip(hex.chars()) {
+                *value = channel(&digit.to_string())? * 17;
+            }
+            Some(rgb)
+        })(),
+        _ => None,
+    };
+    let [r, g, b] = rgb.unwrap_or([0, 0, 0]);
+
+    [r, g, b, alpha]
+}
+
+/// RGBA pixels in rows from the top left corner, the layout expected by `ImageData`.
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Framebuffer {
+    /// A fully transparent framebuffer.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    /// Wraps pixels read back from a canvas.
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(pixels.len(), width as usize * height as usize * 4);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    fn offset(&self, x: i64, y: i64) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return None;
+        }
+
+        Some((y as usize * self.width as usize + x as usize) * 4)
+    }
+
+    pub fn get_pixel(&self, x: i64, y: i64) -> Option<Rgba> {
+        let offset = self.offset(x, y)?;
+        let mut color = [0; 4];
+        color.copy_from_slice(&self.pixels[offset..offset + 4]);
+        Some(color)
+    }
+
+    /// Overwrites a pixel, points outside the framebuffer are ignored.
+    pub fn set_pixel(&mut self, x: i64, y: i64, color: Rgba) {
+        if let Some(offset) = self.offset(x, y) {
+            self.pixels[offset..offset + 4].copy_from_slice(&color);
+        }
+    }
+
+    /// Paints `color` over a pixel with `coverage` of it, the way canvas composites ("source-over").
+    pub fn blend_pixel(&mut self, x: i64, y: i64, color: Rgba, coverage: f64) {
+        let offset = match self.offset(x, y) {
+            Some(offset) => offset,
+            None => return,
+        };
+        let alpha = color[3] as f64 / 255.0 * coverage.clamp(0.0, 1.0);
+        if alpha <= 0.0 {
+            return;
+        }
+
+        let pixel = &mut self.pixels[offset..offset + 4];
+        let below = pixel[3] as f64 / 255.0 * (1.0 - alpha);
+        let out = alpha + below;
+        for channel in 0..3 {
+            let value = (color[channel] as f64 * alpha + pixel[channel] as f64 * below) / out;
+            pixel[channel] = value.round() as u8;
+        }
+        pixel[3] = (out * 255.0).round() as u8;
+    }
+
+    /// Bresenham's line algorithm, with integer error terms for every octant.
+    pub fn draw_line(&mut self, from: (i64, i64), to: (i64, i64), color: Rgba) {
+        let (mut x, mut y) = from;
+        let dx = (to.0 - x).abs();
+        let dy = -(to.1 - y).abs();
+        let step_x = if x < to.0 { 1 } else { -1 };
+        let step_y = if y < to.1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        loop {
+            self.set_pixel(x, y, color);
+            if (x, y) == to {
+                break;
+            }
+
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Midpoint circle algorithm, each computed point of the first octant is mirrored into the other seven.
+    pub fn draw_circle(&mut self, center: (i64, i64), radius: i64, color: Rgba) {
+        let (cx, cy) = center;
+        let mut x = radius;
+        let mut y = 0;
+        let mut decision = 1 - radius;
+        while x >= y {
+            for (px, py) in [
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ] {
+                self.set_pixel(cx + px, cy + py, color);
+            }
+
+            y += 1;
+            if decision < 0 {
+                decision += 2 * y + 1;
+            } else {
+                x -= 1;
+                decision += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    /// Xiaolin Wu's line algorithm, each step splits its coverage between the two pixels
+    /// straddling the line. Takes canvas coordinates, where pixel centers lie at .5.
+    pub fn draw_line_wu(&mut self, from: (f64, f64), to: (f64, f64), color: Rgba) {
+        let (mut x0, mut y0) = (from.0 - 0.5, from.1 - 0.5);
+        let (mut x1, mut y1) = (to.0 - 0.5, to.1 - 0.5);
+        // steep lines are walked along y, with the axes swapped
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+        let mut plot = |x: f64, y: f64, coverage: f64| {
+            if steep {
+                self.blend_pixel(y as i64, x as i64, color, coverage);
+            } else {
+                self.blend_pixel(x as i64, y as i64, color, coverage);
+            }
+        };
+
+        // the end points only cover the part of their pixel the line reaches into
+        let x_start = x0.round();
+        let y_start = y0 + gradient * (x_start - x0);
+        let gap = 1.0 - (x0 + 0.5).fract();
+        plot(x_start, y_start.floor(), (1.0 - y_start.fract()) * gap);
+        plot(x_start, y_start.floor() + 1.0, y_start.fract() * gap);
+
+        let x_end = x1.round();
+        let y_end = y1 + gradient * (x_end - x1);
+        let gap = (x1 + 0.5).fract();
+        plot(x_end, y_end.floor(), (1.0 - y_end.fract()) * gap);
+        plot(x_end, y_end.floor() + 1.0, y_end.fract() * gap);
+
+        let mut y = y_start + gradient;
+        let mut x = x_start + 1.0;
+        while x < x_end {
+            plot(x, y.floor(), 1.0 - y.fract());
+            plot(x, y.floor() + 1.0, y.fract());
+            y += gradient;
+            x += 1.0;
+        }
+    }
+
+    /// Wu's circle algorithm, the first octant is walked along x and the coverage of every
+    /// column is split between the two pixels around the exact radius.
+    pub fn draw_circle_wu(&mut self, center: (f64, f64), radius: f64, color: Rgba) {
+        let (cx, cy) = to_pixel(center);
+        let mut offsets: Vec<(i64, i64, u16)> = Vec::new();
+        let mut x = 0;
+        loop {
+            let y = (radius * radius - (x * x) as f64).max(0.0).sqrt();
+            if (x as f64) > y {
+                break;
+            }
+
+            let inner = y.floor() as i64;
+            // coverage in 1/1000ths, so equal offsets can be deduplicated
+            let outer_coverage = ((y - y.floor()) * 1000.0).round() as u16;
+            for (px, py, coverage) in [
+                (x, inner, 1000 - outer_coverage),
+                (x, inner + 1, outer_coverage),
+            ] {
+                for (ox, oy) in [(px, py), (py, px)] {
+                    for (sx, sy) in [(1, 1), (-1, 1), (1, -1), (-1, -1)] {
+                        offsets.push((sx * ox, sy * oy, coverage));
+                    }
+                }
+            }
+            x += 1;
+        }
+        // the mirrors coincide on the axes and diagonals, those pixels are painted once
+        offsets.sort_unstable();
+        offsets.dedup_by_key(|(x, y, _)| (*x, *y));
+        for (x, y, coverage) in offsets {
+            self.blend_pixel(cx + x, cy + y, color, coverage as f64 / 1000.0);
+        }
+    }
+
+    /// Joins the points with Bresenham lines, back to the first one if `closed`.
+    pub fn draw_polyline(&mut self, points: &[(f64, f64)], closed: bool, color: Rgba) {
+        let pixels: Vec<(i64, i64)> = points.iter().map(|point| to_pixel(*point)).collect();
+        for pair in pixels.windows(2) {
+            self.draw_line(pair[0], pair[1], color);
+        }
+        if closed && pixels.len() > 2 {
+            self.draw_line(pixels[pixels.len() - 1], pixels[0], color);
+        }
+    }
+
+    /// Scanline fill of a polygon by the even-odd rule, see `fill_contours`.
+    pub fn fill_polygon(&mut self, vertices: &[(f64, f64)], color: Rgba) {
+        self.fill_contours(&[vertices.to_vec()], FillRule::EvenOdd, color);
+    }
+
+    /// Scanline fill of several closed contours, e.g. an outline with holes. Edges wait in the
+    /// edge table until the first scanline they cross, then the active edge table keeps their
+    /// crossings sorted. A pixel is filled when its center lies inside by `rule`.
+    pub fn fill_contours(&mut self, contours: &[Vec<(f64, f64)>], rule: FillRule, color: Rgba) {
+        let mut edge_table: BTreeMap<i64, Vec<Edge>> = BTreeMap::new();
+        let edges = contours
+            .iter()
+            .filter(|contour| contour.len() >= 3)
+            .flat_map(|contour| {
+                (0..contour.len()).map(move |i| (contour[i], contour[(i + 1) % contour.len()]))
+            });
+        for (start, end) in edges {
+            // horizontal edges never cross a scanline
+            if start.1 == end.1 {
+                continue;
+            }
+
+            let (top, bottom, winding) = if start.1 < end.1 {
+                (start, end, 1)
+            } else {
+                (end, start, -1)
+            };
+            let inverse_slope = (bottom.0 - top.0) / (bottom.1 - top.1);
+            // scanlines run through pixel centers, clipped to the framebuffer
+            let first = ((top.1 - 0.5).ceil() as i64).max(0);
+            let last = ((bottom.1 - 0.5).ceil() as i64).min(self.height as i64);
+            if first >= last {
+                continue;
+            }
+
+            edge_table.entry(first).or_default().push(Edge {
+                end: last,
+                x: top.0 + (first as f64 + 0.5 - top.1) * inverse_slope,
+                inverse_slope,
+                winding,
+            });
+        }
+
+        let mut active: Vec<Edge> = Vec::new();
+        let mut y = match edge_table.keys().next() {
+            Some(y) => *y,
+            None => return,
+        };
+        while !active.is_empty() || !edge_table.is_empty() {
+            if let Some(edges) = edge_table.remove(&y) {
+                active.extend(edges);
+            }
+            active.retain(|edge| edge.end > y);
+            active.sort_by(|a, b| a.x.total_cmp(&b.x));
+
+            // the span from each crossing to the next is inside or outside as a whole
+            let mut winding = 0;
+            for pair in active.windows(2) {
+                winding += pair[0].winding;
+                let inside = match rule {
+                    FillRule::EvenOdd => winding % 2 != 0,
+                    FillRule::NonZero => winding != 0,
+                };
+                if !inside {
+                    continue;
+                }
+
+                let from = (pair[0].x - 0.5).ceil().max(0.0) as i64;
+                let to = ((pair[1].x - 0.5).ceil() as i64).min(self.width as i64);
+                for x in from..to {
+                    self.blend_pixel(x, y, color, 1.0);
+                }
+            }
+
+            for edge in active.iter_mut() {
+                edge.x += edge.inverse_slope;
+            }
+            y += 1;
+        }
+    }
+
+    /// The pixels a flood fill started at `seed` would paint: every pixel reachable from the
+    /// seed through pixels whose channels differ from the seed's by at most `tolerance`.
+    /// Uses an explicit stack, so large regions can't overflow the call stack.
+    pub fn flood_region(
+        &self,
+        seed: (i64, i64),
+        tolerance: u8,
+        connectivity: Connectivity,
+    ) -> Vec<(i64, i64)> {
+        let target = match self.get_pixel(seed.0, seed.1) {
+            Some(target) => target,
+            None => return Vec::new(),
+        };
+        let matches = |color: Rgba| {
+            color
+                .iter()
+                .zip(target.iter())
+                .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+        };
+
+        let mut visited = vec![false; self.width as usize * self.height as usize];
+        let mut region = Vec::new();
+        let mut stack = vec![seed];
+        while let Some((x, y)) = stack.pop() {
+            let offset = match self.offset(x, y) {
+                Some(offset) => offset,
+                None => continue,
+            };
+            if visited[offset / 4] {
+                continue;
+            }
+
+            visited[offset / 4] = true;
+            if !matches(self.get_pixel(x, y).unwrap()) {
+                continue;
+            }
+
+            region.push((x, y));
+            for (dx, dy) in connectivity.neighbours() {
+                stack.push((x + dx, y + dy));
+            }
+        }
+
+        region
+    }
+
+    /// Like `draw_polyline`, with Wu's anti-aliased lines.
+    pub fn draw_polyline_wu(&mut self, points: &[(f64, f64)], closed: bool, color: Rgba) {
+        for pair in points.windows(2) {
+            self.draw_line_wu(pair[0], pair[1], color);
+        }
+        if closed && points.len() > 2 {
+            self.draw_line_wu(points[points.len() - 1], points[0], color);
+        }
+    }
+}
+
+/// Merges pixels into horizontal spans, ordered by row and then column.
+pub fn spans_of(pixels: &[(i64, i64)]) -> Vec<Span> {
+    let mut pixels = pixels.to_vec();
+    pixels.sort_unstable_by_key(|(x, y)| (*y, *x));
+    pixels.dedup();
+
+    let mut spans: Vec<Span> = Vec::new();
+    for (x, y) in pixels {
+        match spans.last_mut() {
+            Some(span) if span.y == y && span.x + span.length as i64 == x => span.length += 1,
+            _ => spans.push(Span { y, x, length: 1 }),
+        }
+    }
+
+    spans
+}
+
+/// The pixel containing a point given in canvas coordinates.
+pub fn to_pixel(point: (f64, f64)) -> (i64, i64) {
+    (point.0.floor() as i64, point.1.floor() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        circle::Circle,
+        fill::Fill,
+        group::Group,
+        line::Line,
+        rectangle::Rectangle,
+        shape::{Shape, HIT_TOLERANCE},
+        style::{FILLED_KEY, FILL_COLOR_KEY, STROKE_COLOR_KEY},
+        test_util::drawn,
+    };
+
+    const BLACK: Rgba = [0, 0, 0, 255];
+
+    // one row of text per row of pixels, '#' marks a painted pixel
+    fn picture(framebuffer: &Framebuffer) -> Vec<String> {
+        (0..framebuffer.height() as i64)
+            .map(|y| {
+                (0..framebuffer.width() as i64)
+                    .map(|x| match framebuffer.get_pixel(x, y) {
+                        Some([_, _, _, 0]) => '.',
+                        _ => '#',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(parse_color("#ff8000", 1.0), [255, 128, 0, 255]);
+        assert_eq!(parse_color("#f80", 0.5), [255, 136, 0, 128]);
+        assert_eq!(parse_color("red", 1.0), [0, 0, 0, 255]);
+        assert_eq!(parse_color("#zzzzzz", 0.0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn draws_lines_in_every_octant() {
+        let mut framebuffer = Framebuffer::new(7, 5);
+        // shallow, steep and diagonal lines in both directions from the center
+        framebuffer.draw_line((3, 2), (6, 3), BLACK);
+        framebuffer.draw_line((3, 2), (0, 1), BLACK);
+        framebuffer.draw_line((3, 2), (4, 4), BLACK);
+        framebuffer.draw_line((3, 2), (2, 0), BLACK);
+
+        assert_eq!(
+            picture(&framebuffer),
+            ["..#....", "###....", "..###..", "....###", "....#.."]
+        );
+    }
+
+    #[test]
+    fn draws_horizontal_vertical_and_single_pixel_lines() {
+        let mut framebuffer = Framebuffer::new(5, 4);
+        framebuffer.draw_line((0, 0), (4, 0), BLACK);
+        framebuffer.draw_line((4, 3), (4, 1), BLACK);
+        framebuffer.draw_line((1, 2), (1, 2), BLACK);
+
+        assert_eq!(picture(&framebuffer), ["#####", "....#", ".#..#", "....#"]);
+    }
+
+    #[test]
+    fn draws_a_diagonal_line_one_pixel_per_step() {
+        let mut framebuffer = Framebuffer::new(4, 4);
+        framebuffer.draw_line((3, 0), (0, 3), BLACK);
+
+        assert_eq!(picture(&framebuffer), ["...#", "..#.", ".#..", "#..."]);
+    }
+
+    #[test]
+    fn clips_pixels_outside_the_framebuffer() {
+        let mut framebuffer = Framebuffer::new(3, 3);
+        framebuffer.draw_line((-5, 1), (10, 1), BLACK);
+
+        assert_eq!(picture(&framebuffer), ["...", "###", "..."]);
+        assert_eq!(framebuffer.get_pixel(3, 1), None);
+    }
+
+    #[test]
+    fn draws_circles_with_the_midpoint_algorithm() {
+        let mut framebuffer = Framebuffer::new(9, 9);
+        framebuffer.draw_circle((4, 4), 3, BLACK);
+
+        assert_eq!(
+            picture(&framebuffer),
+            [
+                ".........",
+                "...###...",
+                "..#...#..",
+                ".#.....#.",
+                ".#.....#.",
+                ".#.....#.",
+                "..#...#..",
+                "...###...",
+                ".........",
+            ]
+        );
+    }
+
+    fn alpha(framebuffer: &Framebuffer, x: i64, y: i64) -> u8 {
+        framebuffer.get_pixel(x, y).unwrap()[3]
+    }
+
+    #[test]
+    fn blends_colors_by_coverage() {
+        let mut framebuffer = Framebuffer::new(2, 1);
+        framebuffer.blend_pixel(0, 0, BLACK, 0.5);
+        framebuffer.set_pixel(1, 0, BLACK);
+        framebuffer.blend_pixel(1, 0, [255, 255, 255, 255], 0.5);
+        framebuffer.blend_pixel(1, 0, BLACK, 0.0);
+
+        assert_eq!(framebuffer.get_pixel(0, 0), Some([0, 0, 0, 128]));
+        assert_eq!(framebuffer.get_pixel(1, 0), Some([128, 128, 128, 255]));
+    }
+
+    #[test]
+    fn draws_a_horizontal_wu_line_through_pixel_centers() {
+        let mut framebuffer = Framebuffer::new(5, 3);
+        framebuffer.draw_line_wu((0.5, 1.5), (4.5, 1.5), BLACK);
+
+        let row = |y: i64| {
+            (0..5)
+                .map(|x| alpha(&framebuffer, x, y))
+                .collect::<Vec<u8>>()
+        };
+        // the end points cover half of their pixels
+        assert_eq!(row(1), [128, 255, 255, 255, 128]);
+        assert_eq!(row(0), [0; 5]);
+        assert_eq!(row(2), [0; 5]);
+    }
+
+    #[test]
+    fn splits_wu_line_coverage_between_neighbouring_pixels() {
+        let mut framebuffer = Framebuffer::new(12, 8);
+        framebuffer.draw_line_wu((0.5, 1.5), (10.5, 5.5), BLACK);
+
+        for x in 1..10 {
+            let column: u32 = (0..8).map(|y| alpha(&framebuffer, x, y) as u32).sum();
+            assert!(
+                (254..=256).contains(&column),
+                "column {} sums to {}",
+                x,
+                column
+            );
+            let painted = (0..8).filter(|y| alpha(&framebuffer, x, *y) > 0).count();
+            assert!(painted <= 2);
+        }
+        // a steep line is walked along y instead
+        let mut framebuffer = Framebuffer::new(8, 12);
+        framebuffer.draw_line_wu((1.5, 0.5), (5.5, 10.5), BLACK);
+        for y in 1..10 {
+            let row: u32 = (0..8).map(|x| alpha(&framebuffer, x, y) as u32).sum();
+            assert!((254..=256).contains(&row), "row {} sums to {}", y, row);
+        }
+    }
+
+    #[test]
+    fn draws_symmetric_wu_circles() {
+        let mut framebuffer = Framebuffer::new(21, 21);
+        framebuffer.draw_circle_wu((10.5, 10.5), 6.5, BLACK);
+
+        for dx in -8..=8 {
+            for dy in -8..=8 {
+                let value = alpha(&framebuffer, 10 + dx, 10 + dy);
+                assert_eq!(value, alpha(&framebuffer, 10 - dx, 10 + dy));
+                assert_eq!(value, alpha(&framebuffer, 10 + dx, 10 - dy));
+                assert_eq!(value, alpha(&framebuffer, 10 + dy, 10 + dx));
+            }
+        }
+        // the radius falls halfway between two pixels on the axes
+        assert_eq!(alpha(&framebuffer, 16, 10), 128);
+        assert_eq!(alpha(&framebuffer, 17, 10), 128);
+        assert_eq!(alpha(&framebuffer, 10, 10), 0);
+    }
+
+    #[test]
+    fn rasterizes_shapes_with_their_stroke_color() {
+        let mut framebuffer = Framebuffer::new(10, 8);
+        let mut line = drawn(Box::new(Line::new()), &[(0.0, 7.0), (9.0, 7.0)]);
+        line.set_prop(STROKE_COLOR_KEY, "#ff0000");
+        let rectangle = drawn(Box::new(Rectangle::new()), &[(1.0, 1.0), (5.0, 4.0)]);
+        let circle = drawn(Box::new(Circle::new()), &[(7.0, 2.0), (8.0, 2.0)]);
+        for shape in [&line, &rectangle, &circle] {
+            shape.rasterize(&mut framebuffer, shape.get_style().stroke_rgba(), false);
+        }
+
+        assert_eq!(
+            picture(&framebuffer),
+            [
+                "..........",
+                ".#####.#..",
+                ".#...##.#.",
+                ".#...#.#..",
+                ".#####....",
+                "..........",
+                "..........",
+                "##########",
+            ]
+        );
+        assert_eq!(framebuffer.get_pixel(4, 7), Some([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn fills_rectangles_by_pixel_centers() {
+        let mut framebuffer = Framebuffer::new(7, 6);
+        framebuffer.fill_polygon(&[(1.0, 1.0), (5.0, 1.0), (5.0, 4.0), (1.0, 4.0)], BLACK);
+
+        assert_eq!(
+            picture(&framebuffer),
+            [".......", ".####..", ".####..", ".####..", ".......", "......."]
+        );
+    }
+
+    #[test]
+    fn fills_triangles_and_concave_polygons() {
+        let mut framebuffer = Framebuffer::new(9, 5);
+        framebuffer.fill_polygon(&[(0.0, 0.0), (8.0, 0.0), (4.0, 4.0)], BLACK);
+
+        assert_eq!(
+            picture(&framebuffer),
+            [
+                "#######..",
+                ".#####...",
+                "..###....",
+                "...#.....",
+                "........."
+            ]
+        );
+
+        // a U, two spans on the rows of its arms
+        let mut framebuffer = Framebuffer::new(7, 6);
+        framebuffer.fill_polygon(
+            &[
+                (0.0, 0.0),
+                (2.0, 0.0),
+                (2.0, 3.0),
+                (5.0, 3.0),
+                (5.0, 0.0),
+                (7.0, 0.0),
+                (7.0, 5.0),
+                (0.0, 5.0),
+            ],
+            BLACK,
+        );
+
+        assert_eq!(
+            picture(&framebuffer),
+            ["##...##", "##...##", "##...##", "#######", "#######", "......."]
+        );
+    }
+
+    #[test]
+    fn fills_self_intersecting_polygons_by_the_even_odd_rule() {
+        // two overlapping squares traced as one outline, the overlap is outside
+        let mut framebuffer = Framebuffer::new(8, 8);
+        framebuffer.fill_polygon(
+            &[
+                (0.0, 0.0),
+                (4.0, 0.0),
+                (4.0, 6.0),
+                (2.0, 6.0),
+                (2.0, 2.0),
+                (6.0, 2.0),
+                (6.0, 4.0),
+                (0.0, 4.0),
+            ],
+            BLACK,
+        );
+
+        assert_eq!(
+            picture(&framebuffer),
+            [
+                "####....", "####....", "##..##..", "##..##..", "..##....", "..##....", "........",
+                "........",
+            ]
+        );
+    }
+
+    #[test]
+    fn clips_fills_to_the_framebuffer() {
+        let mut framebuffer = Framebuffer::new(4, 3);
+        framebuffer.fill_polygon(
+            &[(-5.0, -5.0), (10.0, -5.0), (10.0, 10.0), (-5.0, 10.0)],
+            BLACK,
+        );
+
+        assert_eq!(picture(&framebuffer), ["####", "####", "####"]);
+    }
+
+    // a 7x7 box outline with a gap in its corner
+    fn boxed() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(9, 9);
+        framebuffer.draw_polyline(
+            &[(1.0, 1.0), (7.0, 1.0), (7.0, 7.0), (1.0, 7.0)],
+            true,
+            BLACK,
+        );
+        framebuffer.set_pixel(7, 7, [0, 0, 0, 0]);
+        framebuffer
+    }
+
+    #[test]
+    fn floods_through_diagonal_gaps_only_when_eight_connected() {
+        let framebuffer = boxed();
+
+        let region = framebuffer.flood_region((4, 4), 0, Connectivity::Four);
+        assert_eq!(region.len(), 25);
+        assert!(!region.contains(&(0, 0)));
+
+        let region = framebuffer.flood_region((4, 4), 0, Connectivity::Eight);
+        assert_eq!(region.len(), 81 - 23);
+        assert!(region.contains(&(0, 0)));
+        assert!(region.contains(&(7, 7)));
+    }
+
+    #[test]
+    fn floods_similar_colors_within_the_tolerance() {
+        let mut framebuffer = Framebuffer::new(3, 1);
+        framebuffer.set_pixel(0, 0, [100, 100, 100, 255]);
+        framebuffer.set_pixel(1, 0, [110, 95, 100, 255]);
+        framebuffer.set_pixel(2, 0, [130, 100, 100, 255]);
+
+        assert_eq!(
+            framebuffer.flood_region((0, 0), 0, Connectivity::Four),
+            [(0, 0)]
+        );
+        assert_eq!(
+            framebuffer
+                .flood_region((0, 0), 10, Connectivity::Four)
+                .len(),
+            2
+        );
+        assert_eq!(
+            framebuffer
+                .flood_region((0, 0), 30, Connectivity::Four)
+                .len(),
+            3
+        );
+        assert!(framebuffer
+            .flood_region((3, 0), 255, Connectivity::Four)
+            .is_empty());
+    }
+
+    #[test]
+    fn merges_pixels_into_spans() {
+        let pixels = [(3, 1), (1, 0), (2, 1), (0, 0), (5, 1), (2, 1)];
+
+        assert_eq!(
+            spans_of(&pixels),
+            [
+                Span {
+                    y: 0,
+                    x: 0,
+                    length: 2
+                },
+                Span {
+                    y: 1,
+                    x: 2,
+                    length: 2
+                },
+                Span {
+                    y: 1,
+                    x: 5,
+                    length: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fills_closed_shapes_before_their_outline() {
+        let mut framebuffer = Framebuffer::new(7, 6);
+        let mut rectangle = drawn(Box::new(Rectangle::new()), &[(1.0, 1.0), (5.0, 4.0)]);
+        rectangle.set_prop(FILLED_KEY, "true");
+        rectangle.set_prop(FILL_COLOR_KEY, "#0000ff");
+        rectangle.rasterize(&mut framebuffer, rectangle.get_style().stroke_rgba(), false);
+
+        assert_eq!(
+            picture(&framebuffer),
+            [".......", ".#####.", ".#####.", ".#####.", ".#####.", "......."]
+        );
+        assert_eq!(framebuffer.get_pixel(3, 2), Some([0, 0, 255, 255]));
+        assert_eq!(framebuffer.get_pixel(1, 1), Some(BLACK));
+    }
+
+    #[test]
+    fn repaints_flooded_regions_as_fill_shapes() {
+        let framebuffer = boxed();
+        let region = framebuffer.flood_region((4, 4), 0, Connectivity::Four);
+        let fill = Fill::from_pixels(&region, "#00ff00");
+
+        assert!(fill.contains(2.5, 6.5, HIT_TOLERANCE));
+        assert!(!fill.contains(1.5, 1.5, HIT_TOLERANCE));
+        assert_eq!(fill.get_bounds(), Some((2.0, 2.0, 7.0, 7.0)));
+
+        let mut repainted = Framebuffer::new(9, 9);
+        fill.rasterize(&mut repainted, BLACK, false);
+        for (x, y) in region {
+            assert_eq!(repainted.get_pixel(x, y), Some([0, 255, 0, 255]));
+        }
+        assert_eq!(repainted.get_pixel(1, 1), Some([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn marks_selected_groups_and_fills_in_the_selection_color() {
+        const RED: Rgba = [255, 0, 0, 255];
+        let group = Group::new(vec![
+            drawn(Box::new(Line::new()), &[(0.0, 0.0), (8.0, 0.0)]),
+            drawn(Box::new(Line::new()), &[(0.0, 8.0), (8.0, 8.0)]),
+        ]);
+        let mut framebuffer = Framebuffer::new(9, 9);
+        group.rasterize_selected(&mut framebuffer, RED, false);
+        assert_eq!(framebuffer.get_pixel(4, 0), Some(RED));
+        assert_eq!(framebuffer.get_pixel(4, 8), Some(RED));
+
+        let region = boxed().flood_region((4, 4), 0, Connectivity::Four);
+        let fill = Fill::from_pixels(&region, "#00ff00");
+        let mut framebuffer = Framebuffer::new(9, 9);
+        fill.rasterize_selected(&mut framebuffer, RED, false);
+        assert_eq!(framebuffer.get_pixel(2, 2), Some(RED));
+        assert_eq!(framebuffer.get_pixel(2, 5), Some(RED));
+        assert_eq!(framebuffer.get_pixel(4, 4), Some([0, 0, 0, 0]));
+    }
+}
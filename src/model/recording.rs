@@ -0,0 +1,306 @@
+use super::{geometry::FillRule, renderer::Renderer, transform::Transform};
+
+/// A single call made on a `RecordingRenderer`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCall {
+    Save,
+    Restore,
+    Transform(Transform),
+    BeginPath,
+    MoveTo(f64, f64),
+    LineTo(f64, f64),
+    ClosePath,
+    Rect(f64, f64, f64, f64),
+    Arc(f64, f64, f64, f64, f64),
+    Ellipse(f64, f64, f64, f64, f64, f64),
+    QuadraticCurveTo((f64, f64), (f64, f64)),
+    BezierCurveTo((f64, f64), (f64, f64), (f64, f64)),
+    Stroke,
+    Fill,
+    FillWithRule(FillRule),
+    StrokeRect(f64, f64, f64, f64),
+    SetStrokeColor(String),
+    SetFillColor(String),
+    SetLineWidth(f64),
+    SetLineDash(Vec<f64>),
+    SetOpacity(f64),
+}
+
+/// Keeps every draw call instead of drawing, so rendering can be checked outside a browser.
+#[derive(Default)]
+pub struct RecordingRenderer {
+    pub calls: Vec<DrawCall>,
+}
+
+impl RecordingRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Renderer for RecordingRenderer {
+    fn save(&mut self) {
+        self.calls.push(DrawCall::Save);
+    }
+
+    fn restore(&mut self) {
+        self.calls.push(DrawCall::Restore);
+    }
+
+    fn transform(&mut self, transform: &Transform) {
+        self.calls.push(DrawCall::Transform(*transform));
+    }
+
+    fn begin_path(&mut self) {
+        self.calls.push(DrawCall::BeginPath);
+    }
+
+    fn move_to(&mut self, x: f64, y: f64) {
+        self.calls.push(DrawCall::MoveTo(x, y));
+    }
+
+    fn line_to(&mut self, x: f64, y: f64) {
+        self.calls.push(DrawCall::LineTo(x, y));
+    }
+
+    fn close_path(&mut self) {
+        self.calls.push(DrawCall::ClosePath);
+    }
+
+    fn rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.calls.push(DrawCall::Rect(x, y, width, height));
+    }
+
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start: f64, end: f64) {
+        self.calls.push(DrawCall::Arc(x, y, radius, start, end));
+    }
+
+    fn ellipse(&mut self, x: f64, y: f64, radius_x: f64, radius_y: f64, start: f64, end: f64) {
+        self.calls
+            .push(DrawCall::Ellipse(x, y, radius_x, radius_y, start, end));
+    }
+
+    fn quadratic_curve_to(&mut self, control: (f64, f64), end: (f64, f64)) {
+        self.calls.push(DrawCall::QuadraticCurveTo(control, end));
+    }
+
+    fn bezier_curve_to(&mut self, first: (f64, f64), second: (f64, f64), end: (f64, f64)) {
+        self.calls.push(DrawCall::BezierCurveTo(first, second, end));
+    }
+
+    fn stroke(&mut self) {
+        self.calls.push(DrawCall::Stroke);
+    }
+
+    fn fill(&mut self) {
+        self.calls.push(DrawCall::Fill);
+    }
+
+    fn fill_with_rule(&mut self, rule: FillRule) {
+        self.calls.push(DrawCall::FillWithRule(rule));
+    }
+
+    fn stroke_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.calls.push(DrawCall::StrokeRect(x, y, width, height));
+    }
+
+    fn set_stroke_color(&mut self, color: &str) {
+        self.calls.push(DrawCall::SetStrokeColor(color.to_string()));
+    }
+
+    fn set_fill_color(&mut self, color: &str) {
+        self.calls.push(DrawCall::SetFillColor(color.to_string()));
+    }
+
+    fn set_line_width(&mut self, width: f64) {
+        self.calls.push(DrawCall::SetLineWidth(width));
+    }
+
+    fn set_line_dash(&mut self, dash: &[f64]) {
+        self.calls.push(DrawCall::SetLineDash(dash.to_vec()));
+    }
+
+    fn set_opacity(&mut self, opacity: f64) {
+        self.calls.push(DrawCall::SetOpacity(opacity));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::model::{
+        bezier::Bezier,
+        circle::Circle,
+        ellipse::Ellipse,
+        line::Line,
+        polygon::Polygon,
+        rectangle::Rectangle,
+        shape::Shape,
+        style::{FILLED_KEY, FILL_COLOR_KEY},
+        test_util::drawn,
+    };
+
+    // the calls that build the path, between begin_path and the restore that follows it
+    fn path_of(shape: &dyn Shape) -> Vec<DrawCall> {
+        let mut renderer = RecordingRenderer::new();
+        shape.draw(&mut renderer);
+        renderer
+            .calls
+            .iter()
+            .skip_while(|call| **call != DrawCall::BeginPath)
+            .skip(1)
+            .take_while(|call| **call != DrawCall::Restore)
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn draws_a_line_with_its_style_and_transform() {
+        let mut line = drawn(Box::new(Line::new()), &[(10.0, 20.0), (30.0, 40.0)]);
+        line.move_by(5.0, 0.0);
+        let mut renderer = RecordingRenderer::new();
+
+        line.draw(&mut renderer);
+
+        assert_eq!(
+            renderer.calls,
+            vec![
+                DrawCall::Save,
+                DrawCall::Transform(Transform::translation(5.0, 0.0)),
+                DrawCall::BeginPath,
+                DrawCall::LineTo(10.0, 20.0),
+                DrawCall::LineTo(30.0, 40.0),
+                DrawCall::Restore,
+                DrawCall::Save,
+                DrawCall::SetStrokeColor("#000000".to_string()),
+                DrawCall::SetLineWidth(1.0),
+                DrawCall::SetOpacity(1.0),
+                DrawCall::Stroke,
+                DrawCall::Restore,
+            ]
+        );
+    }
+
+    #[test]
+    fn draws_rectangles_circles_and_ellipses() {
+        let rectangle = drawn(Box::new(Rectangle::new()), &[(10.0, 10.0), (50.0, 30.0)]);
+        let circle = drawn(Box::new(Circle::new()), &[(100.0, 100.0), (130.0, 140.0)]);
+        let ellipse = drawn(Box::new(Ellipse::new()), &[(200.0, 100.0), (250.0, 125.0)]);
+
+        assert_eq!(
+            path_of(rectangle.as_ref()),
+            vec![DrawCall::Rect(10.0, 10.0, 40.0, 20.0)]
+        );
+        assert_eq!(
+            path_of(circle.as_ref()),
+            vec![DrawCall::Arc(100.0, 100.0, 50.0, 0.0, 2.0 * PI)]
+        );
+        assert_eq!(
+            path_of(ellipse.as_ref()),
+            vec![DrawCall::Ellipse(200.0, 100.0, 50.0, 25.0, 0.0, 2.0 * PI)]
+        );
+    }
+
+    #[test]
+    fn closes_polygons_but_not_polylines() {
+        let points = [(0.0, 0.0), (40.0, 0.0), (20.0, 30.0)];
+        let polygon = drawn(Box::new(Polygon::new(true)), &points);
+        let polyline = drawn(Box::new(Polygon::new(false)), &points);
+
+        let mut expected: Vec<DrawCall> = points
+            .iter()
+            .map(|(x, y)| DrawCall::LineTo(*x, *y))
+            .collect();
+        assert_eq!(path_of(polyline.as_ref()), expected);
+        expected.push(DrawCall::ClosePath);
+        assert_eq!(path_of(polygon.as_ref()), expected);
+    }
+
+    #[test]
+    fn draws_curves_natively_up_to_cubic() {
+        let quadratic = drawn(
+            Box::new(Bezier::new()),
+            &[(0.0, 0.0), (50.0, 100.0), (100.0, 0.0)],
+        );
+        let cubic = drawn(
+            Box::new(Bezier::new()),
+            &[(0.0, 0.0), (30.0, 100.0), (70.0, 100.0), (100.0, 0.0)],
+        );
+        let quartic = drawn(
+            Box::new(Bezier::new()),
+            &[
+                (0.0, 0.0),
+                (25.0, 100.0),
+                (50.0, 0.0),
+                (75.0, 100.0),
+                (100.0, 0.0),
+            ],
+        );
+
+        assert_eq!(
+            path_of(quadratic.as_ref()),
+            vec![
+                DrawCall::MoveTo(0.0, 0.0),
+                DrawCall::QuadraticCurveTo((50.0, 100.0), (100.0, 0.0)),
+            ]
+        );
+        assert_eq!(
+            path_of(cubic.as_ref()),
+            vec![
+                DrawCall::MoveTo(0.0, 0.0),
+                DrawCall::BezierCurveTo((30.0, 100.0), (70.0, 100.0), (100.0, 0.0)),
+            ]
+        );
+        let path = path_of(quartic.as_ref());
+        assert!(path.len() > 4);
+        assert!(path.iter().all(|call| matches!(call, DrawCall::LineTo(..))));
+        assert_eq!(path.first(), Some(&DrawCall::LineTo(0.0, 0.0)));
+        assert_eq!(path.last(), Some(&DrawCall::LineTo(100.0, 0.0)));
+    }
+
+    #[test]
+    fn fills_before_stroking_filled_shapes() {
+        let mut rectangle = drawn(Box::new(Rectangle::new()), &[(0.0, 0.0), (10.0, 10.0)]);
+        rectangle.set_prop(FILLED_KEY, "true");
+        rectangle.set_prop(FILL_COLOR_KEY, "#00ff00");
+        let mut renderer = RecordingRenderer::new();
+
+        rectangle.draw(&mut renderer);
+
+        assert!(renderer
+            .calls
+            .contains(&DrawCall::SetFillColor("#00ff00".to_string())));
+        assert!(renderer
+            .calls
+            .ends_with(&[DrawCall::Fill, DrawCall::Stroke, DrawCall::Restore]));
+    }
+
+    #[test]
+    fn outlines_selected_shapes_in_red() {
+        let line = drawn(Box::new(Line::new()), &[(0.0, 0.0), (10.0, 0.0)]);
+        let mut renderer = RecordingRenderer::new();
+
+        line.draw_selected(&mut renderer);
+
+        assert!(renderer.calls.ends_with(&[
+            DrawCall::Save,
+            DrawCall::SetStrokeColor("red".to_string()),
+            DrawCall::SetLineWidth(3.0),
+            DrawCall::Stroke,
+            DrawCall::Restore,
+        ]));
+    }
+
+    #[test]
+    fn skips_shapes_that_are_not_drawable() {
+        let line = drawn(Box::new(Line::new()), &[(10.0, 20.0)]);
+        let mut renderer = RecordingRenderer::new();
+
+        line.draw(&mut renderer);
+        line.draw_outline(&mut renderer, "red", 1.0);
+
+        assert!(renderer.calls.is_empty());
+    }
+}
@@ -1,14 +1,27 @@
-use std::str::FromStr;
-
-use super::shape::{Shape, ShapeState, ShapeType, ORIGIN_X_KEY, ORIGIN_Y_KEY};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::ShapeData,
+    geometry::{bounds_of, distance_to_polyline},
+    renderer::Renderer,
+    shape::{Shape, ShapeState, ShapeType, ORIGIN_X_KEY, ORIGIN_Y_KEY},
+    style::Style,
+    svg,
+    transform::Transform,
+};
 
 pub const WIDTH_KEY: &str = "Width";
 pub const HEIGHT_KEY: &str = "Height";
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Rectangle {
     origin: Option<(f64, f64)>,
     width: f64,
     height: f64,
+    #[serde(default)]
+    transform: Transform,
+    #[serde(default)]
+    style: Style,
     state: ShapeState,
 }
 
@@ -18,21 +31,30 @@ impl Rectangle {
             origin: None,
             width: 0.0,
             height: 0.0,
+            transform: Transform::identity(),
+            style: Style::new(),
             state: ShapeState::New,
         }
     }
+
+    // corners on the canvas, starting at the origin
+    fn corners(&self) -> Option<[(f64, f64); 4]> {
+        let (ox, oy) = self.origin?;
+        let corner = |x: f64, y: f64| self.transform.apply((ox + x, oy + y));
+
+        Some([
+            corner(0.0, 0.0),
+            corner(self.width, 0.0),
+            corner(self.width, self.height),
+            corner(0.0, self.height),
+        ])
+    }
 }
 
 impl Shape for Rectangle {
-    fn draw(&self, ctx: &web_sys::CanvasRenderingContext2d) {
-        if !self.is_drawable() {
-            return;
-        }
-
+    fn build_path(&self, renderer: &mut dyn Renderer) {
         let (ox, oy) = self.origin.unwrap();
-        ctx.begin_path();
-        ctx.rect(ox, oy, self.width, self.height);
-        ctx.stroke();
+        renderer.rect(ox, oy, self.width, self.height);
     }
 
     fn add_point(&mut self, x: f64, y: f64) {
@@ -59,6 +81,7 @@ impl Shape for Rectangle {
 
     fn set_end(&mut self, x: f64, y: f64) {
         let (ox, oy) = self.origin.unwrap();
+        let (x, y) = self.to_local(x, y);
         self.width = x - ox;
         self.height = y - oy;
     }
@@ -70,8 +93,8 @@ impl Shape for Rectangle {
     fn get_prop_str(&self) -> String {
         let mut string = String::new();
         string += &format!("Type: {:?}\n", self.get_type());
-        if self.origin.is_some() {
-            string += format!("Origin: {:.0?}\n", self.origin.unwrap()).as_str();
+        if let Some(origin) = self.origin {
+            string += format!("Origin: {:.0?}\n", origin).as_str();
         }
         string += format!("Width: {}\n", self.width).as_str();
         string += format!("Height: {}\n", self.height).as_str();
@@ -81,15 +104,24 @@ impl Shape for Rectangle {
 
     fn is_drawable(&self) -> bool {
         (self.state == ShapeState::Complete || self.state == ShapeState::Drawing)
+            && self.origin.is_some()
             && self.width != 0.0
             && self.height != 0.0
     }
 
-    fn contains(&self, x: f64, y: f64) -> bool {
+    fn contains(&self, x: f64, y: f64, tolerance: f64) -> bool {
         if !self.is_drawable() {
             return false;
         }
 
+        if !self.style.is_filled() {
+            // only the outline counts, measured on the canvas
+            let corners = self.corners().unwrap();
+            return distance_to_polyline((x, y), &corners, true)
+                <= self.style.hit_tolerance(tolerance);
+        }
+
+        let (x, y) = self.to_local(x, y);
         let (ox, oy) = self.origin.unwrap();
         let (ex, ey) = (ox + self.width, oy + self.height);
 
@@ -105,12 +137,21 @@ impl Shape for Rectangle {
     }
 
     fn get_end(&self) -> Option<(f64, f64)> {
-        if self.origin.is_none() {
-            return None;
-        }
+        Some(self.corners()?[2])
+    }
 
-        let (ox, oy) = self.origin.unwrap();
-        Some((ox + self.width, oy + self.height))
+    fn get_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        bounds_of(&self.corners()?)
+    }
+
+    fn get_outline(&self) -> Vec<(f64, f64)> {
+        self.corners()
+            .map(|corners| corners.to_vec())
+            .unwrap_or_default()
+    }
+
+    fn is_closed(&self) -> bool {
+        true
     }
 
     fn get_props(&self) -> Vec<(String, String)> {
@@ -121,137 +162,120 @@ impl Shape for Rectangle {
 
         map.push((WIDTH_KEY.to_string(), self.width.to_string()));
         map.push((HEIGHT_KEY.to_string(), self.height.to_string()));
+        map.extend(self.style.get_props());
 
         return map;
     }
 
     fn set_prop(&mut self, key: &str, value: &str) {
+        if self.style.set_prop(key, value) {
+            return;
+        }
+
+        let value: f64 = match value.parse() {
+            Ok(value) => value,
+            Err(_) => return,
+        };
         match key {
             ORIGIN_X_KEY => {
                 if let Some((_, oy)) = self.origin {
-                    self.origin = Some((value.parse().unwrap(), oy));
+                    self.origin = Some((value, oy));
                 } else {
-                    self.origin = Some((value.parse().unwrap(), 0.0));
+                    self.origin = Some((value, 0.0));
                 }
             }
             ORIGIN_Y_KEY => {
                 if let Some((ox, _)) = self.origin {
-                    self.origin = Some((ox, value.parse().unwrap()));
+                    self.origin = Some((ox, value));
                 } else {
-                    self.origin = Some((0.0, value.parse().unwrap()));
+                    self.origin = Some((0.0, value));
                 }
             }
             WIDTH_KEY => {
-                self.width = value.parse().unwrap();
+                self.width = value;
             }
             HEIGHT_KEY => {
-                self.height = value.parse().unwrap();
+                self.height = value;
             }
             _ => {}
         }
     }
 
-    fn move_by(&mut self, x: f64, y: f64) {
-        if let Some((ox, oy)) = self.origin {
-            self.origin = Some((ox + x, oy + y));
-        }
-    }
-
-    fn resize(&mut self, change: (f64, f64), origin: (f64, f64)) {
-        if self.origin.is_none() {
-            return;
-        }
+    fn resize(&mut self, change: (f64, f64), origin: (f64, f64), tolerance: f64) {
+        let inverse = match (self.origin, self.transform.inverse()) {
+            (Some(_), Some(inverse)) => inverse,
+            _ => return,
+        };
 
+        // edges are detected in local coordinates, relative to the origin
         let (ox, oy) = self.origin.unwrap();
-        let (ex, ey) = (ox + self.width, oy + self.height);
-
-        let (x1, x2) = if ex > ox { (ox, ex) } else { (ex, ox) };
-
-        let (y1, y2) = if ey > oy { (oy, ey) } else { (ey, oy) };
-
-        let epsilon = 5.0;
-        let (x, y) = origin;
-        // detect which corner is being dragged
-        if (x - x1).abs() < epsilon && (y - y1).abs() < epsilon {
-            // top left
-            self.origin = Some((ox + change.0, oy + change.1));
-            self.width -= change.0;
-            self.height -= change.1;
-            return;
-        } else if (x - x2).abs() < epsilon && (y - y1).abs() < epsilon {
-            // top right
-            self.origin = Some((ox, oy + change.1));
-            self.width += change.0;
-            self.height -= change.1;
-            return;
-        } else if (x - x1).abs() < epsilon && (y - y2).abs() < epsilon {
-            // bottom left
-            self.origin = Some((ox + change.0, oy));
+        let (x, y) = inverse.apply(origin);
+        let (x, y) = (x - ox, y - oy);
+        let change = inverse.apply_vector(change);
+
+        // keep the tolerance the same on the canvas along both local axes
+        let axis_length = |v: (f64, f64)| {
+            let (x, y) = self.transform.apply_vector(v);
+            (x * x + y * y).sqrt()
+        };
+        let epsilon_x = tolerance / axis_length((1.0, 0.0));
+        let epsilon_y = tolerance / axis_length((0.0, 1.0));
+
+        // corners move two edges, edges move one; edges through the origin also shift it
+        let (mut shift_x, mut shift_y) = (0.0, 0.0);
+        if x.abs() < epsilon_x {
+            shift_x = change.0;
             self.width -= change.0;
-            self.height += change.1;
-            return;
-        } else if (x - x2).abs() < epsilon && (y - y2).abs() < epsilon {
-            // bottom right
+        } else if (x - self.width).abs() < epsilon_x {
             self.width += change.0;
-            self.height += change.1;
-            return;
         }
-
-        // detect which edge is being dragged
-        if (x - x1).abs() < epsilon {
-            // left
-            self.origin = Some((ox + change.0, oy));
-            self.width -= change.0;
-            return;
-        } else if (x - x2).abs() < epsilon {
-            // right
-            self.width += change.0;
-            return;
-        } else if (y - y1).abs() < epsilon {
-            // top
-            self.origin = Some((ox, oy + change.1));
+        if y.abs() < epsilon_y {
+            shift_y = change.1;
             self.height -= change.1;
-            return;
-        } else if (y - y2).abs() < epsilon {
-            // bottom
+        } else if (y - self.height).abs() < epsilon_y {
             self.height += change.1;
-            return;
         }
+
+        self.origin = Some((ox + shift_x, oy + shift_y));
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn get_style(&self) -> Style {
+        self.style.clone()
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.style = style;
     }
 
     fn set_state(&mut self, state: ShapeState) {
         self.state = state;
     }
 
-    fn get_json(&self) -> String {
-        let mut map = serde_json::Map::new();
-        map.insert("type".to_string(), "rectangle".to_string().into());
-        map.insert("state".to_string(), self.state.to_string().into());
-        if let Some((ox, oy)) = self.origin {
-            map.insert("origin_x".to_string(), ox.into());
-            map.insert("origin_y".to_string(), oy.into());
-        }
-        map.insert("width".to_string(), self.width.into());
-        map.insert("height".to_string(), self.height.into());
-
-        return serde_json::to_string(&map).unwrap();
+    fn to_data(&self) -> ShapeData {
+        ShapeData::Rectangle(self.clone())
     }
 
-    fn from_json(&mut self, json: &str) {
-        let map: serde_json::Map<String, serde_json::Value> = serde_json::from_str(json).unwrap();
-        if let Some(serde_json::Value::String(state)) = map.get("state") {
-            self.state = ShapeState::from_str(state).unwrap();
-        }
-        if let Some(serde_json::Value::Number(ox)) = map.get("origin_x") {
-            if let Some(serde_json::Value::Number(oy)) = map.get("origin_y") {
-                self.origin = Some((ox.as_f64().unwrap(), oy.as_f64().unwrap()));
-            }
-        }
-        if let Some(serde_json::Value::Number(width)) = map.get("width") {
-            self.width = width.as_f64().unwrap();
-        }
-        if let Some(serde_json::Value::Number(height)) = map.get("height") {
-            self.height = height.as_f64().unwrap();
-        }
+    fn get_svg(&self) -> String {
+        let (ox, oy) = self.origin.unwrap_or((0.0, 0.0));
+        // SVG doesn't allow negative sizes
+        svg::element(
+            "rect",
+            &[
+                ("x", svg::number(ox.min(ox + self.width))),
+                ("y", svg::number(oy.min(oy + self.height))),
+                ("width", svg::number(self.width.abs())),
+                ("height", svg::number(self.height.abs())),
+            ],
+            &self.style,
+            &self.transform,
+        )
     }
 }
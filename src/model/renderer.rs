@@ -0,0 +1,151 @@
+use wasm_bindgen::JsValue;
+
+use super::{geometry::FillRule, transform::Transform};
+
+/// Drawing surface the shapes render into, modelled after the canvas 2D context.
+pub trait Renderer {
+    fn save(&mut self);
+    fn restore(&mut self);
+    /// Composes `transform` after the current transform of the surface.
+    fn transform(&mut self, transform: &Transform);
+
+    fn begin_path(&mut self);
+    fn move_to(&mut self, x: f64, y: f64);
+    fn line_to(&mut self, x: f64, y: f64);
+    fn close_path(&mut self);
+    fn rect(&mut self, x: f64, y: f64, width: f64, height: f64);
+    /// Circular arc around (x, y), angles in radians.
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start: f64, end: f64);
+    /// Axis-aligned elliptical arc around (x, y), angles in radians.
+    fn ellipse(&mut self, x: f64, y: f64, radius_x: f64, radius_y: f64, start: f64, end: f64);
+    fn quadratic_curve_to(&mut self, control: (f64, f64), end: (f64, f64));
+    fn bezier_curve_to(&mut self, first: (f64, f64), second: (f64, f64), end: (f64, f64));
+
+    fn stroke(&mut self);
+    fn fill(&mut self);
+    /// Fills the current path, deciding what's inside by `rule`; `fill` uses the non-zero rule.
+    fn fill_with_rule(&mut self, rule: FillRule);
+    fn stroke_rect(&mut self, x: f64, y: f64, width: f64, height: f64);
+
+    fn set_stroke_color(&mut self, color: &str);
+    fn set_fill_color(&mut self, color: &str);
+    fn set_line_width(&mut self, width: f64);
+    fn set_line_dash(&mut self, dash: &[f64]);
+    fn set_opacity(&mut self, opacity: f64);
+}
+
+/// Renders onto an HTML canvas.
+pub struct CanvasRenderer {
+    ctx: web_sys::CanvasRenderingContext2d,
+}
+
+impl CanvasRenderer {
+    pub fn new(ctx: web_sys::CanvasRenderingContext2d) -> Self {
+        Self { ctx }
+    }
+}
+
+impl Renderer for CanvasRenderer {
+    fn save(&mut self) {
+        self.ctx.save();
+    }
+
+    fn restore(&mut self) {
+        self.ctx.restore();
+    }
+
+    fn transform(&mut self, transform: &Transform) {
+        let (a, b, c, d, e, f) = transform.to_canvas();
+        self.ctx
+            .transform(a, b, c, d, e, f)
+            .expect("Couldn't set transform!");
+    }
+
+    fn begin_path(&mut self) {
+        self.ctx.begin_path();
+    }
+
+    fn move_to(&mut self, x: f64, y: f64) {
+        self.ctx.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f64, y: f64) {
+        self.ctx.line_to(x, y);
+    }
+
+    fn close_path(&mut self) {
+        self.ctx.close_path();
+    }
+
+    fn rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.ctx.rect(x, y, width, height);
+    }
+
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start: f64, end: f64) {
+        self.ctx
+            .arc(x, y, radius, start, end)
+            .expect("Couldn't arc!");
+    }
+
+    fn ellipse(&mut self, x: f64, y: f64, radius_x: f64, radius_y: f64, start: f64, end: f64) {
+        self.ctx
+            .ellipse(x, y, radius_x, radius_y, 0.0, start, end)
+            .expect("Couldn't ellipse!");
+    }
+
+    fn quadratic_curve_to(&mut self, control: (f64, f64), end: (f64, f64)) {
+        self.ctx
+            .quadratic_curve_to(control.0, control.1, end.0, end.1);
+    }
+
+    fn bezier_curve_to(&mut self, first: (f64, f64), second: (f64, f64), end: (f64, f64)) {
+        self.ctx
+            .bezier_curve_to(first.0, first.1, second.0, second.1, end.0, end.1);
+    }
+
+    fn stroke(&mut self) {
+        self.ctx.stroke();
+    }
+
+    fn fill(&mut self) {
+        self.ctx.fill();
+    }
+
+    fn fill_with_rule(&mut self, rule: FillRule) {
+        let rule = match rule {
+            FillRule::EvenOdd => web_sys::CanvasWindingRule::Evenodd,
+            FillRule::NonZero => web_sys::CanvasWindingRule::Nonzero,
+        };
+        self.ctx.fill_with_canvas_winding_rule(rule);
+    }
+
+    fn stroke_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.ctx.stroke_rect(x, y, width, height);
+    }
+
+    fn set_stroke_color(&mut self, color: &str) {
+        self.ctx.set_stroke_style(&JsValue::from_str(color));
+    }
+
+    fn set_fill_color(&mut self, color: &str) {
+        self.ctx.set_fill_style(&JsValue::from_str(color));
+    }
+
+    fn set_line_width(&mut self, width: f64) {
+        self.ctx.set_line_width(width);
+    }
+
+    fn set_line_dash(&mut self, dash: &[f64]) {
+        let dash: js_sys::Array = dash
+            .iter()
+            .map(|length| JsValue::from_f64(*length))
+            .collect();
+        self.ctx
+            .set_line_dash(&dash)
+            .expect("Couldn't set line dash!");
+    }
+
+    fn set_opacity(&mut self, opacity: f64) {
+        self.ctx.set_global_alpha(opacity);
+    }
+}
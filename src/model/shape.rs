@@ -1,18 +1,62 @@
 use core::fmt;
-use std::str::FromStr;
+use std::ops::Range;
 
-use wasm_bindgen::JsValue;
+use serde::{Deserialize, Serialize};
 
-use super::{circle::Circle, line::Line, rectangle::Rectangle};
+use super::{
+    bezier::Bezier,
+    circle::Circle,
+    document::{DocumentError, LayerData, ShapeData},
+    ellipse::Ellipse,
+    fill::Fill,
+    geometry::{bounds_of, distance, polyline_intersects_rect, FillRule},
+    group::Group,
+    layer::{layer_of_json, on_layer, Layer},
+    line::Line,
+    path::Path,
+    polygon::Polygon,
+    raster::{Framebuffer, Rgba},
+    rectangle::Rectangle,
+    renderer::Renderer,
+    style::Style,
+    transform::Transform,
+};
 
 pub const ORIGIN_X_KEY: &str = "Origin x";
 pub const ORIGIN_Y_KEY: &str = "Origin y";
 
+/// How close to an outline or a handle the pointer hits it, in screen pixels.
+pub const HIT_TOLERANCE: f64 = 5.0;
+
+/// Property key of a single coordinate of an indexed point, e.g. "Vertex 3 x".
+pub fn point_key(prefix: &str, idx: usize, axis: char) -> String {
+    format!("{} {} {}", prefix, idx + 1, axis)
+}
+
+/// Reverse of `point_key`, returns the zero-based index and the axis.
+pub fn parse_point_key(prefix: &str, key: &str) -> Option<(usize, char)> {
+    let rest = key.strip_prefix(prefix)?.strip_prefix(' ')?;
+    let (idx, axis) = rest.split_once(' ')?;
+    let idx: usize = idx.parse().ok()?;
+    if idx == 0 || (axis != "x" && axis != "y") {
+        return None;
+    }
+
+    Some((idx - 1, axis.chars().next()?))
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ShapeType {
     Line,
     Rectangle,
     Circle,
+    Polygon,
+    Polyline,
+    Bezier,
+    Ellipse,
+    Fill,
+    Path,
+    Group,
 }
 
 impl fmt::Display for ShapeType {
@@ -21,11 +65,18 @@ impl fmt::Display for ShapeType {
             ShapeType::Line => write!(f, "Line"),
             ShapeType::Rectangle => write!(f, "Rectangle"),
             ShapeType::Circle => write!(f, "Circle"),
+            ShapeType::Polygon => write!(f, "Polygon"),
+            ShapeType::Polyline => write!(f, "Polyline"),
+            ShapeType::Bezier => write!(f, "Bezier"),
+            ShapeType::Ellipse => write!(f, "Ellipse"),
+            ShapeType::Fill => write!(f, "Fill"),
+            ShapeType::Path => write!(f, "Path"),
+            ShapeType::Group => write!(f, "Group"),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ShapeState {
     New,
     Drawing,
@@ -42,33 +93,82 @@ impl fmt::Display for ShapeState {
     }
 }
 
-impl FromStr for ShapeState {
-    type Err = JsValue;
+pub trait Shape {
+    /// Adds the outline of the shape, in its local coordinates, to the current path.
+    fn build_path(&self, renderer: &mut dyn Renderer);
+    /// Starts a new path with the outline of the shape placed on the canvas.
+    fn trace_path(&self, renderer: &mut dyn Renderer) {
+        renderer.save();
+        // composed with the surface's transform, e.g. the scale of an export
+        renderer.transform(&self.get_transform());
+        renderer.begin_path();
+        self.build_path(renderer);
+        // the path keeps the transform, restoring before stroking keeps the line width intact
+        renderer.restore();
+    }
+    fn draw(&self, renderer: &mut dyn Renderer) {
+        if !self.is_drawable() {
+            return;
+        }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "New" => Ok(ShapeState::New),
-            "Drawing" => Ok(ShapeState::Drawing),
-            "Complete" => Ok(ShapeState::Complete),
-            _ => Err(JsValue::from_str("Invalid shape state")),
+        let style = self.get_style();
+        self.trace_path(renderer);
+        renderer.save();
+        style.apply(renderer);
+        if style.is_filled() {
+            renderer.fill();
         }
+        renderer.stroke();
+        renderer.restore();
     }
-}
+    /// Strokes the outline of the shape on top of it without touching its style.
+    fn draw_outline(&self, renderer: &mut dyn Renderer, color: &str, width: f64) {
+        if !self.is_drawable() {
+            return;
+        }
 
-pub trait Shape {
-    fn draw(&self, ctx: &web_sys::CanvasRenderingContext2d);
-    fn draw_highlighted(&self, ctx: &web_sys::CanvasRenderingContext2d) {
-        ctx.set_line_width(3.0);
-        self.draw(ctx);
-        ctx.set_line_width(1.0);
+        self.trace_path(renderer);
+        renderer.save();
+        renderer.set_stroke_color(color);
+        renderer.set_line_width(width);
+        renderer.stroke();
+        renderer.restore();
     }
+    fn draw_highlighted(&self, renderer: &mut dyn Renderer) {
+        let style = self.get_style();
+        self.draw(renderer);
+        self.draw_outline(renderer, &style.stroke_color, style.stroke_width + 2.0);
+    }
+
+    fn draw_selected(&self, renderer: &mut dyn Renderer) {
+        self.draw(renderer);
+        self.draw_outline(renderer, "red", self.get_style().stroke_width + 2.0);
+    }
+    /// Draws the handles the selected shape is edited by, the same size on screen at any zoom
+    /// with `pixel` the length of a screen pixel.
+    fn draw_handles(&self, _renderer: &mut dyn Renderer, _pixel: f64) {}
+
+    /// Draws the outline pixel by pixel with Bresenham lines, or Wu's lines if `anti_aliased`,
+    /// ignoring the stroke width. Closed filled shapes are scanline filled first.
+    fn rasterize(&self, framebuffer: &mut Framebuffer, color: Rgba, anti_aliased: bool) {
+        if !self.is_drawable() {
+            return;
+        }
 
-    fn draw_selected(&self, ctx: &web_sys::CanvasRenderingContext2d) {
-        ctx.set_line_width(3.0);
-        ctx.set_stroke_style(&JsValue::from_str("red"));
-        self.draw(ctx);
-        ctx.set_line_width(1.0);
-        ctx.set_stroke_style(&JsValue::from_str("black"));
+        let outline = self.get_outline();
+        if let (Some(fill), true) = (self.get_style().fill_rgba(), self.is_closed()) {
+            framebuffer.fill_polygon(&outline, fill);
+        }
+        if anti_aliased {
+            framebuffer.draw_polyline_wu(&outline, self.is_closed(), color);
+        } else {
+            framebuffer.draw_polyline(&outline, self.is_closed(), color);
+        }
+    }
+
+    /// Rasterizes the shape marked as selected in `color`, like `draw_selected`.
+    fn rasterize_selected(&self, framebuffer: &mut Framebuffer, color: Rgba, anti_aliased: bool) {
+        self.rasterize(framebuffer, color, anti_aliased);
     }
 
     fn get_prop_str(&self) -> String;
@@ -76,44 +176,142 @@ pub trait Shape {
     fn get_state(&self) -> ShapeState;
     fn get_origin(&self) -> Option<(f64, f64)>;
     fn get_end(&self) -> Option<(f64, f64)>;
+    /// Axis-aligned bounds as (min x, min y, max x, max y).
+    fn get_bounds(&self) -> Option<(f64, f64, f64, f64)>;
+    /// Outline on the canvas flattened into points, see `is_closed`.
+    fn get_outline(&self) -> Vec<(f64, f64)>;
+    /// Whether the outline connects its last point back to the first.
+    fn is_closed(&self) -> bool;
+    /// Closed outlines on the canvas enclosing the shape, with curves flattened to within
+    /// `tolerance` pixels. Empty for open shapes.
+    fn get_contours(&self, _tolerance: f64) -> Vec<Vec<(f64, f64)>> {
+        if !self.is_drawable() || !self.is_closed() {
+            return Vec::new();
+        }
+
+        vec![self.get_outline()]
+    }
+    /// Rule deciding which parts of the contours are inside the shape.
+    fn get_fill_rule(&self) -> FillRule {
+        FillRule::NonZero
+    }
     fn get_props(&self) -> Vec<(String, String)>;
+    fn get_transform(&self) -> Transform;
+    fn get_style(&self) -> Style;
+    /// Maps a point from canvas coordinates into the shape's local coordinates.
+    fn to_local(&self, x: f64, y: f64) -> (f64, f64) {
+        match self.get_transform().inverse() {
+            Some(inverse) => inverse.apply((x, y)),
+            // a degenerate transform collapses the shape, nothing maps onto it
+            None => (f64::NAN, f64::NAN),
+        }
+    }
     fn is_drawable(&self) -> bool;
-    fn contains(&self, x: f64, y: f64) -> bool;
+    /// Whether the point hits the shape, for outlines within `tolerance` on the canvas.
+    fn contains(&self, x: f64, y: f64, tolerance: f64) -> bool;
 
     fn add_point(&mut self, x: f64, y: f64);
+    /// Ends drawing of shapes that take an arbitrary number of points.
+    fn finish(&mut self) {}
     fn set_end(&mut self, x: f64, y: f64);
     fn set_prop(&mut self, key: &str, value: &str);
     fn init_from_points(&mut self, origin: (f64, f64), end: (f64, f64));
-    fn move_by(&mut self, x: f64, y: f64);
-    fn resize(&mut self, change: (f64, f64), origin: (f64, f64));
+    /// Drags the handle within `tolerance` of `origin` on the canvas by `change`.
+    fn resize(&mut self, change: (f64, f64), origin: (f64, f64), tolerance: f64);
+    fn set_transform(&mut self, transform: Transform);
+    fn set_style(&mut self, style: Style);
+    /// Composes `transform` after the current transform of the shape.
+    fn apply_transform(&mut self, transform: Transform) {
+        self.set_transform(transform.multiply(&self.get_transform()));
+    }
+    fn move_by(&mut self, x: f64, y: f64) {
+        self.apply_transform(Transform::translation(x, y));
+    }
+    /// Rotates the shape by `angle` radians around `pivot`.
+    fn rotate(&mut self, angle: f64, pivot: (f64, f64)) {
+        self.apply_transform(Transform::around(Transform::rotation(angle), pivot));
+    }
+    /// Scales the shape by `factor` along the x and y axes, keeping `pivot` in place.
+    fn scale(&mut self, factor: (f64, f64), pivot: (f64, f64)) {
+        self.apply_transform(Transform::around(
+            Transform::scaling(factor.0, factor.1),
+            pivot,
+        ));
+    }
+    /// Skews the shape by `angle` radians along the x and y axes, keeping `pivot` in place.
+    fn skew(&mut self, angle: (f64, f64), pivot: (f64, f64)) {
+        self.apply_transform(Transform::around(Transform::skew(angle.0, angle.1), pivot));
+    }
     fn set_state(&mut self, state: ShapeState);
+    fn as_group(&self) -> Option<&Group> {
+        None
+    }
+    fn as_group_mut(&mut self) -> Option<&mut Group> {
+        None
+    }
 
-    fn get_json(&self) -> String;
-    fn from_json(&mut self, json: &str);
+    fn to_data(&self) -> ShapeData;
+    fn get_json(&self) -> String {
+        serde_json::to_string(&self.to_data()).expect("Couldn't serialize the shape!")
+    }
+    /// The shape as a single SVG element.
+    fn get_svg(&self) -> String;
+}
+
+/// Where the selected shapes go in the drawing order of their layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZOrder {
+    Front,
+    Forward,
+    Backward,
+    Back,
 }
 
 pub struct ShapeStorage {
+    // grouped by layer from the bottom one up, so their order is the drawing order
     shapes: Vec<Box<dyn Shape>>,
+    layers: Vec<Layer>,
+    // how many of the shapes each layer holds
+    layer_lengths: Vec<usize>,
+    // where new shapes go
+    active_layer: usize,
     current_shape_idx: usize,
     highlighted_shape_idx: Option<usize>,
-    selected_shape_idx: Option<usize>,
+    // the last selected shape is the primary one, e.g. for resizing
+    selected_shape_idxs: Vec<usize>,
+    // on the canvas, the hit tolerance in screen pixels at the current zoom
+    hit_tolerance: f64,
+    // where the last click picked a shape, picking there again goes to the one below
+    last_pick: Option<(f64, f64)>,
 }
 
 impl ShapeStorage {
     pub fn new() -> Self {
         Self {
             shapes: Vec::new(),
+            layers: vec![Layer::numbered(1)],
+            layer_lengths: vec![0],
+            active_layer: 0,
             current_shape_idx: 0,
             highlighted_shape_idx: None,
-            selected_shape_idx: None,
+            selected_shape_idxs: Vec::new(),
+            hit_tolerance: HIT_TOLERANCE,
+            last_pick: None,
         }
     }
 
+    /// Sets how close on the canvas the pointer has to be to hit a shape.
+    pub fn set_hit_tolerance(&mut self, tolerance: f64) {
+        self.hit_tolerance = tolerance;
+    }
+
+    pub fn get_hit_tolerance(&self) -> f64 {
+        self.hit_tolerance
+    }
+
     pub fn get_or_create_shape(&mut self, shape_type: ShapeType) -> &mut dyn Shape {
         if self.shapes.is_empty() {
-            self.shapes.push(ShapeStorage::create_helper(shape_type));
-
-            return self.shapes[self.current_shape_idx].as_mut();
+            return self.create_shape(shape_type);
         }
 
         if self.shapes[self.current_shape_idx].get_type() != shape_type {
@@ -123,7 +321,7 @@ impl ShapeStorage {
         match self.shapes[self.current_shape_idx].get_state() {
             ShapeState::Complete => self.create_shape(shape_type),
             _ => {
-                self.selected_shape_idx = Some(self.current_shape_idx);
+                self.selected_shape_idxs = vec![self.current_shape_idx];
                 return self.shapes[self.current_shape_idx].as_mut();
             }
         }
@@ -137,39 +335,462 @@ impl ShapeStorage {
         return Some(self.shapes[self.current_shape_idx].as_mut());
     }
 
+    pub fn get_current_idx(&self) -> Option<usize> {
+        if self.shapes.is_empty() {
+            return None;
+        }
+
+        return Some(self.current_shape_idx);
+    }
+
     pub fn get_shapes(&self) -> impl Iterator<Item = &Box<dyn Shape>> {
         return self.shapes.iter();
     }
 
+    /// The shapes on visible layers, in drawing order.
+    pub fn get_visible_shapes(&self) -> impl Iterator<Item = &Box<dyn Shape>> {
+        self.shapes
+            .iter()
+            .enumerate()
+            .filter(move |(idx, _)| self.is_shape_visible(*idx))
+            .map(|(_, shape)| shape)
+    }
+
+    pub fn is_shape_visible(&self, idx: usize) -> bool {
+        self.layer_of(idx)
+            .is_some_and(|layer| self.layers[layer].visible)
+    }
+
+    pub fn get_shape(&self, idx: usize) -> Option<&dyn Shape> {
+        self.shapes.get(idx).map(|shape| shape.as_ref())
+    }
+
+    pub fn get_shape_mut(&mut self, idx: usize) -> Option<&mut dyn Shape> {
+        match self.shapes.get_mut(idx) {
+            Some(shape) => Some(shape.as_mut()),
+            None => None,
+        }
+    }
+
+    pub fn shape_count(&self) -> usize {
+        self.shapes.len()
+    }
+
+    /// The JSON of the shape at `idx` tagged with its layer, as history commands keep it.
+    pub fn shape_json(&self, idx: usize) -> Option<String> {
+        let layer = self.layer_of(idx)?;
+        Some(on_layer(&self.shapes[idx].get_json(), layer))
+    }
+
+    /// Where a shape added to the active layer goes.
+    pub fn next_idx(&self) -> usize {
+        self.layer_range(self.active_layer).end
+    }
+
+    pub fn get_layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn get_active_layer(&self) -> usize {
+        self.active_layer
+    }
+
+    pub fn set_active_layer(&mut self, layer: usize) {
+        if layer < self.layers.len() {
+            self.active_layer = layer;
+        }
+    }
+
+    /// The layer the shape at `idx` is on.
+    pub fn layer_of(&self, idx: usize) -> Option<usize> {
+        let mut end = 0;
+        for (layer, length) in self.layer_lengths.iter().enumerate() {
+            end += length;
+            if idx < end {
+                return Some(layer);
+            }
+        }
+
+        None
+    }
+
+    /// Adds an empty layer above the active one and makes it the active one.
+    pub fn add_layer(&mut self) -> usize {
+        let layer = (1..)
+            .map(Layer::numbered)
+            .find(|layer| self.layers.iter().all(|other| other.name != layer.name))
+            .unwrap();
+        self.active_layer += 1;
+        self.layers.insert(self.active_layer, layer);
+        self.layer_lengths.insert(self.active_layer, 0);
+
+        self.active_layer
+    }
+
+    /// Removes a layer with its shapes, the last layer is always kept.
+    pub fn remove_layer(&mut self, layer: usize) {
+        if layer >= self.layers.len() || self.layers.len() == 1 {
+            return;
+        }
+
+        let range = self.layer_range(layer);
+        self.shapes.drain(range.clone());
+        self.layers.remove(layer);
+        self.layer_lengths.remove(layer);
+        self.selected_shape_idxs
+            .retain(|selected| !range.contains(selected));
+        for selected in self.selected_shape_idxs.iter_mut() {
+            if *selected >= range.end {
+                *selected -= range.len();
+            }
+        }
+        self.highlighted_shape_idx = None;
+        self.current_shape_idx = self.shapes.len().saturating_sub(1);
+        if self.active_layer > layer || self.active_layer == self.layers.len() {
+            self.active_layer -= 1;
+        }
+    }
+
+    pub fn rename_layer(&mut self, layer: usize, name: &str) {
+        if let Some(layer) = self.layers.get_mut(layer) {
+            layer.name = name.to_string();
+        }
+    }
+
+    /// Shows or hides a layer, the shapes of a hidden one are deselected.
+    pub fn set_layer_visible(&mut self, layer: usize, visible: bool) {
+        if layer < self.layers.len() {
+            self.layers[layer].visible = visible;
+            self.deselect_layer(layer);
+        }
+    }
+
+    /// Locks or unlocks a layer, the shapes of a locked one are deselected.
+    pub fn set_layer_locked(&mut self, layer: usize, locked: bool) {
+        if layer < self.layers.len() {
+            self.layers[layer].locked = locked;
+            self.deselect_layer(layer);
+        }
+    }
+
+    /// Moves a layer with its shapes to another place in the stack.
+    pub fn move_layer(&mut self, from: usize, to: usize) {
+        if from >= self.layers.len() || to >= self.layers.len() || from == to {
+            return;
+        }
+
+        let mut layer_order: Vec<usize> = (0..self.layers.len()).collect();
+        let moved = layer_order.remove(from);
+        layer_order.insert(to, moved);
+
+        let order = layer_order
+            .iter()
+            .flat_map(|layer| self.layer_range(*layer))
+            .collect();
+        self.layers = layer_order
+            .iter()
+            .map(|layer| self.layers[*layer].clone())
+            .collect();
+        self.layer_lengths = layer_order
+            .iter()
+            .map(|layer| self.layer_lengths[*layer])
+            .collect();
+        self.active_layer = layer_order
+            .iter()
+            .position(|layer| *layer == self.active_layer)
+            .unwrap();
+        self.reorder(order);
+    }
+
+    /// Moves shapes in the drawing order, each within its layer. Shapes stepping forward or
+    /// backward pass one unselected shape.
+    pub fn arrange(&mut self, idxs: &[usize], z_order: ZOrder) {
+        let mut order = Vec::new();
+        for layer in 0..self.layers.len() {
+            let mut layer_order: Vec<usize> = self.layer_range(layer).collect();
+            let (moved, others): (Vec<usize>, Vec<usize>) =
+                layer_order.iter().partition(|idx| idxs.contains(idx));
+            match z_order {
+                ZOrder::Front => layer_order = [others, moved].concat(),
+                ZOrder::Back => layer_order = [moved, others].concat(),
+                // from the top down, so neighbouring selected shapes move together
+                ZOrder::Forward => {
+                    for position in (0..layer_order.len().saturating_sub(1)).rev() {
+                        let (idx, above) = (layer_order[position], layer_order[position + 1]);
+                        if idxs.contains(&idx) && !idxs.contains(&above) {
+                            layer_order.swap(position, position + 1);
+                        }
+                    }
+                }
+                ZOrder::Backward => {
+                    for position in 1..layer_order.len() {
+                        let (idx, below) = (layer_order[position], layer_order[position - 1]);
+                        if idxs.contains(&idx) && !idxs.contains(&below) {
+                            layer_order.swap(position, position - 1);
+                        }
+                    }
+                }
+            }
+            order.extend(layer_order);
+        }
+        self.reorder(order);
+    }
+
+    /// Moves shapes on top of another layer, keeping their order.
+    pub fn move_to_layer(&mut self, idxs: &[usize], layer: usize) {
+        if layer >= self.layers.len() {
+            return;
+        }
+
+        let mut moved: Vec<usize> = idxs
+            .iter()
+            .copied()
+            .filter(|idx| *idx < self.shapes.len())
+            .collect();
+        moved.sort_unstable();
+        moved.dedup();
+
+        let mut order = Vec::new();
+        let mut lengths = Vec::new();
+        for other in 0..self.layers.len() {
+            let start = order.len();
+            order.extend(self.layer_range(other).filter(|idx| !moved.contains(idx)));
+            if other == layer {
+                order.extend(moved.iter().copied());
+            }
+            lengths.push(order.len() - start);
+        }
+        self.layer_lengths = lengths;
+        self.reorder(order);
+    }
+
+    /// Puts the shape described by `json` in place of the shape at `idx`.
+    pub fn replace_shape(&mut self, idx: usize, json: &str) {
+        if idx >= self.shapes.len() {
+            return;
+        }
+
+        match ShapeStorage::shape_from_json(json) {
+            Ok(shape) => self.shapes[idx] = shape,
+            Err(error) => log::error!("Couldn't restore shape {}: {}", idx, error),
+        }
+    }
+
+    pub fn insert_shape(&mut self, idx: usize, json: &str) {
+        let shape = match ShapeStorage::shape_from_json(json) {
+            Ok(shape) => shape,
+            Err(error) => {
+                log::error!("Couldn't restore shape {}: {}", idx, error);
+                return;
+            }
+        };
+        // shapes without a layer go to the active one
+        let layer = layer_of_json(json)
+            .filter(|layer| *layer < self.layers.len())
+            .unwrap_or(self.active_layer);
+        let idx = self.insert_on_layer(layer, idx, shape);
+        self.selected_shape_idxs.push(idx);
+    }
+
+    pub fn remove_shape(&mut self, idx: usize) {
+        let layer = match self.layer_of(idx) {
+            Some(layer) => layer,
+            None => return,
+        };
+
+        self.shapes.remove(idx);
+        self.layer_lengths[layer] -= 1;
+        self.current_shape_idx = self.shapes.len().saturating_sub(1);
+        self.highlighted_shape_idx = None;
+        self.selected_shape_idxs.retain(|selected| *selected != idx);
+        for selected in self.selected_shape_idxs.iter_mut() {
+            if *selected > idx {
+                *selected -= 1;
+            }
+        }
+    }
+
+    /// Replaces every layer and shape with the ones serialized by `serialize_to_json`.
+    pub fn replace_all(&mut self, json: &str) {
+        match serde_json::from_str(json) {
+            Ok(layers) => self.replace_layers(layers),
+            Err(error) => log::error!("Couldn't restore the shapes: {}", error),
+        }
+    }
+
+    pub fn replace_layers(&mut self, layers: Vec<LayerData>) {
+        self.clear();
+        self.layers.clear();
+        self.layer_lengths.clear();
+        self.add_layers(layers);
+        if self.layers.is_empty() {
+            self.layers.push(Layer::numbered(1));
+            self.layer_lengths.push(0);
+        }
+        self.active_layer = self.active_layer.min(self.layers.len() - 1);
+        self.current_shape_idx = self.shapes.len().saturating_sub(1);
+    }
+
+    /// Clears the selection, leaving any entered group.
+    pub fn deselect(&mut self) {
+        self.leave_groups();
+        self.selected_shape_idxs.clear();
+    }
+
+    /// Removes every shape, the layers stay.
     pub fn clear(&mut self) {
         self.current_shape_idx = 0;
         self.highlighted_shape_idx = None;
-        self.selected_shape_idx = None;
+        self.selected_shape_idxs.clear();
         self.shapes.clear();
+        self.layer_lengths.iter_mut().for_each(|length| *length = 0);
+    }
+
+    /// Whether the shape at `idx` can be hit on the canvas, i.e. its layer is visible and unlocked.
+    pub fn is_editable(&self, idx: usize) -> bool {
+        self.layer_of(idx)
+            .is_some_and(|layer| self.layers[layer].is_editable())
+    }
+
+    /// Indices of the shapes at the point, the topmost first.
+    pub fn hits_at(&self, x: f64, y: f64) -> impl Iterator<Item = usize> + '_ {
+        (0..self.shapes.len()).rev().filter(move |i| {
+            self.is_editable(*i) && self.shapes[*i].contains(x, y, self.hit_tolerance)
+        })
+    }
+
+    pub fn get_shape_at(&self, x: f64, y: f64) -> Option<&dyn Shape> {
+        self.hits_at(x, y).next().map(|i| self.shapes[i].as_ref())
     }
 
     pub fn intersect_and_highlight(&mut self, x: f64, y: f64) -> Option<&dyn Shape> {
-        for (i, shape) in self.shapes.iter().enumerate() {
-            if shape.contains(x, y) {
-                self.highlighted_shape_idx = Some(i);
-                return Some(shape.as_ref());
+        let hit = self.hits_at(x, y).next();
+        self.highlighted_shape_idx = hit;
+
+        return self.get_highlighted();
+    }
+
+    /// Selects the topmost shape at the point. Picking the same spot again selects the shape
+    /// below the selected one, cycling through the overlapping shapes. Inside an entered group,
+    /// the child at the point is picked instead.
+    pub fn intersect_and_select(&mut self, x: f64, y: f64) -> Option<&dyn Shape> {
+        let tolerance = self.hit_tolerance;
+        if let [selected] = self.selected_shape_idxs[..] {
+            if let Some(group) = self.shapes[selected].as_group_mut() {
+                if group.is_entered() && group.pick(x, y, tolerance) {
+                    return Some(self.shapes[selected].as_ref());
+                }
             }
         }
+        self.leave_groups();
 
-        self.highlighted_shape_idx = None;
-        return None;
+        let hits: Vec<usize> = self.hits_at(x, y).collect();
+        let again = self
+            .last_pick
+            .is_some_and(|point| distance(point, (x, y)) <= self.hit_tolerance);
+        self.last_pick = Some((x, y));
+
+        let below = match (again, self.selected_shape_idxs.as_slice()) {
+            (true, [selected]) => hits
+                .iter()
+                .position(|hit| hit == selected)
+                .map(|position| hits[(position + 1) % hits.len()]),
+            _ => None,
+        };
+        match below.or(hits.first().copied()) {
+            Some(i) => {
+                self.selected_shape_idxs = vec![i];
+                return Some(self.shapes[i].as_ref());
+            }
+            None => {
+                self.selected_shape_idxs.clear();
+                return None;
+            }
+        }
     }
 
-    pub fn intersect_and_select(&mut self, x: f64, y: f64) -> Option<&dyn Shape> {
+    /// Adds the shape at the point to the selection, or removes it if it's already selected.
+    pub fn intersect_and_toggle(&mut self, x: f64, y: f64) {
+        self.leave_groups();
+        let hit = self.hits_at(x, y).next();
+        if let Some(i) = hit {
+            match self
+                .selected_shape_idxs
+                .iter()
+                .position(|selected| *selected == i)
+            {
+                Some(position) => {
+                    self.selected_shape_idxs.remove(position);
+                }
+                None => self.selected_shape_idxs.push(i),
+            }
+        }
+    }
+
+    /// Selects the shapes lying fully inside the rectangle, or touching it if `intersecting`.
+    pub fn select_in_rect(&mut self, rect: (f64, f64, f64, f64), intersecting: bool, extend: bool) {
+        let (x1, y1, x2, y2) = rect;
+        self.leave_groups();
+        if !extend {
+            self.selected_shape_idxs.clear();
+        }
+
         for (i, shape) in self.shapes.iter().enumerate() {
-            if shape.contains(x, y) {
-                self.selected_shape_idx = Some(i);
-                return Some(shape.as_ref());
+            if !shape.is_drawable() || !self.is_editable(i) || self.selected_shape_idxs.contains(&i)
+            {
+                continue;
+            }
+
+            let outline = shape.get_outline();
+            let hit = if intersecting {
+                // a rectangle lying inside a filled shape doesn't cross its outline
+                polyline_intersects_rect(&outline, shape.is_closed(), rect)
+                    || shape.contains(x1, y1, self.hit_tolerance)
+            } else {
+                match bounds_of(&outline) {
+                    Some((minx, miny, maxx, maxy)) => {
+                        minx >= x1 && miny >= y1 && maxx <= x2 && maxy <= y2
+                    }
+                    None => false,
+                }
+            };
+            if hit {
+                self.selected_shape_idxs.push(i);
             }
         }
+    }
 
-        self.selected_shape_idx = None;
-        return None;
+    /// Selects the group at the point and enters it to edit the child there on its own, or goes
+    /// deeper into the entered group.
+    pub fn enter_group_at(&mut self, x: f64, y: f64) -> bool {
+        let tolerance = self.hit_tolerance;
+        let hit = match self.hits_at(x, y).next() {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let entered = match self.shapes[hit].as_group_mut() {
+            Some(group) => group.enter(x, y, tolerance),
+            None => false,
+        };
+        if entered {
+            for (i, shape) in self.shapes.iter_mut().enumerate() {
+                if let (Some(group), true) = (shape.as_group_mut(), i != hit) {
+                    group.leave();
+                }
+            }
+            self.selected_shape_idxs = vec![hit];
+        }
+
+        entered
+    }
+
+    /// Goes back to editing every group as a whole.
+    pub fn leave_groups(&mut self) {
+        for shape in self.shapes.iter_mut() {
+            if let Some(group) = shape.as_group_mut() {
+                group.leave();
+            }
+        }
     }
 
     pub fn get_highlighted(&self) -> Option<&dyn Shape> {
@@ -181,15 +802,39 @@ impl ShapeStorage {
     }
 
     pub fn get_selected(&self) -> Option<&dyn Shape> {
-        if let Some(idx) = self.selected_shape_idx {
+        if let Some(idx) = self.get_selected_idx() {
             return Some(self.shapes[idx].as_ref());
         }
 
         return None;
     }
 
+    pub fn get_selected_idx(&self) -> Option<usize> {
+        self.selected_shape_idxs.last().copied()
+    }
+
+    pub fn get_selected_idxs(&self) -> &[usize] {
+        &self.selected_shape_idxs
+    }
+
+    pub fn get_selection(&self) -> impl Iterator<Item = &dyn Shape> {
+        self.selected_shape_idxs
+            .iter()
+            .map(|idx| self.shapes[*idx].as_ref())
+    }
+
+    /// Bounds of every selected shape together.
+    pub fn get_selection_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        let corners: Vec<(f64, f64)> = self
+            .get_selection()
+            .filter_map(|shape| shape.get_bounds())
+            .flat_map(|(x1, y1, x2, y2)| [(x1, y1), (x2, y2)])
+            .collect();
+        bounds_of(&corners)
+    }
+
     pub fn get_selected_mut(&mut self) -> Option<&mut dyn Shape> {
-        if let Some(idx) = self.selected_shape_idx {
+        if let Some(idx) = self.get_selected_idx() {
             return Some(self.shapes[idx].as_mut());
         }
 
@@ -197,56 +842,133 @@ impl ShapeStorage {
     }
 
     pub fn new_shape(&mut self, shape_type: ShapeType) {
-        self.shapes.push(ShapeStorage::create_helper(shape_type));
-        self.current_shape_idx = self.shapes.len() - 1;
-        self.selected_shape_idx = Some(self.current_shape_idx);
-        self.highlighted_shape_idx = None;
+        let idx = self.push_shape(ShapeStorage::create_helper(shape_type));
+        self.selected_shape_idxs = vec![idx];
     }
 
     pub fn submit_shape(&mut self) {
-        if let Some(idx) = self.selected_shape_idx {
+        if let Some(idx) = self.get_selected_idx() {
             let shape = self.shapes[idx].as_mut();
             if shape.get_end().is_some() && shape.get_origin().is_some() {
-                shape.set_state(ShapeState::Complete);            
+                shape.set_state(ShapeState::Complete);
             }
         }
     }
 
+    pub fn finish_shape(&mut self) {
+        if let Some(shape) = self.get_current_mut() {
+            if shape.get_state() == ShapeState::Drawing {
+                shape.finish();
+            }
+        }
+    }
+
+    /// Every layer with its shapes, as they're saved.
+    pub fn get_layer_data(&self) -> Vec<LayerData> {
+        self.layers
+            .iter()
+            .enumerate()
+            .map(|(idx, layer)| LayerData {
+                layer: layer.clone(),
+                shapes: self.shapes[self.layer_range(idx)]
+                    .iter()
+                    .map(|shape| shape.to_data())
+                    .collect(),
+            })
+            .collect()
+    }
+
     pub fn serialize_to_json(&self) -> String {
-        let mut json = String::new();
-        json.push_str("[");
-        for shape in self.shapes.iter() {
-            json.push_str(&shape.get_json());
-            json.push_str(",");
-        }
-        json.pop();
-        json.push_str("]");
-
-        return json;
-    }
-
-    pub fn deserialize_from_json(&mut self, json: &str) {
-        let json_vec: Vec<serde_json::Value> = serde_json::from_str(json).unwrap();
-        for shape_json in json_vec.iter() {
-            let shape_type = shape_json["type"].as_str().unwrap();
-            let shape_type = match shape_type {
-                "line" => ShapeType::Line,
-                "rectangle" => ShapeType::Rectangle,
-                "circle" => ShapeType::Circle,
-                _ => panic!("Unknown shape type"),
-            };
+        serde_json::to_string(&self.get_layer_data()).expect("Couldn't serialize the shapes!")
+    }
 
-            let mut shape = ShapeStorage::create_helper(shape_type);
-            shape.from_json(&shape_json.to_string());
-            self.shapes.push(shape);
+    /// Adds the shapes on top of the active layer.
+    pub fn add_shapes(&mut self, shapes: Vec<Box<dyn Shape>>) {
+        for shape in shapes {
+            self.push_shape(shape);
+        }
+    }
+
+    /// Adds the layers on top of the others.
+    pub fn add_layers(&mut self, layers: Vec<LayerData>) {
+        for data in layers {
+            self.layers.push(data.layer);
+            self.layer_lengths.push(data.shapes.len());
+            self.shapes
+                .extend(data.shapes.into_iter().map(ShapeData::into_shape));
         }
     }
 
+    fn shape_from_json(json: &str) -> Result<Box<dyn Shape>, DocumentError> {
+        ShapeData::from_json(json).map(ShapeData::into_shape)
+    }
+
     fn create_shape(&mut self, shape_type: ShapeType) -> &mut dyn Shape {
-        self.current_shape_idx += 1;
-        self.shapes.push(ShapeStorage::create_helper(shape_type));
+        // the current shape isn't necessarily the last one after an undo
+        let idx = self.push_shape(ShapeStorage::create_helper(shape_type));
+
+        return self.shapes[idx].as_mut();
+    }
+
+    // adds the shape on top of the active layer and returns its index
+    fn push_shape(&mut self, shape: Box<dyn Shape>) -> usize {
+        let idx = self.next_idx();
+        self.insert_on_layer(self.active_layer, idx, shape)
+    }
+
+    // inserts the shape at `idx`, kept within the layer, and returns where it went
+    fn insert_on_layer(&mut self, layer: usize, idx: usize, shape: Box<dyn Shape>) -> usize {
+        let range = self.layer_range(layer);
+        let idx = idx.clamp(range.start, range.end);
+        self.shapes.insert(idx, shape);
+        self.layer_lengths[layer] += 1;
+        self.current_shape_idx = idx;
+        for selected in self.selected_shape_idxs.iter_mut() {
+            if *selected >= idx {
+                *selected += 1;
+            }
+        }
+        self.highlighted_shape_idx = None;
 
-        return self.shapes[self.current_shape_idx].as_mut();
+        idx
+    }
+
+    fn layer_range(&self, layer: usize) -> Range<usize> {
+        let start = self.layer_lengths[..layer].iter().sum();
+        start..start + self.layer_lengths[layer]
+    }
+
+    fn deselect_layer(&mut self, layer: usize) {
+        let range = self.layer_range(layer);
+        self.selected_shape_idxs
+            .retain(|selected| !range.contains(selected));
+        if self
+            .highlighted_shape_idx
+            .is_some_and(|idx| range.contains(&idx))
+        {
+            self.highlighted_shape_idx = None;
+        }
+    }
+
+    // puts the shapes in the order of their old indices in `order`, the selection follows them
+    fn reorder(&mut self, order: Vec<usize>) {
+        let mut new_idxs = vec![0; order.len()];
+        for (new_idx, old_idx) in order.iter().enumerate() {
+            new_idxs[*old_idx] = new_idx;
+        }
+
+        let mut shapes: Vec<Option<Box<dyn Shape>>> = self.shapes.drain(..).map(Some).collect();
+        self.shapes = order
+            .iter()
+            .map(|idx| shapes[*idx].take().unwrap())
+            .collect();
+        for selected in self.selected_shape_idxs.iter_mut() {
+            *selected = new_idxs[*selected];
+        }
+        self.highlighted_shape_idx = self.highlighted_shape_idx.map(|idx| new_idxs[idx]);
+        if let Some(idx) = new_idxs.get(self.current_shape_idx) {
+            self.current_shape_idx = *idx;
+        }
     }
 
     fn create_helper(shape_type: ShapeType) -> Box<dyn Shape> {
@@ -254,6 +976,112 @@ impl ShapeStorage {
             ShapeType::Line => Box::new(Line::new()),
             ShapeType::Rectangle => Box::new(Rectangle::new()),
             ShapeType::Circle => Box::new(Circle::new()),
+            ShapeType::Polygon => Box::new(Polygon::new(true)),
+            ShapeType::Polyline => Box::new(Polygon::new(false)),
+            ShapeType::Bezier => Box::new(Bezier::new()),
+            ShapeType::Ellipse => Box::new(Ellipse::new()),
+            ShapeType::Fill => Box::new(Fill::new()),
+            ShapeType::Path => Box::new(Path::new()),
+            ShapeType::Group => Box::new(Group::new(Vec::new())),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::style::FILL_COLOR_KEY;
+
+    fn square(x: f64) -> Box<dyn Shape> {
+        let mut rectangle = Rectangle::new();
+        rectangle.add_point(x, 0.0);
+        rectangle.add_point(x + 100.0, 100.0);
+        rectangle.set_prop(FILL_COLOR_KEY, "#000000");
+        Box::new(rectangle)
+    }
+
+    // the shapes by where they start, bottom first
+    fn order(storage: &ShapeStorage) -> Vec<f64> {
+        storage
+            .get_shapes()
+            .map(|shape| shape.get_origin().unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn ignores_numbers_that_dont_parse() {
+        let style_keys: Vec<String> = Style::new()
+            .get_props()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        let shape_types = [
+            ShapeType::Line,
+            ShapeType::Rectangle,
+            ShapeType::Circle,
+            ShapeType::Ellipse,
+        ];
+        for shape_type in shape_types {
+            let mut shape = ShapeStorage::create_helper(shape_type);
+            shape.add_point(10.0, 20.0);
+            shape.add_point(30.0, 50.0);
+            let json = shape.get_json();
+            // a cleared input sends nothing, a half-typed one a partial number
+            for (key, _) in shape.get_props() {
+                if !style_keys.contains(&key) {
+                    shape.set_prop(&key, "");
+                    shape.set_prop(&key, "-");
+                }
+            }
+            assert_eq!(shape.get_json(), json, "{}", shape_type);
+        }
+    }
+
+    #[test]
+    fn arranges_the_selection_within_its_layer() {
+        let mut storage = ShapeStorage::new();
+        storage.add_shapes(vec![square(0.0), square(1.0), square(2.0), square(3.0)]);
+        storage.add_layer();
+        storage.add_shapes(vec![square(4.0)]);
+
+        storage.arrange(&[0], ZOrder::Front);
+        assert_eq!(order(&storage), [1.0, 2.0, 3.0, 0.0, 4.0]);
+        storage.arrange(&[3], ZOrder::Back);
+        assert_eq!(order(&storage), [0.0, 1.0, 2.0, 3.0, 4.0]);
+        // neighbours step together, the topmost ones can't go further
+        storage.arrange(&[1, 2], ZOrder::Forward);
+        assert_eq!(order(&storage), [0.0, 3.0, 1.0, 2.0, 4.0]);
+        storage.arrange(&[2, 3], ZOrder::Forward);
+        assert_eq!(order(&storage), [0.0, 3.0, 1.0, 2.0, 4.0]);
+        storage.arrange(&[1], ZOrder::Forward);
+        assert_eq!(order(&storage), [0.0, 1.0, 3.0, 2.0, 4.0]);
+        storage.arrange(&[0, 2, 4], ZOrder::Backward);
+        assert_eq!(order(&storage), [0.0, 3.0, 1.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn picks_the_topmost_shape_and_cycles_through_the_ones_below() {
+        let mut storage = ShapeStorage::new();
+        storage.add_shapes(vec![square(0.0), square(50.0), square(200.0)]);
+
+        let picks: Vec<Option<usize>> = (0..4)
+            .map(|_| {
+                storage.intersect_and_select(75.0, 50.0);
+                storage.get_selected_idx()
+            })
+            .collect();
+        assert_eq!(picks, [Some(1), Some(0), Some(1), Some(0)]);
+
+        // somewhere else starts from the top again
+        storage.intersect_and_select(250.0, 50.0);
+        storage.intersect_and_select(75.0, 50.0);
+        assert_eq!(storage.get_selected_idx(), Some(1));
+        storage.intersect_and_highlight(75.0, 50.0);
+        assert_eq!(
+            storage
+                .get_shape_at(75.0, 50.0)
+                .map(|shape| shape.get_origin()),
+            Some(Some((50.0, 0.0)))
+        );
+    }
+}
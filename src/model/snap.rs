@@ -0,0 +1,327 @@
+//! Snapping of the pointer to the grid and to notable points of the shapes.
+
+use super::{
+    clip::Segment,
+    geometry::{bounds_of, distance, segment_intersection},
+    shape::{Shape, ShapeType},
+};
+
+/// What a snapped point lies on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapKind {
+    Grid,
+    Endpoint,
+    Midpoint,
+    Center,
+    Intersection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapTarget {
+    pub point: (f64, f64),
+    pub kind: SnapKind,
+}
+
+/// Which targets snapping goes for, and the spacing of the grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapSettings {
+    pub grid: bool,
+    pub endpoints: bool,
+    pub midpoints: bool,
+    pub centers: bool,
+    pub intersections: bool,
+    pub spacing: f64,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        Self {
+            grid: false,
+            endpoints: false,
+            midpoints: false,
+            centers: false,
+            intersections: false,
+            spacing: 20.0,
+        }
+    }
+}
+
+impl SnapSettings {
+    pub fn is_enabled(&self, kind: SnapKind) -> bool {
+        match kind {
+            SnapKind::Grid => self.grid,
+            SnapKind::Endpoint => self.endpoints,
+            SnapKind::Midpoint => self.midpoints,
+            SnapKind::Center => self.centers,
+            SnapKind::Intersection => self.intersections,
+        }
+    }
+
+    pub fn toggle(&mut self, kind: SnapKind) {
+        let flag = match kind {
+            SnapKind::Grid => &mut self.grid,
+            SnapKind::Endpoint => &mut self.endpoints,
+            SnapKind::Midpoint => &mut self.midpoints,
+            SnapKind::Center => &mut self.centers,
+            SnapKind::Intersection => &mut self.intersections,
+        };
+        *flag = !*flag;
+    }
+
+    /// The nearest grid intersection.
+    pub fn grid_point(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let spacing = self.spacing;
+        (
+            (x / spacing).round() * spacing,
+            (y / spacing).round() * spacing,
+        )
+    }
+}
+
+/// The target `point` snaps to. Points of the shapes within `radius` win over the grid, the
+/// closest of them first; the grid catches everything else.
+pub fn snap<'a>(
+    point: (f64, f64),
+    shapes: impl Iterator<Item = &'a dyn Shape>,
+    settings: &SnapSettings,
+    radius: f64,
+) -> Option<SnapTarget> {
+    let mut ends = Vec::new();
+    let mut centers = Vec::new();
+    let mut edges = Vec::new();
+    for shape in shapes.filter(|shape| shape.is_drawable()) {
+        if settings.endpoints {
+            ends.extend(endpoints(shape));
+        }
+        if settings.centers {
+            centers.extend(center(shape));
+        }
+        edges.extend(straight_edges(shape));
+    }
+
+    // only edges passing close by can end or cross near the point
+    edges.retain(|(a, b)| {
+        let (x1, y1, x2, y2) = bounds_of(&[*a, *b]).unwrap();
+        point.0 >= x1 - radius
+            && point.0 <= x2 + radius
+            && point.1 >= y1 - radius
+            && point.1 <= y2 + radius
+    });
+
+    // in order of preference where they coincide, the first of equally close ones wins
+    let mut candidates = Vec::new();
+    if settings.intersections {
+        for (i, (a, b)) in edges.iter().enumerate() {
+            for (c, d) in edges[i + 1..].iter() {
+                // edges meeting at a vertex are endpoints, not intersections
+                if a == c || a == d || b == c || b == d {
+                    continue;
+                }
+                if let Some(point) = segment_intersection(*a, *b, *c, *d) {
+                    candidates.push(SnapTarget {
+                        point,
+                        kind: SnapKind::Intersection,
+                    });
+                }
+            }
+        }
+    }
+    let midpoints = match settings.midpoints {
+        true => edges
+            .iter()
+            .map(|(a, b)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0))
+            .collect(),
+        false => Vec::new(),
+    };
+    for (points, kind) in [
+        (ends, SnapKind::Endpoint),
+        (midpoints, SnapKind::Midpoint),
+        (centers, SnapKind::Center),
+    ] {
+        candidates.extend(points.into_iter().map(|point| SnapTarget { point, kind }));
+    }
+
+    let nearest = candidates
+        .into_iter()
+        .map(|target| (distance(target.point, point), target))
+        .filter(|(dist, _)| *dist <= radius)
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, target)| target);
+    match nearest {
+        Some(target) => Some(target),
+        None if settings.grid && settings.spacing > 0.0 => Some(SnapTarget {
+            point: settings.grid_point(point),
+            kind: SnapKind::Grid,
+        }),
+        None => None,
+    }
+}
+
+// the straight edges on the canvas, curves have none
+fn straight_edges(shape: &dyn Shape) -> Vec<Segment> {
+    let polylines = match shape.get_type() {
+        ShapeType::Circle | ShapeType::Ellipse | ShapeType::Bezier | ShapeType::Fill => {
+            return Vec::new()
+        }
+        ShapeType::Path => shape.get_contours(1.0),
+        ShapeType::Group => {
+            return shape
+                .as_group()
+                .unwrap()
+                .children()
+                .flat_map(straight_edges)
+                .collect()
+        }
+        _ => vec![shape.get_outline()],
+    };
+
+    let mut edges = Vec::new();
+    for points in polylines {
+        edges.extend(points.windows(2).map(|pair| (pair[0], pair[1])));
+        if shape.is_closed() && points.len() > 2 {
+            edges.push((points[points.len() - 1], points[0]));
+        }
+    }
+
+    edges
+}
+
+fn endpoints(shape: &dyn Shape) -> Vec<(f64, f64)> {
+    match shape.get_type() {
+        ShapeType::Circle | ShapeType::Ellipse | ShapeType::Fill => Vec::new(),
+        // the ends of the curve, the control points in between aren't on it
+        ShapeType::Bezier => {
+            let outline = shape.get_outline();
+            outline
+                .first()
+                .into_iter()
+                .chain(outline.last())
+                .copied()
+                .collect()
+        }
+        ShapeType::Group => shape
+            .as_group()
+            .unwrap()
+            .children()
+            .flat_map(endpoints)
+            .collect(),
+        _ => straight_edges(shape)
+            .into_iter()
+            .flat_map(|(a, b)| [a, b])
+            .collect(),
+    }
+}
+
+fn center(shape: &dyn Shape) -> Option<(f64, f64)> {
+    match shape.get_type() {
+        ShapeType::Circle | ShapeType::Ellipse => {
+            Some(shape.get_transform().apply(shape.get_origin()?))
+        }
+        ShapeType::Fill => None,
+        _ => {
+            let (x1, y1, x2, y2) = bounds_of(&shape.get_outline())?;
+            Some(((x1 + x2) / 2.0, (y1 + y2) / 2.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{circle::Circle, line::Line, rectangle::Rectangle, test_util::drawn};
+
+    fn all() -> SnapSettings {
+        SnapSettings {
+            grid: true,
+            endpoints: true,
+            midpoints: true,
+            centers: true,
+            intersections: true,
+            spacing: 20.0,
+        }
+    }
+
+    fn snap_to(
+        point: (f64, f64),
+        shapes: &[Box<dyn Shape>],
+        settings: &SnapSettings,
+    ) -> Option<SnapTarget> {
+        snap(
+            point,
+            shapes.iter().map(|shape| shape.as_ref()),
+            settings,
+            5.0,
+        )
+    }
+
+    #[test]
+    fn snaps_to_the_nearest_grid_intersection() {
+        let settings = all();
+
+        assert_eq!(settings.grid_point((29.0, 31.0)), (20.0, 40.0));
+        assert_eq!(settings.grid_point((-9.0, 11.0)), (-0.0, 20.0));
+        assert_eq!(
+            snap_to((47.0, 52.0), &[], &settings),
+            Some(SnapTarget {
+                point: (40.0, 60.0),
+                kind: SnapKind::Grid
+            })
+        );
+        assert_eq!(snap_to((47.0, 52.0), &[], &SnapSettings::default()), None);
+    }
+
+    #[test]
+    fn snaps_to_points_of_shapes_before_the_grid() {
+        let shapes = [
+            drawn(Box::new(Line::new()), &[(3.0, 3.0), (103.0, 103.0)]),
+            drawn(Box::new(Line::new()), &[(3.0, 103.0), (103.0, 3.0)]),
+            drawn(
+                Box::new(Rectangle::new()),
+                &[(200.0, 200.0), (250.0, 230.0)],
+            ),
+            drawn(Box::new(Circle::new()), &[(300.0, 300.0), (340.0, 300.0)]),
+        ];
+        let cases = [
+            ((5.0, 1.0), (3.0, 3.0), SnapKind::Endpoint),
+            ((54.0, 55.0), (53.0, 53.0), SnapKind::Intersection),
+            ((226.0, 198.0), (225.0, 200.0), SnapKind::Midpoint),
+            ((224.0, 214.0), (225.0, 215.0), SnapKind::Center),
+            ((249.0, 232.0), (250.0, 230.0), SnapKind::Endpoint),
+            ((302.0, 297.0), (300.0, 300.0), SnapKind::Center),
+            // nothing close by but the grid
+            ((338.0, 298.0), (340.0, 300.0), SnapKind::Grid),
+        ];
+
+        for (point, expected, kind) in cases {
+            let target = snap_to(point, &shapes, &all()).unwrap();
+            assert_eq!((target.point, target.kind), (expected, kind), "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn skips_disabled_targets() {
+        let shapes = [
+            drawn(Box::new(Line::new()), &[(3.0, 3.0), (103.0, 103.0)]),
+            drawn(Box::new(Line::new()), &[(3.0, 103.0), (103.0, 3.0)]),
+        ];
+        let mut settings = all();
+        settings.toggle(SnapKind::Intersection);
+        settings.toggle(SnapKind::Grid);
+
+        let target = snap_to((54.0, 53.0), &shapes, &settings).unwrap();
+        assert_eq!(
+            (target.point, target.kind),
+            ((53.0, 53.0), SnapKind::Midpoint)
+        );
+        // the center of a line is its midpoint
+        settings.toggle(SnapKind::Midpoint);
+        assert!(!settings.is_enabled(SnapKind::Midpoint));
+        let target = snap_to((54.0, 53.0), &shapes, &settings).unwrap();
+        assert_eq!(
+            (target.point, target.kind),
+            ((53.0, 53.0), SnapKind::Center)
+        );
+        settings.toggle(SnapKind::Center);
+        assert_eq!(snap_to((54.0, 53.0), &shapes, &settings), None);
+    }
+}
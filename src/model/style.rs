@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    path::EVEN_ODD_KEY,
+    raster::{parse_color, Rgba},
+    renderer::Renderer,
+};
+
+pub const STROKE_COLOR_KEY: &str = "Stroke color";
+pub const STROKE_WIDTH_KEY: &str = "Stroke width";
+pub const FILLED_KEY: &str = "Filled";
+pub const FILL_COLOR_KEY: &str = "Fill color";
+pub const OPACITY_KEY: &str = "Opacity";
+
+const DEFAULT_FILL_COLOR: &str = "#ffffff";
+
+/// How a shape is painted, colors are CSS hex strings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    pub stroke_color: String,
+    pub fill_color: Option<String>,
+    pub stroke_width: f64,
+    pub opacity: f64,
+}
+
+impl Style {
+    pub fn new() -> Self {
+        Self {
+            stroke_color: "#000000".to_string(),
+            fill_color: None,
+            stroke_width: 1.0,
+            opacity: 1.0,
+        }
+    }
+
+    pub fn is_filled(&self) -> bool {
+        self.fill_color.is_some()
+    }
+
+    /// Distance from the outline that still counts as a hit, `tolerance` beyond the stroke.
+    pub fn hit_tolerance(&self, tolerance: f64) -> f64 {
+        tolerance + self.stroke_width / 2.0
+    }
+
+    pub fn apply(&self, renderer: &mut dyn Renderer) {
+        renderer.set_stroke_color(&self.stroke_color);
+        if let Some(fill_color) = &self.fill_color {
+            renderer.set_fill_color(fill_color);
+        }
+        renderer.set_line_width(self.stroke_width);
+        renderer.set_opacity(self.opacity);
+    }
+
+    /// Stroke color with the opacity as alpha, for the software rasterizer.
+    pub fn stroke_rgba(&self) -> Rgba {
+        parse_color(&self.stroke_color, self.opacity)
+    }
+
+    /// Fill color with the opacity as alpha, if the shape is filled.
+    pub fn fill_rgba(&self) -> Option<Rgba> {
+        self.fill_color
+            .as_ref()
+            .map(|fill_color| parse_color(fill_color, self.opacity))
+    }
+
+    pub fn get_props(&self) -> Vec<(String, String)> {
+        vec![
+            (STROKE_COLOR_KEY.to_string(), self.stroke_color.clone()),
+            (STROKE_WIDTH_KEY.to_string(), self.stroke_width.to_string()),
+            (FILLED_KEY.to_string(), self.is_filled().to_string()),
+            (
+                FILL_COLOR_KEY.to_string(),
+                self.fill_color
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_FILL_COLOR.to_string()),
+            ),
+            (OPACITY_KEY.to_string(), self.opacity.to_string()),
+        ]
+    }
+
+    /// Returns false when `key` isn't a style property.
+    pub fn set_prop(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            STROKE_COLOR_KEY => self.stroke_color = value.to_string(),
+            STROKE_WIDTH_KEY => {
+                if let Ok(width) = value.parse::<f64>() {
+                    self.stroke_width = width.max(0.0);
+                }
+            }
+            FILLED_KEY => {
+                self.fill_color = match value {
+                    "true" => self
+                        .fill_color
+                        .clone()
+                        .or_else(|| Some(DEFAULT_FILL_COLOR.to_string())),
+                    _ => None,
+                }
+            }
+            FILL_COLOR_KEY => self.fill_color = Some(value.to_string()),
+            OPACITY_KEY => {
+                if let Ok(opacity) = value.parse::<f64>() {
+                    self.opacity = opacity.clamp(0.0, 1.0);
+                }
+            }
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style::new()
+    }
+}
+
+/// Input element type used to edit the property `key` in the side panel.
+pub fn prop_input_type(key: &str) -> &'static str {
+    match key {
+        STROKE_COLOR_KEY | FILL_COLOR_KEY => "color",
+        FILLED_KEY | EVEN_ODD_KEY => "checkbox",
+        _ => "number",
+    }
+}
+
+/// Whether the side panel can set `value` as the property `key`. It can't while a number is
+/// still being typed, or when the input was left empty because the selected shapes differ.
+pub fn is_valid_prop_value(key: &str, value: &str) -> bool {
+    match prop_input_type(key) {
+        "color" => {
+            value.len() == 7
+                && value.starts_with('#')
+                && value[1..].chars().all(|c| c.is_ascii_hexdigit())
+        }
+        "checkbox" => value == "true" || value == "false",
+        _ => value.parse::<f64>().is_ok_and(f64::is_finite),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{circle::RADIUS_KEY, polygon::VERTEX_COUNT_KEY};
+
+    #[test]
+    fn accepts_only_complete_values() {
+        assert!(is_valid_prop_value(RADIUS_KEY, "12.5"));
+        assert!(is_valid_prop_value(VERTEX_COUNT_KEY, "6"));
+        assert!(is_valid_prop_value(STROKE_COLOR_KEY, "#00ff7F"));
+        assert!(is_valid_prop_value(FILLED_KEY, "false"));
+
+        for value in ["", "-", "1e", "NaN", "inf"] {
+            assert!(!is_valid_prop_value(RADIUS_KEY, value), "{:?}", value);
+        }
+        assert!(!is_valid_prop_value(STROKE_WIDTH_KEY, ""));
+        assert!(!is_valid_prop_value(STROKE_COLOR_KEY, ""));
+        assert!(!is_valid_prop_value(FILL_COLOR_KEY, "#ff00"));
+        assert!(!is_valid_prop_value(FILL_COLOR_KEY, "red"));
+        assert!(!is_valid_prop_value(EVEN_ODD_KEY, ""));
+    }
+}
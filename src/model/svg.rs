@@ -0,0 +1,203 @@
+use super::{shape::Shape, style::Style, transform::Transform};
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+
+/// Writes every drawable shape into a standalone SVG document of the given size.
+pub fn export_svg<'a>(
+    shapes: impl Iterator<Item = &'a dyn Shape>,
+    width: f64,
+    height: f64,
+) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"{}\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+        SVG_NAMESPACE, width, height, width, height
+    );
+    for shape in shapes.filter(|shape| shape.is_drawable()) {
+        svg += "  ";
+        svg += &shape.get_svg();
+        svg.push('\n');
+    }
+    svg += "</svg>\n";
+
+    svg
+}
+
+/// Builds a self-closing SVG element carrying the shape's geometry, style and transform.
+pub fn element(
+    name: &str,
+    geometry: &[(&str, String)],
+    style: &Style,
+    transform: &Transform,
+) -> String {
+    let mut attributes: Vec<(&str, String)> = geometry.to_vec();
+    attributes.push(("stroke", style.stroke_color.clone()));
+    attributes.push(("stroke-width", number(style.stroke_width)));
+    attributes.push((
+        "fill",
+        style
+            .fill_color
+            .clone()
+            .unwrap_or_else(|| "none".to_string()),
+    ));
+    if style.opacity < 1.0 {
+        attributes.push(("opacity", number(style.opacity)));
+    }
+    if *transform != Transform::identity() {
+        let (a, b, c, d, e, f) = transform.to_canvas();
+        let values: Vec<String> = [a, b, c, d, e, f]
+            .iter()
+            .map(|value| number(*value))
+            .collect();
+        attributes.push(("transform", format!("matrix({})", values.join(" "))));
+    }
+
+    let mut element = format!("<{}", name);
+    for (key, value) in attributes.iter() {
+        element += &format!(" {}=\"{}\"", key, escape(value));
+    }
+    element += "/>";
+
+    element
+}
+
+/// Space separated "x,y" pairs, as used by `points` and path data.
+pub fn points(points: &[(f64, f64)]) -> String {
+    points
+        .iter()
+        .map(|(x, y)| format!("{},{}", number(*x), number(*y)))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Formats a coordinate without float noise such as `6.123233995736766e-17`.
+pub fn number(value: f64) -> String {
+    let rounded = (value * 1e4).round() / 1e4;
+    // avoids writing "-0"
+    if rounded == 0.0 {
+        return "0".to_string();
+    }
+
+    rounded.to_string()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::model::{
+        bezier::Bezier,
+        circle::Circle,
+        ellipse::Ellipse,
+        line::Line,
+        polygon::Polygon,
+        rectangle::Rectangle,
+        style::{FILLED_KEY, FILL_COLOR_KEY, OPACITY_KEY, STROKE_COLOR_KEY, STROKE_WIDTH_KEY},
+        test_util::drawn,
+    };
+
+    fn export(shapes: &[Box<dyn Shape>]) -> String {
+        export_svg(shapes.iter().map(|shape| shape.as_ref()), 800.0, 600.0)
+    }
+
+    #[test]
+    fn exports_basic_shapes() {
+        let shapes = vec![
+            drawn(Box::new(Line::new()), &[(10.0, 20.0), (110.0, 70.0)]),
+            // dragged from the bottom right corner to the top left one
+            drawn(
+                Box::new(Rectangle::new()),
+                &[(300.0, 200.0), (200.0, 150.0)],
+            ),
+            drawn(Box::new(Circle::new()), &[(400.0, 300.0), (430.0, 340.0)]),
+            drawn(Box::new(Ellipse::new()), &[(600.0, 100.0), (650.0, 125.0)]),
+        ];
+
+        assert_eq!(
+            export(&shapes),
+            include_str!("../../tests/golden/basic_shapes.svg")
+        );
+    }
+
+    #[test]
+    fn exports_polygons_and_curves() {
+        let shapes = vec![
+            drawn(
+                Box::new(Polygon::new(true)),
+                &[(10.0, 10.0), (60.0, 10.0), (35.0, 50.0)],
+            ),
+            drawn(
+                Box::new(Polygon::new(false)),
+                &[(100.0, 10.0), (150.0, 60.0), (200.0, 10.0)],
+            ),
+            drawn(
+                Box::new(Bezier::new()),
+                &[(10.0, 200.0), (60.0, 100.0), (110.0, 200.0)],
+            ),
+            drawn(
+                Box::new(Bezier::new()),
+                &[
+                    (200.0, 200.0),
+                    (250.0, 100.0),
+                    (300.0, 300.0),
+                    (350.0, 200.0),
+                ],
+            ),
+        ];
+
+        assert_eq!(
+            export(&shapes),
+            include_str!("../../tests/golden/polygons_and_curves.svg")
+        );
+    }
+
+    #[test]
+    fn exports_style_and_transform() {
+        let mut rectangle = drawn(Box::new(Rectangle::new()), &[(0.0, 0.0), (40.0, 20.0)]);
+        rectangle.set_prop(STROKE_COLOR_KEY, "#ff0000");
+        rectangle.set_prop(STROKE_WIDTH_KEY, "3");
+        rectangle.set_prop(FILLED_KEY, "true");
+        rectangle.set_prop(FILL_COLOR_KEY, "#00ff00");
+        rectangle.set_prop(OPACITY_KEY, "0.5");
+        rectangle.move_by(100.0, 50.0);
+        let mut line = drawn(Box::new(Line::new()), &[(0.0, 0.0), (10.0, 0.0)]);
+        line.rotate(PI / 2.0, (0.0, 0.0));
+
+        assert_eq!(
+            export(&[rectangle, line]),
+            include_str!("../../tests/golden/style_and_transform.svg")
+        );
+    }
+
+    #[test]
+    fn skips_shapes_that_are_not_drawable() {
+        let shapes = vec![
+            drawn(Box::new(Line::new()), &[(10.0, 20.0)]),
+            Box::new(Circle::new()) as Box<dyn Shape>,
+        ];
+
+        assert_eq!(
+            export(&shapes),
+            include_str!("../../tests/golden/empty.svg")
+        );
+    }
+
+    #[test]
+    fn escapes_attribute_values() {
+        let mut style = Style::new();
+        style.stroke_color = "\"><script>".to_string();
+
+        assert_eq!(
+            element("line", &[], &style, &Transform::identity()),
+            "<line stroke=\"&quot;&gt;&lt;script&gt;\" stroke-width=\"1\" fill=\"none\"/>"
+        );
+    }
+}
@@ -0,0 +1,631 @@
+use roxmltree::Node;
+
+use super::{
+    bezier::Bezier,
+    circle::Circle,
+    ellipse::Ellipse,
+    line::Line,
+    polygon::Polygon,
+    rectangle::Rectangle,
+    shape::{Shape, ShapeState, ShapeType},
+    style::Style,
+    transform::Transform,
+};
+
+/// Shapes read from an SVG document.
+pub struct SvgImport {
+    pub shapes: Vec<Box<dyn Shape>>,
+    /// Elements that were skipped or couldn't be imported exactly.
+    pub warnings: Vec<String>,
+}
+
+/// Reads the supported subset of SVG, fails only if the text isn't an SVG document.
+pub fn import_svg(text: &str) -> Result<SvgImport, String> {
+    let document = roxmltree::Document::parse(text).map_err(|error| error.to_string())?;
+    let root = document.root_element();
+    if root.tag_name().name() != "svg" {
+        return Err(format!(
+            "Expected an <svg> document, found <{}>",
+            root.tag_name().name()
+        ));
+    }
+
+    let mut import = SvgImport {
+        shapes: Vec::new(),
+        warnings: Vec::new(),
+    };
+    import_children(root, viewbox_transform(root), &Paint::new(), &mut import);
+
+    Ok(import)
+}
+
+// presentation attributes inherited from the enclosing groups
+#[derive(Clone)]
+struct Paint {
+    stroke: Option<String>,
+    fill: Option<String>,
+    stroke_width: Option<f64>,
+    opacity: f64,
+}
+
+impl Paint {
+    fn new() -> Self {
+        Self {
+            stroke: None,
+            fill: None,
+            stroke_width: None,
+            opacity: 1.0,
+        }
+    }
+
+    fn inherit(&self, node: Node) -> Self {
+        let mut paint = self.clone();
+        if let Some(stroke) = attribute(node, "stroke") {
+            paint.stroke = Some(stroke);
+        }
+        if let Some(fill) = attribute(node, "fill") {
+            paint.fill = Some(fill);
+        }
+        if let Some(width) = attribute(node, "stroke-width").and_then(|width| parse_length(&width))
+        {
+            paint.stroke_width = Some(width);
+        }
+        if let Some(opacity) = attribute(node, "opacity").and_then(|opacity| opacity.parse().ok()) {
+            paint.opacity *= f64::clamp(opacity, 0.0, 1.0);
+        }
+
+        paint
+    }
+
+    fn to_style(&self, fillable: bool) -> Style {
+        let mut style = Style::new();
+        // SVG fills everything black unless told otherwise
+        style.fill_color = match self.fill.as_deref() {
+            _ if !fillable => None,
+            Some("none") => None,
+            Some(color) => Some(normalize_color(color)),
+            None => Some("#000000".to_string()),
+        };
+        // shapes here always have an outline, a missing one takes the fill color
+        style.stroke_color = match self.stroke.as_deref() {
+            Some("none") | None => style
+                .fill_color
+                .clone()
+                .unwrap_or_else(|| style.stroke_color.clone()),
+            Some(color) => normalize_color(color),
+        };
+        if let Some(width) = self.stroke_width {
+            style.stroke_width = width;
+        }
+        style.opacity = self.opacity;
+
+        style
+    }
+}
+
+fn import_children(node: Node, transform: Transform, paint: &Paint, import: &mut SvgImport) {
+    for child in node.children().filter(|child| child.is_element()) {
+        let name = child.tag_name().name();
+        let transform = match attribute(child, "transform").map(|text| parse_transform(&text)) {
+            Some(Ok(own)) => transform.multiply(&own),
+            Some(Err(error)) => {
+                import
+                    .warnings
+                    .push(format!("Skipped <{}>: {}", name, error));
+                continue;
+            }
+            None => transform,
+        };
+        let paint = paint.inherit(child);
+
+        let shapes = match name {
+            "g" | "a" | "svg" => {
+                import_children(child, transform, &paint, import);
+                continue;
+            }
+            // not rendered
+            "title" | "desc" | "metadata" | "defs" => continue,
+            "line" => import_line(child),
+            "rect" => import_rect(child, import),
+            "circle" => import_circle(child),
+            "ellipse" => import_ellipse(child),
+            "polyline" => import_poly(child, false),
+            "polygon" => import_poly(child, true),
+            "path" => import_path(child, import),
+            _ => Err("element isn't supported".to_string()),
+        };
+
+        match shapes {
+            Ok(shapes) => {
+                for mut shape in shapes {
+                    let fillable = shape.get_type() != ShapeType::Line;
+                    shape.set_transform(transform);
+                    shape.set_style(paint.to_style(fillable));
+                    import.shapes.push(shape);
+                }
+            }
+            Err(error) => import
+                .warnings
+                .push(format!("Skipped <{}>: {}", name, error)),
+        }
+    }
+}
+
+fn import_line(node: Node) -> Result<Vec<Box<dyn Shape>>, String> {
+    let mut line = Line::new();
+    line.init_from_points(
+        (length(node, "x1")?, length(node, "y1")?),
+        (length(node, "x2")?, length(node, "y2")?),
+    );
+
+    Ok(vec![complete(Box::new(line))])
+}
+
+fn import_rect(node: Node, import: &mut SvgImport) -> Result<Vec<Box<dyn Shape>>, String> {
+    let (x, y) = (length(node, "x")?, length(node, "y")?);
+    let (width, height) = (length(node, "width")?, length(node, "height")?);
+    if width <= 0.0 || height <= 0.0 {
+        return Err("it has no area".to_string());
+    }
+    if length(node, "rx")? > 0.0 || length(node, "ry")? > 0.0 {
+        import
+            .warnings
+            .push("Rounded corners of a <rect> were dropped".to_string());
+    }
+
+    let mut rectangle = Rectangle::new();
+    rectangle.init_from_points((x, y), (x + width, y + height));
+
+    Ok(vec![complete(Box::new(rectangle))])
+}
+
+fn import_circle(node: Node) -> Result<Vec<Box<dyn Shape>>, String> {
+    let (cx, cy) = (length(node, "cx")?, length(node, "cy")?);
+    let radius = length(node, "r")?;
+    if radius <= 0.0 {
+        return Err("it has no radius".to_string());
+    }
+
+    let mut circle = Circle::new();
+    circle.init_from_points((cx, cy), (cx + radius, cy));
+
+    Ok(vec![complete(Box::new(circle))])
+}
+
+fn import_ellipse(node: Node) -> Result<Vec<Box<dyn Shape>>, String> {
+    let (cx, cy) = (length(node, "cx")?, length(node, "cy")?);
+    let (rx, ry) = (length(node, "rx")?, length(node, "ry")?);
+    if rx <= 0.0 || ry <= 0.0 {
+        return Err("it has no radius".to_string());
+    }
+
+    let mut ellipse = Ellipse::new();
+    ellipse.init_from_points((cx, cy), (cx + rx, cy + ry));
+
+    Ok(vec![complete(Box::new(ellipse))])
+}
+
+fn import_poly(node: Node, closed: bool) -> Result<Vec<Box<dyn Shape>>, String> {
+    let numbers = parse_numbers(node.attribute("points").unwrap_or_default())?;
+    if numbers.len() % 2 != 0 {
+        return Err("it has an odd number of coordinates".to_string());
+    }
+    let points: Vec<(f64, f64)> = numbers.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+
+    Ok(vec![poly(&points, closed)?])
+}
+
+fn import_path(node: Node, import: &mut SvgImport) -> Result<Vec<Box<dyn Shape>>, String> {
+    let pieces = parse_path(node.attribute("d").unwrap_or_default())?;
+    if pieces.len() > 1 {
+        import
+            .warnings
+            .push(format!("A <path> was split into {} shapes", pieces.len()));
+    }
+
+    pieces
+        .into_iter()
+        .map(|piece| match piece {
+            PathPiece::Lines { points, closed } => poly(&points, closed),
+            PathPiece::Curve(points) => {
+                let mut bezier = Bezier::new();
+                for (x, y) in points {
+                    bezier.add_point(x, y);
+                }
+                Ok(complete(Box::new(bezier)))
+            }
+        })
+        .collect()
+}
+
+fn poly(points: &[(f64, f64)], closed: bool) -> Result<Box<dyn Shape>, String> {
+    let min_points = if closed { 3 } else { 2 };
+    if points.len() < min_points {
+        return Err(format!("it needs at least {} points", min_points));
+    }
+
+    let mut polygon = Polygon::new(closed);
+    for (x, y) in points {
+        polygon.add_point(*x, *y);
+    }
+
+    Ok(complete(Box::new(polygon)))
+}
+
+// skips `finish`, which would merge repeated points the file may rely on
+fn complete(mut shape: Box<dyn Shape>) -> Box<dyn Shape> {
+    shape.set_state(ShapeState::Complete);
+    shape
+}
+
+/// Part of a path that maps onto one of our shapes.
+#[derive(Debug, PartialEq)]
+enum PathPiece {
+    Lines {
+        points: Vec<(f64, f64)>,
+        closed: bool,
+    },
+    Curve(Vec<(f64, f64)>),
+}
+
+// splits path data into runs of straight segments and single Bézier curves
+fn parse_path(data: &str) -> Result<Vec<PathPiece>, String> {
+    let mut pieces = Vec::new();
+    let mut run: Vec<(f64, f64)> = Vec::new();
+    let mut only_lines = true;
+    let (mut current, mut start) = ((0.0, 0.0), (0.0, 0.0));
+
+    let flush = |run: &mut Vec<(f64, f64)>, pieces: &mut Vec<PathPiece>| {
+        if run.len() >= 2 {
+            pieces.push(PathPiece::Lines {
+                points: run.clone(),
+                closed: false,
+            });
+        }
+        run.clear();
+    };
+
+    for (command, numbers) in tokenize_path(data)? {
+        let relative = command.is_ascii_lowercase();
+        let offset = |point: (f64, f64), current: (f64, f64)| {
+            if relative {
+                (current.0 + point.0, current.1 + point.1)
+            } else {
+                point
+            }
+        };
+        let arity = match command.to_ascii_uppercase() {
+            'M' | 'L' => 2,
+            'H' | 'V' => 1,
+            'Q' => 4,
+            'C' => 6,
+            'Z' => 0,
+            other => return Err(format!("path command '{}' isn't supported", other)),
+        };
+        if arity == 0 {
+            if !numbers.is_empty() {
+                return Err("'Z' takes no coordinates".to_string());
+            }
+        } else if numbers.is_empty() || numbers.len() % arity != 0 {
+            return Err(format!("wrong number of coordinates after '{}'", command));
+        }
+
+        match command.to_ascii_uppercase() {
+            'M' => {
+                flush(&mut run, &mut pieces);
+                for (i, pair) in numbers.chunks(2).enumerate() {
+                    current = offset((pair[0], pair[1]), current);
+                    // coordinates following the first pair are implicit line-tos
+                    if i == 0 {
+                        start = current;
+                        only_lines = true;
+                    }
+                    run.push(current);
+                }
+            }
+            'L' | 'H' | 'V' => {
+                for values in numbers.chunks(arity) {
+                    current = match command {
+                        'L' | 'l' => offset((values[0], values[1]), current),
+                        'H' => (values[0], current.1),
+                        'h' => (current.0 + values[0], current.1),
+                        'V' => (current.0, values[0]),
+                        _ => (current.0, current.1 + values[0]),
+                    };
+                    if run.is_empty() {
+                        run.push(start);
+                    }
+                    run.push(current);
+                }
+            }
+            'Q' | 'C' => {
+                for values in numbers.chunks(arity) {
+                    flush(&mut run, &mut pieces);
+                    let mut points = vec![current];
+                    for pair in values.chunks(2) {
+                        points.push(offset((pair[0], pair[1]), current));
+                    }
+                    current = *points.last().unwrap();
+                    pieces.push(PathPiece::Curve(points));
+                    only_lines = false;
+                }
+                run.push(current);
+            }
+            _ => {
+                if only_lines && run.len() >= 3 {
+                    pieces.push(PathPiece::Lines {
+                        points: run.clone(),
+                        closed: true,
+                    });
+                    run.clear();
+                } else {
+                    run.push(start);
+                    flush(&mut run, &mut pieces);
+                }
+                current = start;
+                run.push(current);
+            }
+        }
+    }
+    flush(&mut run, &mut pieces);
+
+    Ok(pieces)
+}
+
+fn tokenize_path(data: &str) -> Result<Vec<(char, Vec<f64>)>, String> {
+    let mut commands: Vec<(char, Vec<f64>)> = Vec::new();
+    let mut number = String::new();
+
+    let push_number = |number: &mut String, commands: &mut Vec<(char, Vec<f64>)>| {
+        if number.is_empty() {
+            return Ok(());
+        }
+        let value: f64 = number
+            .parse()
+            .map_err(|_| format!("'{}' isn't a number", number))?;
+        number.clear();
+        match commands.last_mut() {
+            Some((_, numbers)) => {
+                numbers.push(value);
+                Ok(())
+            }
+            None => Err("path data must start with a command".to_string()),
+        }
+    };
+
+    for c in data.chars() {
+        match c {
+            c if c.is_ascii_alphabetic() && c != 'e' && c != 'E' => {
+                push_number(&mut number, &mut commands)?;
+                commands.push((c, Vec::new()));
+            }
+            '0'..='9' | 'e' | 'E' => number.push(c),
+            // a sign starts a new number unless it belongs to an exponent
+            '-' | '+' if !(number.ends_with('e') || number.ends_with('E')) => {
+                push_number(&mut number, &mut commands)?;
+                number.push(c);
+            }
+            '-' | '+' => number.push(c),
+            // a second decimal point starts a new number, as in "0.5.5"
+            '.' if number.contains('.') => {
+                push_number(&mut number, &mut commands)?;
+                number.push(c);
+            }
+            '.' => number.push(c),
+            c if c == ',' || c.is_whitespace() => push_number(&mut number, &mut commands)?,
+            c => return Err(format!("unexpected '{}' in path data", c)),
+        }
+    }
+    push_number(&mut number, &mut commands)?;
+
+    Ok(commands)
+}
+
+fn parse_transform(text: &str) -> Result<Transform, String> {
+    let mut transform = Transform::identity();
+    for part in text
+        .split(')')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let (name, arguments) = part
+            .trim_start_matches(',')
+            .split_once('(')
+            .ok_or_else(|| format!("malformed transform '{}'", text))?;
+        let values = parse_numbers(arguments)?;
+        let next = match (name.trim(), values.as_slice()) {
+            ("matrix", [a, b, c, d, e, f]) => Transform::from_canvas(*a, *b, *c, *d, *e, *f),
+            ("translate", [x]) => Transform::translation(*x, 0.0),
+            ("translate", [x, y]) => Transform::translation(*x, *y),
+            ("scale", [factor]) => Transform::scaling(*factor, *factor),
+            ("scale", [x, y]) => Transform::scaling(*x, *y),
+            ("rotate", [angle]) => Transform::rotation(angle.to_radians()),
+            ("rotate", [angle, x, y]) => {
+                Transform::around(Transform::rotation(angle.to_radians()), (*x, *y))
+            }
+            ("skewX", [angle]) => Transform::skew(angle.to_radians(), 0.0),
+            ("skewY", [angle]) => Transform::skew(0.0, angle.to_radians()),
+            (name, _) => return Err(format!("malformed transform '{}'", name)),
+        };
+        // the list reads outermost first
+        transform = transform.multiply(&next);
+    }
+
+    Ok(transform)
+}
+
+// maps the viewBox onto the width and height of the document
+fn viewbox_transform(root: Node) -> Transform {
+    let viewbox = match root.attribute("viewBox").map(parse_numbers) {
+        Some(Ok(values)) if values.len() == 4 && values[2] > 0.0 && values[3] > 0.0 => values,
+        _ => return Transform::identity(),
+    };
+    let size = |name: &str, fallback: f64| {
+        root.attribute(name)
+            .and_then(parse_length)
+            .unwrap_or(fallback)
+    };
+    let (width, height) = (size("width", viewbox[2]), size("height", viewbox[3]));
+
+    Transform::scaling(width / viewbox[2], height / viewbox[3])
+        .multiply(&Transform::translation(-viewbox[0], -viewbox[1]))
+}
+
+fn parse_numbers(text: &str) -> Result<Vec<f64>, String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("'{}' isn't a number", value))
+        })
+        .collect()
+}
+
+fn parse_length(text: &str) -> Option<f64> {
+    text.trim().trim_end_matches("px").parse().ok()
+}
+
+// missing geometry attributes default to 0, as in SVG
+fn length(node: Node, name: &str) -> Result<f64, String> {
+    match node.attribute(name) {
+        Some(value) => {
+            parse_length(value).ok_or_else(|| format!("unsupported {} '{}'", name, value))
+        }
+        None => Ok(0.0),
+    }
+}
+
+// the style attribute wins over presentation attributes
+fn attribute(node: Node, name: &str) -> Option<String> {
+    let from_style = node.attribute("style").and_then(|style| {
+        style
+            .split(';')
+            .filter_map(|declaration| declaration.split_once(':'))
+            .find(|(key, _)| key.trim() == name)
+            .map(|(_, value)| value.trim().to_string())
+    });
+
+    from_style.or_else(|| node.attribute(name).map(|value| value.trim().to_string()))
+}
+
+// color inputs only understand #rrggbb
+fn normalize_color(color: &str) -> String {
+    let color = color.to_lowercase();
+    match color.strip_prefix('#') {
+        Some(hex) if hex.len() == 3 => {
+            let doubled: String = hex.chars().flat_map(|c| [c, c]).collect();
+            format!("#{}", doubled)
+        }
+        _ => color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::svg::export_svg;
+
+    fn round_trip(svg: &str) -> String {
+        let import = import_svg(svg).unwrap();
+        assert!(import.warnings.is_empty(), "{:?}", import.warnings);
+        export_svg(
+            import.shapes.iter().map(|shape| shape.as_ref()),
+            800.0,
+            600.0,
+        )
+    }
+
+    #[test]
+    fn reads_back_exported_files() {
+        for golden in [
+            include_str!("../../tests/golden/basic_shapes.svg"),
+            include_str!("../../tests/golden/polygons_and_curves.svg"),
+            include_str!("../../tests/golden/style_and_transform.svg"),
+            include_str!("../../tests/golden/empty.svg"),
+        ] {
+            assert_eq!(round_trip(golden), golden);
+        }
+    }
+
+    #[test]
+    fn splits_paths_into_lines_and_curves() {
+        assert_eq!(
+            parse_path("M10,10 l10-10h5 Q 30,0 30,10 Z m 0,10 10,0 0,10 z").unwrap(),
+            vec![
+                PathPiece::Lines {
+                    points: vec![(10.0, 10.0), (20.0, 0.0), (25.0, 0.0)],
+                    closed: false,
+                },
+                PathPiece::Curve(vec![(25.0, 0.0), (30.0, 0.0), (30.0, 10.0)]),
+                PathPiece::Lines {
+                    points: vec![(30.0, 10.0), (10.0, 10.0)],
+                    closed: false,
+                },
+                PathPiece::Lines {
+                    points: vec![(10.0, 20.0), (20.0, 20.0), (20.0, 30.0)],
+                    closed: true,
+                },
+            ]
+        );
+        assert_eq!(
+            parse_path("M0 0C1.5.5-1e1 2 3 4").unwrap(),
+            vec![PathPiece::Curve(vec![
+                (0.0, 0.0),
+                (1.5, 0.5),
+                (-10.0, 2.0),
+                (3.0, 4.0)
+            ])]
+        );
+    }
+
+    #[test]
+    fn applies_group_transforms_and_inherited_style() {
+        let import = import_svg(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 400 300" width="800" height="600">
+                <g transform="translate(10 20)" stroke="#F00" style="fill: none">
+                    <circle cx="5" cy="5" r="5" transform="scale(2)"/>
+                </g>
+            </svg>"##,
+        )
+        .unwrap();
+
+        assert_eq!(import.shapes.len(), 1);
+        let circle = &import.shapes[0];
+        assert_eq!(circle.get_type(), ShapeType::Circle);
+        assert_eq!(circle.get_style().stroke_color, "#ff0000");
+        assert_eq!(circle.get_style().fill_color, None);
+        // viewBox scale, then the group's translation, then the circle's own scaling
+        assert_eq!(circle.get_transform().apply((5.0, 5.0)), (40.0, 60.0));
+    }
+
+    #[test]
+    fn reports_what_it_cannot_import() {
+        let import = import_svg(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <text x="0" y="0">Hello</text>
+                <rect width="10" height="10" rx="2"/>
+                <path d="M 0 0 A 5 5 0 0 1 10 10"/>
+                <circle r="50%"/>
+            </svg>"#,
+        )
+        .unwrap();
+
+        assert_eq!(import.shapes.len(), 1);
+        assert_eq!(
+            import.warnings,
+            vec![
+                "Skipped <text>: element isn't supported",
+                "Rounded corners of a <rect> were dropped",
+                "Skipped <path>: path command 'A' isn't supported",
+                "Skipped <circle>: unsupported r '50%'",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_documents_that_are_not_svg() {
+        assert!(import_svg("[]").is_err());
+        assert!(import_svg("<html></html>").is_err());
+    }
+}
@@ -0,0 +1,12 @@
+//! Fixtures shared by the model's tests.
+
+use super::shape::Shape;
+
+/// Adds `points` to a new shape as clicks on the canvas, then finishes it.
+pub fn drawn(mut shape: Box<dyn Shape>, points: &[(f64, f64)]) -> Box<dyn Shape> {
+    for (x, y) in points {
+        shape.add_point(*x, *y);
+    }
+    shape.finish();
+    shape
+}
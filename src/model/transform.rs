@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+
+/// 2D affine transform stored as a homogeneous 3x3 matrix acting on column vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Transform {
+    matrix: [[f64; 3]; 3],
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    pub fn translation(x: f64, y: f64) -> Self {
+        Self {
+            matrix: [[1.0, 0.0, x], [0.0, 1.0, y], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    /// Rotation by `angle` radians, clockwise on screen.
+    pub fn rotation(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self {
+            matrix: [[cos, -sin, 0.0], [sin, cos, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    pub fn scaling(x: f64, y: f64) -> Self {
+        Self {
+            matrix: [[x, 0.0, 0.0], [0.0, y, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    /// Skew by `x` radians along the x axis and `y` radians along the y axis.
+    pub fn skew(x: f64, y: f64) -> Self {
+        Self {
+            matrix: [[1.0, x.tan(), 0.0], [y.tan(), 1.0, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    /// Conjugates `transform` so that it keeps `pivot` in place.
+    pub fn around(transform: Transform, pivot: (f64, f64)) -> Self {
+        Transform::translation(pivot.0, pivot.1)
+            .multiply(&transform)
+            .multiply(&Transform::translation(-pivot.0, -pivot.1))
+    }
+
+    /// Matrix product `self * other`, i.e. `other` is applied first.
+    pub fn multiply(&self, other: &Transform) -> Self {
+        let mut matrix = [[0.0; 3]; 3];
+        for (row, values) in matrix.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                *value = (0..3)
+                    .map(|k| self.matrix[row][k] * other.matrix[k][col])
+                    .sum();
+            }
+        }
+
+        Self { matrix }
+    }
+
+    /// Scale factor of a transform that keeps circles round, made of rotations, reflections,
+    /// uniform scaling and translations only.
+    pub fn uniform_scale(&self) -> Option<f64> {
+        let (a, b, c, d, _, _) = self.to_canvas();
+        let epsilon = 1e-9;
+        let rotation = (a - d).abs() < epsilon && (b + c).abs() < epsilon;
+        let reflection = (a + d).abs() < epsilon && (b - c).abs() < epsilon;
+        if rotation || reflection {
+            return Some((a * a + b * b).sqrt());
+        }
+
+        None
+    }
+
+    /// The most the transform stretches any length, its largest singular value.
+    pub fn max_scale(&self) -> f64 {
+        let (a, b, c, d, _, _) = self.to_canvas();
+        // eigenvalues of the transposed matrix times the matrix
+        let sum = a * a + b * b + c * c + d * d;
+        let determinant = a * d - b * c;
+        let root = (sum * sum - 4.0 * determinant * determinant)
+            .max(0.0)
+            .sqrt();
+        ((sum + root) / 2.0).sqrt()
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let m = &self.matrix;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let m = &self.matrix;
+        let cofactor = |r1: usize, r2: usize, c1: usize, c2: usize| {
+            m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]
+        };
+        // transposed matrix of cofactors divided by the determinant
+        let matrix = [
+            [
+                cofactor(1, 2, 1, 2) / det,
+                -cofactor(0, 2, 1, 2) / det,
+                cofactor(0, 1, 1, 2) / det,
+            ],
+            [
+                -cofactor(1, 2, 0, 2) / det,
+                cofactor(0, 2, 0, 2) / det,
+                -cofactor(0, 1, 0, 2) / det,
+            ],
+            [
+                cofactor(1, 2, 0, 1) / det,
+                -cofactor(0, 2, 0, 1) / det,
+                cofactor(0, 1, 0, 1) / det,
+            ],
+        ];
+
+        Some(Self { matrix })
+    }
+
+    pub fn apply(&self, p: (f64, f64)) -> (f64, f64) {
+        let m = &self.matrix;
+        let x = m[0][0] * p.0 + m[0][1] * p.1 + m[0][2];
+        let y = m[1][0] * p.0 + m[1][1] * p.1 + m[1][2];
+        let w = m[2][0] * p.0 + m[2][1] * p.1 + m[2][2];
+
+        (x / w, y / w)
+    }
+
+    /// Applies only the linear part, for directions and distances.
+    pub fn apply_vector(&self, v: (f64, f64)) -> (f64, f64) {
+        let m = &self.matrix;
+        (m[0][0] * v.0 + m[0][1] * v.1, m[1][0] * v.0 + m[1][1] * v.1)
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.matrix[row][col]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, value: f64) {
+        self.matrix[row][col] = value;
+    }
+
+    /// Reverse of `to_canvas`, also the order of SVG's `matrix(a b c d e f)`.
+    pub fn from_canvas(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Self {
+        Self {
+            matrix: [[a, c, e], [b, d, f], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    /// Arguments of `CanvasRenderingContext2d::set_transform`.
+    pub fn to_canvas(self) -> (f64, f64, f64, f64, f64, f64) {
+        let m = &self.matrix;
+        (m[0][0], m[1][0], m[0][1], m[1][1], m[0][2], m[1][2])
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn assert_close(a: (f64, f64), b: (f64, f64)) {
+        assert!(
+            (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn applies_the_right_factor_of_a_product_first() {
+        let scale_then_move =
+            Transform::translation(10.0, 0.0).multiply(&Transform::scaling(2.0, 2.0));
+        assert_close(scale_then_move.apply((1.0, 1.0)), (12.0, 2.0));
+        let move_then_scale =
+            Transform::scaling(2.0, 2.0).multiply(&Transform::translation(10.0, 0.0));
+        assert_close(move_then_scale.apply((1.0, 1.0)), (22.0, 2.0));
+        assert_eq!(
+            scale_then_move.multiply(&Transform::identity()),
+            scale_then_move
+        );
+    }
+
+    #[test]
+    fn inverts_only_regular_matrices() {
+        let transform = Transform::translation(3.0, -4.0)
+            .multiply(&Transform::rotation(0.5))
+            .multiply(&Transform::scaling(2.0, 0.5));
+        let inverse = transform.inverse().unwrap();
+        assert_close(inverse.apply(transform.apply((7.0, 11.0))), (7.0, 11.0));
+
+        assert!(Transform::scaling(0.0, 1.0).inverse().is_none());
+        assert!(Transform::from_canvas(1.0, 2.0, 2.0, 4.0, 5.0, 6.0)
+            .inverse()
+            .is_none());
+    }
+
+    #[test]
+    fn keeps_the_pivot_in_place() {
+        let transform = Transform::around(Transform::rotation(FRAC_PI_2), (10.0, 20.0));
+        assert_close(transform.apply((10.0, 20.0)), (10.0, 20.0));
+        assert_close(transform.apply((15.0, 20.0)), (10.0, 25.0));
+    }
+
+    #[test]
+    fn measures_the_scale() {
+        let rotated = Transform::rotation(0.3).multiply(&Transform::scaling(2.0, 2.0));
+        assert!((rotated.uniform_scale().unwrap() - 2.0).abs() < 1e-9);
+        assert!((rotated.max_scale() - 2.0).abs() < 1e-9);
+
+        let reflected = Transform::scaling(-3.0, 3.0);
+        assert_eq!(reflected.uniform_scale(), Some(3.0));
+
+        let stretched = Transform::scaling(2.0, 5.0);
+        assert_eq!(stretched.uniform_scale(), None);
+        assert!((stretched.max_scale() - 5.0).abs() < 1e-9);
+        assert_eq!(Transform::skew(0.5, 0.0).uniform_scale(), None);
+    }
+
+    #[test]
+    fn round_trips_canvas_arguments() {
+        let arguments = (1.0, 2.0, 3.0, 4.0, 5.0, 6.0);
+        let (a, b, c, d, e, f) = arguments;
+        let transform = Transform::from_canvas(a, b, c, d, e, f);
+        assert_eq!(transform.to_canvas(), arguments);
+        // x' = a x + c y + e, y' = b x + d y + f
+        assert_close(transform.apply((1.0, 1.0)), (9.0, 12.0));
+    }
+}
@@ -0,0 +1,157 @@
+use super::transform::Transform;
+
+pub const MIN_ZOOM: f64 = 0.1;
+pub const MAX_ZOOM: f64 = 32.0;
+
+/// The part of the drawing shown on screen: shapes live in world coordinates, scaled by `zoom`
+/// and then shifted by `offset` screen pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub offset: (f64, f64),
+    pub zoom: f64,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            offset: (0.0, 0.0),
+            zoom: 1.0,
+        }
+    }
+}
+
+impl Viewport {
+    /// Maps world coordinates onto the screen.
+    pub fn transform(&self) -> Transform {
+        Transform::translation(self.offset.0, self.offset.1)
+            .multiply(&Transform::scaling(self.zoom, self.zoom))
+    }
+
+    pub fn to_world(self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            (x - self.offset.0) / self.zoom,
+            (y - self.offset.1) / self.zoom,
+        )
+    }
+
+    pub fn to_screen(self, (x, y): (f64, f64)) -> (f64, f64) {
+        (x * self.zoom + self.offset.0, y * self.zoom + self.offset.1)
+    }
+
+    /// Length in world coordinates of `pixels` on the screen.
+    pub fn to_world_length(self, pixels: f64) -> f64 {
+        pixels / self.zoom
+    }
+
+    /// Multiplies the zoom by `factor`, within `MIN_ZOOM` and `MAX_ZOOM`, keeping the world
+    /// point under `anchor` on the screen in place.
+    pub fn zoom_at(&mut self, anchor: (f64, f64), factor: f64) {
+        let world = self.to_world(anchor);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset = (
+            anchor.0 - world.0 * self.zoom,
+            anchor.1 - world.1 * self.zoom,
+        );
+    }
+
+    /// Moves the view by screen pixels.
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        self.offset = (self.offset.0 + dx, self.offset.1 + dy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        fill::Fill,
+        line::Line,
+        raster::{to_pixel, Connectivity, Framebuffer},
+        rectangle::Rectangle,
+        shape::{Shape, HIT_TOLERANCE},
+        test_util::drawn,
+    };
+
+    fn assert_near(a: (f64, f64), b: (f64, f64)) {
+        assert!(
+            (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn converts_between_screen_and_world() {
+        let viewport = Viewport {
+            offset: (100.0, -50.0),
+            zoom: 2.0,
+        };
+
+        assert_near(viewport.to_screen((10.0, 20.0)), (120.0, -10.0));
+        assert_near(viewport.to_world((120.0, -10.0)), (10.0, 20.0));
+        assert_near(viewport.transform().apply((10.0, 20.0)), (120.0, -10.0));
+        assert_eq!(viewport.to_world_length(5.0), 2.5);
+    }
+
+    #[test]
+    fn zooms_around_the_anchor() {
+        let mut viewport = Viewport::default();
+        let anchor = (300.0, 200.0);
+        let world = viewport.to_world(anchor);
+
+        viewport.zoom_at(anchor, 4.0);
+        assert_eq!(viewport.zoom, 4.0);
+        assert_near(viewport.to_world(anchor), world);
+
+        viewport.pan(10.0, 0.0);
+        viewport.zoom_at((0.0, 0.0), 0.5);
+        assert_near(viewport.to_screen(world), (155.0, 100.0));
+
+        viewport.zoom_at(anchor, 1000.0);
+        assert_eq!(viewport.zoom, MAX_ZOOM);
+        viewport.zoom_at(anchor, 0.0);
+        assert_eq!(viewport.zoom, MIN_ZOOM);
+    }
+
+    #[test]
+    fn keeps_hit_tolerance_constant_on_screen() {
+        let mut line = Line::new();
+        line.add_point(0.0, 0.0);
+        line.add_point(100.0, 0.0);
+
+        // 4 and 8 screen pixels off the line
+        let mut viewport = Viewport::default();
+        viewport.zoom_at((0.0, 0.0), 4.0);
+        let tolerance = viewport.to_world_length(HIT_TOLERANCE);
+        assert!(line.contains(50.0, 1.0, tolerance));
+        assert!(!line.contains(50.0, 2.0, tolerance));
+
+        viewport.zoom_at((0.0, 0.0), 0.125);
+        let tolerance = viewport.to_world_length(HIT_TOLERANCE);
+        assert!(line.contains(50.0, 8.0, tolerance));
+        assert!(!line.contains(50.0, 16.0, tolerance));
+    }
+
+    #[test]
+    fn places_regions_flooded_on_the_screen_in_the_world() {
+        let viewport = Viewport {
+            offset: (-20.0, 10.0),
+            zoom: 4.0,
+        };
+        let mut rectangle = drawn(Box::new(Rectangle::new()), &[(10.0, 0.0), (15.0, 5.0)]);
+        rectangle.apply_transform(viewport.transform());
+        let mut framebuffer = Framebuffer::new(64, 64);
+        rectangle.rasterize(&mut framebuffer, rectangle.get_style().stroke_rgba(), false);
+
+        let seed = to_pixel(viewport.to_screen((12.5, 2.5)));
+        let pixels = framebuffer.flood_region(seed, 0, Connectivity::Four);
+        let mut fill = Fill::from_pixels(&pixels, "#00ff00");
+        fill.set_transform(viewport.transform().inverse().unwrap());
+
+        // inside the outline drawn on the screen pixels from 20 to 40 and 10 to 30
+        assert_eq!(fill.get_bounds(), Some((10.25, 0.25, 15.0, 5.0)));
+        assert!(fill.contains(12.5, 2.5, HIT_TOLERANCE));
+        assert!(!fill.contains(16.0, 2.5, HIT_TOLERANCE));
+    }
+}